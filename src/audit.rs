@@ -0,0 +1,116 @@
+use crate::config::CargoConfig;
+use crate::metadata::CargoMetadata;
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml_edit::{value, DocumentMut, InlineTable, Item, Table};
+
+/// 补丁版本相对已发布版本新增的传递依赖
+#[derive(Debug, Serialize)]
+pub struct DependencyAudit {
+    pub crate_name: String,
+    pub version: String,
+    pub source: String,
+    pub added: Vec<AuditedDependency>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditedDependency {
+    pub name: String,
+    pub version: String,
+}
+
+/// 比较本地克隆与已发布版本的传递依赖
+pub fn audit(crate_name: &str, source: &str, patch_path: &Path) -> Result<DependencyAudit> {
+    info!("🔎 Resolving dependencies of the local clone...");
+    let patched_metadata = CargoMetadata::load_manifest(&patch_path.join("Cargo.toml"))?;
+    let root = patched_metadata
+        .packages
+        .iter()
+        .find(|p| p.name == crate_name)
+        .ok_or_else(|| anyhow!("'{}' not found in {}", crate_name, patch_path.display()))?;
+    let version = root.version.clone();
+    let patched = patched_metadata
+        .transitive_dependencies(crate_name)
+        .unwrap_or_default();
+
+    info!("🔎 Resolving dependencies of the published {crate_name} v{version}...");
+    let manifest = published_manifest(crate_name, &version, source)?;
+    let published = CargoMetadata::load_manifest(&manifest)?
+        .transitive_dependencies(crate_name)
+        .unwrap_or_default();
+
+    let added = patched
+        .into_iter()
+        .filter(|(name, _)| !published.contains_key(name))
+        .map(|(name, version)| AuditedDependency { name, version })
+        .collect();
+
+    Ok(DependencyAudit {
+        crate_name: crate_name.to_string(),
+        version,
+        source: source.to_string(),
+        added,
+    })
+}
+
+/// 在临时目录创建只依赖已发布版本的空项目，返回其 `Cargo.toml` 路径
+///
+/// 项目放在系统临时目录，避免继承当前项目 `.cargo/config.toml` 中的补丁
+fn published_manifest(crate_name: &str, version: &str, source: &str) -> Result<PathBuf> {
+    let dir = std::env::temp_dir().join(format!("cargo-lpatch-audit-{crate_name}"));
+    fs::create_dir_all(dir.join("src"))
+        .with_context(|| format!("Failed to create {}", dir.display()))?;
+    fs::write(dir.join("src").join("lib.rs"), "")?;
+
+    let mut dependency = InlineTable::new();
+    if source.contains("://") || source.contains("git@") {
+        dependency.insert("git", source.into());
+    } else {
+        dependency.insert("version", format!("={version}").into());
+        if source != "crates-io" {
+            dependency.insert("registry", source.into());
+            write_registry_config(&dir, source)?;
+        }
+    }
+
+    let mut package = Table::new();
+    package.insert("name", value(format!("lpatch-audit-{crate_name}")));
+    package.insert("version", value("0.0.0"));
+    package.insert("edition", value("2021"));
+    package.insert("publish", value(false));
+    let mut dependencies = Table::new();
+    dependencies.insert(crate_name, value(dependency));
+
+    let mut doc = DocumentMut::new();
+    doc.insert("package", Item::Table(package));
+    doc.insert("dependencies", Item::Table(dependencies));
+    // 空的 [workspace] 使临时项目不会被识别为外层 workspace 的成员
+    doc.insert("workspace", Item::Table(Table::new()));
+
+    let manifest = dir.join("Cargo.toml");
+    fs::write(&manifest, doc.to_string())
+        .with_context(|| format!("Failed to write {}", manifest.display()))?;
+    Ok(manifest)
+}
+
+/// 临时项目读不到当前项目的 registry 配置，需要单独写入 index
+fn write_registry_config(dir: &Path, registry: &str) -> Result<()> {
+    let index = CargoConfig::registry_index(registry)?
+        .ok_or_else(|| anyhow!("Registry '{}' has no index configured", registry))?;
+
+    let mut entry = Table::new();
+    entry.insert("index", value(index));
+    let mut registries = Table::new();
+    registries.set_implicit(true);
+    registries.insert(registry, Item::Table(entry));
+    let mut doc = DocumentMut::new();
+    doc.insert("registries", Item::Table(registries));
+
+    let config_dir = dir.join(".cargo");
+    fs::create_dir_all(&config_dir)?;
+    fs::write(config_dir.join("config.toml"), doc.to_string())
+        .with_context(|| format!("Failed to write {}", config_dir.display()))?;
+    Ok(())
+}
@@ -0,0 +1,94 @@
+use anyhow::{anyhow, Context, Result};
+use semver::{BuildMetadata, Prerelease, Version};
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use toml_edit::{DocumentMut, Item};
+
+/// 要递增的版本号部分
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bump {
+    Patch,
+    Minor,
+    Major,
+}
+
+impl Bump {
+    pub fn parse(s: &str) -> Self {
+        match s {
+            "major" => Bump::Major,
+            "minor" => Bump::Minor,
+            _ => Bump::Patch,
+        }
+    }
+
+    /// 按 semver 规则递增，较低的部分归零，预发布和构建元数据被清除
+    pub fn apply(self, version: &Version) -> Version {
+        let mut next = match self {
+            Bump::Patch => Version::new(version.major, version.minor, version.patch + 1),
+            Bump::Minor => Version::new(version.major, version.minor + 1, 0),
+            Bump::Major => Version::new(version.major + 1, 0, 0),
+        };
+        next.pre = Prerelease::EMPTY;
+        next.build = BuildMetadata::EMPTY;
+        next
+    }
+}
+
+/// 一次版本号修改
+#[derive(Debug, Serialize)]
+pub struct VersionBump {
+    pub crate_name: String,
+    pub old_version: String,
+    pub new_version: String,
+}
+
+/// 递增 `Cargo.toml` 中 `[package].version`，保留文件其余内容和格式
+pub fn bump_manifest(manifest_path: &Path, crate_name: &str, bump: Bump) -> Result<VersionBump> {
+    let content = fs::read_to_string(manifest_path).with_context(|| {
+        format!(
+            "Failed to read Cargo.toml file: {}",
+            manifest_path.display()
+        )
+    })?;
+    let mut doc: DocumentMut = content.parse().with_context(|| {
+        format!(
+            "Failed to parse Cargo.toml file: {}",
+            manifest_path.display()
+        )
+    })?;
+
+    let version_item = doc
+        .get_mut("package")
+        .and_then(|package| package.get_mut("version"))
+        .ok_or_else(|| anyhow!("{} has no [package].version", manifest_path.display()))?;
+    let old = version_item.as_str().ok_or_else(|| {
+        anyhow!(
+            "[package].version in {} is not a string (inherited from the workspace?)",
+            manifest_path.display()
+        )
+    })?;
+    let old = Version::parse(old)
+        .with_context(|| format!("Invalid version '{}' in {}", old, manifest_path.display()))?;
+    let new = bump.apply(&old);
+
+    // 只替换值本身，保留原有的注释和空白
+    let decor = version_item.as_value().map(|v| v.decor().clone());
+    *version_item = Item::Value(new.to_string().into());
+    if let (Some(decor), Some(value)) = (decor, version_item.as_value_mut()) {
+        *value.decor_mut() = decor;
+    }
+
+    fs::write(manifest_path, doc.to_string()).with_context(|| {
+        format!(
+            "Failed to write Cargo.toml file: {}",
+            manifest_path.display()
+        )
+    })?;
+
+    Ok(VersionBump {
+        crate_name: crate_name.to_string(),
+        old_version: old.to_string(),
+        new_version: new.to_string(),
+    })
+}
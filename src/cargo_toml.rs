@@ -5,29 +5,108 @@ use std::fs;
 use std::path::{Path, PathBuf};
 
 /// 表示一个依赖的信息
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct DependencyInfo {
     pub name: String,
+    #[serde(flatten)]
     pub dep_type: DependencyType,
+    /// 声明依赖的表
+    pub section: DependencySection,
+    /// 来自 `[target.'cfg(...)'.dependencies]` 时对应的 target 表达式
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_cfg: Option<String>,
+    /// 是否为 `optional = true` 的可选依赖
+    pub optional: bool,
 }
 
-/// 依赖类型
-#[derive(Debug, Clone)]
+/// 依赖所在的表，平台相关依赖按 `[target.<cfg>]` 下的同名表归类
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DependencySection {
+    /// `[dependencies]`
+    #[default]
+    Normal,
+    /// `[build-dependencies]`
+    Build,
+    /// `[dev-dependencies]`
+    Dev,
+}
+
+impl DependencySection {
+    pub fn parse(s: &str) -> Self {
+        match s {
+            "dev" => DependencySection::Dev,
+            "build" => DependencySection::Build,
+            _ => DependencySection::Normal,
+        }
+    }
+
+    /// Cargo.toml 中对应的表名
+    pub fn table_name(self) -> &'static str {
+        match self {
+            DependencySection::Normal => "dependencies",
+            DependencySection::Build => "build-dependencies",
+            DependencySection::Dev => "dev-dependencies",
+        }
+    }
+}
+
+/// 依赖类型，序列化时以 `"type": "version" | "git" | "path"` 区分
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
 pub enum DependencyType {
-    /// 来自 crates.io 的版本依赖
-    Version { version: String },
+    /// 来自 crates.io 或 `registry` 指定的其他 registry 的版本依赖
+    Version {
+        version: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        registry: Option<String>,
+    },
     /// 来自 git 仓库的依赖
     Git {
         git: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
         branch: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         tag: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         rev: Option<String>,
     },
     /// 本地路径依赖
     Path { path: String },
 }
 
+impl DependencyType {
+    /// 为该依赖打补丁时使用的 patch 源，路径依赖已经是本地的，没有 patch 源
+    pub fn patch_source(&self) -> Option<String> {
+        match self {
+            DependencyType::Git { git, .. } => Some(patch_source(Some(git), None)),
+            DependencyType::Version { registry, .. } => {
+                Some(patch_source(None, registry.as_deref()))
+            }
+            DependencyType::Path { .. } => None,
+        }
+    }
+}
+
+/// 选择 `[patch.<source>]` 的源：git URL > registry 名称 > crates-io
+///
+/// 与依赖声明不一致的 patch 源会被 cargo 静默忽略
+pub fn patch_source(git_url: Option<&str>, registry: Option<&str>) -> String {
+    git_url.or(registry).unwrap_or("crates-io").to_string()
+}
+
+/// `[replace]` 表中的一项替换
+#[derive(Debug, Clone)]
+pub struct Replacement {
+    /// 原始的 package id spec，例如 `foo:0.1.0`
+    pub spec: String,
+    /// 替换目标，`name` 为从 spec 中解析出的 crate 名称
+    pub target: DependencyInfo,
+}
+
 /// 依赖的完整定义（用于解析 TOML）
+// 只在解析 TOML 时短暂存在，不值得为 Detailed 额外装箱
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(untagged)]
 pub enum DependencyDefinition {
@@ -47,11 +126,37 @@ pub enum DependencyDefinition {
         rev: Option<String>,
         #[serde(skip_serializing_if = "Option::is_none")]
         path: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        registry: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        optional: Option<bool>,
+        /// `package = "..."` 重命名时的真实 crate 名称，键名只是别名
+        #[serde(skip_serializing_if = "Option::is_none")]
+        package: Option<String>,
+        /// `workspace = true` 表示从 `[workspace.dependencies]` 继承
+        #[serde(skip_serializing_if = "Option::is_none")]
+        workspace: Option<bool>,
         #[serde(flatten)]
         other: HashMap<String, toml::Value>,
     },
 }
 
+/// `[target.<cfg>]` 下的依赖表
+#[derive(Debug, Deserialize)]
+pub struct TargetDependencies {
+    pub dependencies: Option<HashMap<String, DependencyDefinition>>,
+    #[serde(rename = "dev-dependencies")]
+    pub dev_dependencies: Option<HashMap<String, DependencyDefinition>>,
+    #[serde(rename = "build-dependencies")]
+    pub build_dependencies: Option<HashMap<String, DependencyDefinition>>,
+}
+
+/// `[workspace]` 表中与依赖继承相关的部分
+#[derive(Debug, Deserialize)]
+pub struct WorkspaceTable {
+    pub dependencies: Option<HashMap<String, DependencyDefinition>>,
+}
+
 /// Cargo.toml 文件的结构
 #[derive(Debug, Deserialize)]
 pub struct CargoToml {
@@ -61,6 +166,20 @@ pub struct CargoToml {
     pub dev_dependencies: Option<HashMap<String, DependencyDefinition>>,
     #[serde(rename = "build-dependencies", skip_serializing_if = "Option::is_none")]
     pub build_dependencies: Option<HashMap<String, DependencyDefinition>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replace: Option<HashMap<String, DependencyDefinition>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<HashMap<String, TargetDependencies>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workspace: Option<WorkspaceTable>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub features: Option<HashMap<String, Vec<String>>>,
+    /// 可供 `workspace = true` 继承的依赖（来自本文件或上层 workspace 根目录）
+    #[serde(skip)]
+    pub workspace_dependencies: HashMap<String, DependencyDefinition>,
+    /// 上层 workspace 根目录，继承来的 path 依赖相对于它解析
+    #[serde(skip)]
+    pub workspace_root: Option<PathBuf>,
     #[serde(flatten)]
     pub _other: HashMap<String, toml::Value>,
 }
@@ -71,12 +190,53 @@ impl CargoToml {
         let content = fs::read_to_string(path)
             .with_context(|| format!("Failed to read Cargo.toml file: {}", path.display()))?;
 
-        let cargo_toml: CargoToml = toml::from_str(&content)
+        let mut cargo_toml: CargoToml = toml::from_str(&content)
             .with_context(|| format!("Failed to parse Cargo.toml file: {}", path.display()))?;
 
+        // 本文件就是 workspace 根时直接使用自己的表，否则向上查找 workspace 根
+        match &mut cargo_toml.workspace {
+            Some(workspace) => {
+                cargo_toml.workspace_dependencies =
+                    workspace.dependencies.take().unwrap_or_default();
+            }
+            None => {
+                if let Some((root, dependencies)) = Self::find_workspace_dependencies(path)? {
+                    cargo_toml.workspace_root = Some(root);
+                    cargo_toml.workspace_dependencies = dependencies;
+                }
+            }
+        }
+
         Ok(cargo_toml)
     }
 
+    /// 在上层目录中查找 workspace 根，返回根目录及其 `[workspace.dependencies]`
+    fn find_workspace_dependencies(
+        manifest_path: &Path,
+    ) -> Result<Option<(PathBuf, HashMap<String, DependencyDefinition>)>> {
+        let mut dir = manifest_path.parent().and_then(Path::parent);
+        while let Some(current) = dir {
+            let candidate = current.join("Cargo.toml");
+            if candidate.exists() {
+                let content = fs::read_to_string(&candidate).with_context(|| {
+                    format!("Failed to read Cargo.toml file: {}", candidate.display())
+                })?;
+                let root: CargoToml = toml::from_str(&content).with_context(|| {
+                    format!("Failed to parse Cargo.toml file: {}", candidate.display())
+                })?;
+                if let Some(workspace) = root.workspace {
+                    return Ok(Some((
+                        current.to_path_buf(),
+                        workspace.dependencies.unwrap_or_default(),
+                    )));
+                }
+            }
+            dir = current.parent();
+        }
+
+        Ok(None)
+    }
+
     /// 查找当前目录或父目录中的 Cargo.toml 文件
     pub fn find_and_load() -> Result<Self> {
         let cargo_toml_path = Self::find_cargo_toml()?;
@@ -84,7 +244,7 @@ impl CargoToml {
     }
 
     /// 查找 Cargo.toml 文件
-    fn find_cargo_toml() -> Result<PathBuf> {
+    pub fn find_cargo_toml() -> Result<PathBuf> {
         let mut current_dir = std::env::current_dir().context("Failed to get current directory")?;
 
         loop {
@@ -109,17 +269,39 @@ impl CargoToml {
 
         // 处理常规依赖
         if let Some(deps) = &self.dependencies {
-            dependencies.extend(self.parse_dependencies(deps));
+            dependencies.extend(self.parse_dependencies(deps, DependencySection::Normal, None));
         }
 
         // 处理开发依赖
         if let Some(dev_deps) = &self.dev_dependencies {
-            dependencies.extend(self.parse_dependencies(dev_deps));
+            dependencies.extend(self.parse_dependencies(dev_deps, DependencySection::Dev, None));
         }
 
         // 处理构建依赖
         if let Some(build_deps) = &self.build_dependencies {
-            dependencies.extend(self.parse_dependencies(build_deps));
+            dependencies.extend(self.parse_dependencies(
+                build_deps,
+                DependencySection::Build,
+                None,
+            ));
+        }
+
+        // 处理平台相关依赖 [target.'cfg(...)'.*]
+        if let Some(targets) = &self.target {
+            let mut cfgs: Vec<_> = targets.keys().collect();
+            cfgs.sort();
+            for cfg in cfgs {
+                let target = &targets[cfg];
+                for (deps, section) in [
+                    (&target.dependencies, DependencySection::Normal),
+                    (&target.dev_dependencies, DependencySection::Dev),
+                    (&target.build_dependencies, DependencySection::Build),
+                ] {
+                    if let Some(deps) = deps {
+                        dependencies.extend(self.parse_dependencies(deps, section, Some(cfg)));
+                    }
+                }
+            }
         }
 
         dependencies
@@ -132,10 +314,23 @@ impl CargoToml {
             .find(|dep| dep.name == name)
     }
 
+    /// 只在指定的表中查找依赖，用于同一个 crate 出现在多个表中时消除歧义
+    pub fn find_dependency_in_section(
+        &self,
+        name: &str,
+        section: DependencySection,
+    ) -> Option<DependencyInfo> {
+        self.get_all_dependencies()
+            .into_iter()
+            .find(|dep| dep.name == name && dep.section == section)
+    }
+
     /// 解析依赖定义
     fn parse_dependencies(
         &self,
         deps: &HashMap<String, DependencyDefinition>,
+        section: DependencySection,
+        target_cfg: Option<&str>,
     ) -> Vec<DependencyInfo> {
         deps.iter()
             .filter_map(|(name, def)| {
@@ -146,6 +341,11 @@ impl CargoToml {
                     })
                     .ok()
             })
+            .map(|mut dep| {
+                dep.section = section;
+                dep.target_cfg = target_cfg.map(str::to_string);
+                dep
+            })
             .collect()
     }
 
@@ -160,8 +360,22 @@ impl CargoToml {
                 name: name.to_string(),
                 dep_type: DependencyType::Version {
                     version: version.clone(),
+                    registry: None,
                 },
+                section: DependencySection::Normal,
+                target_cfg: None,
+                optional: false,
             }),
+            DependencyDefinition::Detailed {
+                workspace: Some(true),
+                optional,
+                ..
+            } => {
+                // 来源从 workspace 表继承，optional 只能由成员自己声明
+                let mut dep = self.resolve_workspace_dep(name)?;
+                dep.optional = optional.unwrap_or(false);
+                Ok(dep)
+            }
             DependencyDefinition::Detailed {
                 version,
                 git,
@@ -169,8 +383,14 @@ impl CargoToml {
                 tag,
                 rev,
                 path,
+                registry,
+                optional,
+                package,
                 ..
             } => {
+                let optional = optional.unwrap_or(false);
+                // patch 按真实 crate 名称匹配，重命名的依赖使用 package 的值
+                let name = package.as_deref().unwrap_or(name);
                 // 优先级：git > path > version
                 if let Some(git_url) = git {
                     Ok(DependencyInfo {
@@ -181,6 +401,9 @@ impl CargoToml {
                             tag: tag.clone(),
                             rev: rev.clone(),
                         },
+                        section: DependencySection::Normal,
+                        target_cfg: None,
+                        optional,
                     })
                 } else if let Some(path_str) = path {
                     Ok(DependencyInfo {
@@ -188,13 +411,20 @@ impl CargoToml {
                         dep_type: DependencyType::Path {
                             path: path_str.clone(),
                         },
+                        section: DependencySection::Normal,
+                        target_cfg: None,
+                        optional,
                     })
                 } else if let Some(version_str) = version {
                     Ok(DependencyInfo {
                         name: name.to_string(),
                         dep_type: DependencyType::Version {
                             version: version_str.clone(),
+                            registry: registry.clone(),
                         },
+                        section: DependencySection::Normal,
+                        target_cfg: None,
+                        optional,
                     })
                 } else {
                     Err(anyhow!("Invalid dependency definition for '{}'", name))
@@ -203,6 +433,163 @@ impl CargoToml {
         }
     }
 
+    /// 解析 `workspace = true` 的依赖：从 `[workspace.dependencies]` 取出定义并分类
+    ///
+    /// workspace 表中的 path 相对于 workspace 根目录，返回前会换算成可直接使用的路径
+    fn resolve_workspace_dep(&self, name: &str) -> Result<DependencyInfo> {
+        let inherited = self.workspace_dependencies.get(name).ok_or_else(|| {
+            anyhow!(
+                "'{}' uses `workspace = true` but is not in [workspace.dependencies]",
+                name
+            )
+        })?;
+        if matches!(
+            inherited,
+            DependencyDefinition::Detailed {
+                workspace: Some(true),
+                ..
+            }
+        ) {
+            return Err(anyhow!(
+                "[workspace.dependencies] entry for '{}' cannot itself use `workspace = true`",
+                name
+            ));
+        }
+
+        let mut dep = self.parse_dependency_definition(name, inherited)?;
+        if let (DependencyType::Path { path }, Some(root)) =
+            (&mut dep.dep_type, &self.workspace_root)
+        {
+            if Path::new(path.as_str()).is_relative() {
+                *path = root.join(&*path).display().to_string();
+            }
+        }
+        Ok(dep)
+    }
+
+    /// 查找会启用可选依赖 `dep` 的 feature
+    ///
+    /// 包括显式引用 `dep:<name>`、`<name>` 或 `<name>/<feature>` 的 feature，
+    /// 以及没有任何 `dep:<name>` 引用时 Cargo 隐式生成的同名 feature。
+    /// `<name>?/<feature>` 只在依赖已启用时生效，不计入
+    pub fn features_enabling(&self, dep: &str) -> Vec<String> {
+        let features = self.features.as_ref();
+        let explicit_dep = format!("dep:{dep}");
+        let sub_feature = format!("{dep}/");
+
+        let mut enabling: Vec<String> = features
+            .into_iter()
+            .flatten()
+            .filter(|(_, values)| {
+                values
+                    .iter()
+                    .any(|v| *v == explicit_dep || v == dep || v.starts_with(&sub_feature))
+            })
+            .map(|(feature, _)| feature.clone())
+            .collect();
+
+        let implicit = !features
+            .into_iter()
+            .flatten()
+            .any(|(_, values)| values.contains(&explicit_dep));
+        if implicit && !enabling.iter().any(|f| f == dep) {
+            enabling.push(dep.to_string());
+        }
+
+        enabling.sort();
+        enabling
+    }
+
+    /// 获取 `[replace]` 表中的所有替换项
+    pub fn get_replacements(&self) -> Vec<Replacement> {
+        let Some(replace) = &self.replace else {
+            return Vec::new();
+        };
+
+        replace
+            .iter()
+            .filter_map(|(spec, def)| {
+                let name = Self::spec_crate_name(spec);
+                self.parse_dependency_definition(&name, def)
+                    .map_err(|e| {
+                        error!("⚠️  Failed to parse replacement '{spec}': {e}");
+                        e
+                    })
+                    .ok()
+                    .map(|target| Replacement {
+                        spec: spec.clone(),
+                        target,
+                    })
+            })
+            .collect()
+    }
+
+    /// 从 package id spec 中提取 crate 名称
+    ///
+    /// 支持 `foo`、`foo:1.0.0`、`foo@1.0.0` 以及 `https://host/repo#foo@1.0.0` 等形式
+    fn spec_crate_name(spec: &str) -> String {
+        let (base, fragment) = match spec.split_once('#') {
+            Some((base, fragment)) => (base, Some(fragment)),
+            None => (spec, None),
+        };
+
+        if let Some(fragment) = fragment {
+            // 片段中带名称时（`name@ver` 或 `name:ver`）优先使用，否则只是版本号
+            if let Some((name, _)) = fragment.split_once(['@', ':']) {
+                return name.to_string();
+            }
+            if !fragment.starts_with(|c: char| c.is_ascii_digit()) {
+                return fragment.to_string();
+            }
+        }
+
+        if base.contains("://") {
+            return base
+                .trim_end_matches('/')
+                .trim_end_matches(".git")
+                .rsplit('/')
+                .next()
+                .unwrap_or(base)
+                .to_string();
+        }
+
+        base.split(['@', ':']).next().unwrap_or(base).to_string()
+    }
+
+    /// 从指定的 Cargo.toml 中删除一项 `[replace]`，保留文件其余内容和格式
+    pub fn remove_replacement(manifest_path: &Path, spec: &str) -> Result<bool> {
+        let content = fs::read_to_string(manifest_path).with_context(|| {
+            format!(
+                "Failed to read Cargo.toml file: {}",
+                manifest_path.display()
+            )
+        })?;
+        let mut doc: toml_edit::DocumentMut = content.parse().with_context(|| {
+            format!(
+                "Failed to parse Cargo.toml file: {}",
+                manifest_path.display()
+            )
+        })?;
+
+        let Some(replace) = doc.get_mut("replace").and_then(|r| r.as_table_like_mut()) else {
+            return Ok(false);
+        };
+        if replace.remove(spec).is_none() {
+            return Ok(false);
+        }
+        if replace.is_empty() {
+            doc.remove("replace");
+        }
+
+        fs::write(manifest_path, doc.to_string()).with_context(|| {
+            format!(
+                "Failed to write Cargo.toml file: {}",
+                manifest_path.display()
+            )
+        })?;
+        Ok(true)
+    }
+
     /// 获取所有 git 依赖
     pub fn get_git_dependencies(&self) -> Vec<DependencyInfo> {
         self.get_all_dependencies()
@@ -252,3 +639,141 @@ impl DependencyInfo {
     //     matches!(self.dep_type, DependencyType::Path { .. })
     // }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    #[test]
+    fn patch_source_prefers_git_then_registry_then_crates_io() {
+        let git = DependencyType::Git {
+            git: "https://example.com/org/foo.git".to_string(),
+            branch: None,
+            tag: None,
+            rev: None,
+        };
+        let internal = DependencyType::Version {
+            version: "1".to_string(),
+            registry: Some("internal".to_string()),
+        };
+        let crates_io = DependencyType::Version {
+            version: "1".to_string(),
+            registry: None,
+        };
+        let path = DependencyType::Path {
+            path: "../foo".to_string(),
+        };
+
+        assert_eq!(
+            git.patch_source().as_deref(),
+            Some("https://example.com/org/foo.git")
+        );
+        assert_eq!(internal.patch_source().as_deref(), Some("internal"));
+        assert_eq!(crates_io.patch_source().as_deref(), Some("crates-io"));
+        assert_eq!(path.patch_source(), None);
+    }
+
+    #[test]
+    fn finds_target_gated_git_dependency() {
+        let tmp = tempfile::tempdir().unwrap();
+        test_support::project(
+            tmp.path(),
+            r#"[package]
+name = "app"
+version = "0.1.0"
+
+[target.'cfg(windows)'.dependencies]
+foo = { git = "https://example.com/org/foo.git", branch = "dev" }
+
+[target.'cfg(unix)'.build-dependencies]
+bar = "1.0"
+"#,
+        );
+        let cargo_toml = CargoToml::load_from_path(&tmp.path().join("Cargo.toml")).unwrap();
+
+        let foo = cargo_toml.find_dependency("foo").unwrap();
+        assert_eq!(foo.section, DependencySection::Normal);
+        assert_eq!(foo.target_cfg.as_deref(), Some("cfg(windows)"));
+        match foo.dep_type {
+            DependencyType::Git { git, branch, .. } => {
+                assert_eq!(git, "https://example.com/org/foo.git");
+                assert_eq!(branch.as_deref(), Some("dev"));
+            }
+            other => panic!("expected a git dependency, got {other:?}"),
+        }
+        assert_eq!(cargo_toml.get_git_dependencies().len(), 1);
+
+        let bar = cargo_toml.find_dependency("bar").unwrap();
+        assert_eq!(bar.section, DependencySection::Build);
+        assert_eq!(bar.target_cfg.as_deref(), Some("cfg(unix)"));
+    }
+
+    /// 带一个成员 crate 的 workspace，返回成员的 Cargo.toml
+    fn workspace_with_member(root: &Path, member_deps: &str) -> PathBuf {
+        test_support::project(
+            root,
+            r#"[workspace]
+members = ["crates/member"]
+
+[workspace.dependencies]
+foo = { git = "https://example.com/org/foo.git", tag = "v1.0.0" }
+local = { path = "vendor/local" }
+serde = "1.0"
+"#,
+        );
+        let member = root.join("crates/member");
+        test_support::project(
+            &member,
+            &format!("[package]\nname = \"member\"\nversion = \"0.1.0\"\n\n[dependencies]\n{member_deps}"),
+        );
+        member.join("Cargo.toml")
+    }
+
+    #[test]
+    fn workspace_dependency_keeps_member_optional_flag() {
+        let tmp = tempfile::tempdir().unwrap();
+        let manifest = workspace_with_member(
+            tmp.path(),
+            "foo = { workspace = true, optional = true }\nserde = { workspace = true }\n",
+        );
+        let cargo_toml = CargoToml::load_from_path(&manifest).unwrap();
+
+        let foo = cargo_toml.find_dependency("foo").unwrap();
+        assert!(foo.optional);
+        match foo.dep_type {
+            DependencyType::Git { git, tag, .. } => {
+                assert_eq!(git, "https://example.com/org/foo.git");
+                assert_eq!(tag.as_deref(), Some("v1.0.0"));
+            }
+            other => panic!("expected a git dependency, got {other:?}"),
+        }
+
+        let serde = cargo_toml.find_dependency("serde").unwrap();
+        assert!(!serde.optional);
+        assert!(matches!(
+            serde.dep_type,
+            DependencyType::Version { ref version, .. } if version == "1.0"
+        ));
+    }
+
+    #[test]
+    fn inherited_relative_path_is_rebased_onto_workspace_root() {
+        let tmp = tempfile::tempdir().unwrap();
+        let manifest = workspace_with_member(
+            tmp.path(),
+            "local = { workspace = true, optional = true }\n",
+        );
+        let cargo_toml = CargoToml::load_from_path(&manifest).unwrap();
+        assert_eq!(cargo_toml.workspace_root.as_deref(), Some(tmp.path()));
+
+        let local = cargo_toml.find_dependency("local").unwrap();
+        assert!(local.optional);
+        match local.dep_type {
+            DependencyType::Path { path } => {
+                assert_eq!(Path::new(&path), tmp.path().join("vendor/local"));
+            }
+            other => panic!("expected a path dependency, got {other:?}"),
+        }
+    }
+}
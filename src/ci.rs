@@ -0,0 +1,282 @@
+use anyhow::{anyhow, Result};
+use std::collections::BTreeSet;
+use std::path::{Component, Path};
+
+/// 支持生成的 CI 平台
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CiFormat {
+    Github,
+    Gitlab,
+    Circleci,
+}
+
+impl CiFormat {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "github" => Ok(CiFormat::Github),
+            "gitlab" => Ok(CiFormat::Gitlab),
+            "circleci" => Ok(CiFormat::Circleci),
+            _ => Err(anyhow!(
+                "Unknown CI format '{s}', expected 'github', 'gitlab' or 'circleci'"
+            )),
+        }
+    }
+}
+
+/// CI 中需要重建的一个 patch
+#[derive(Debug, Clone)]
+pub struct CiPatch {
+    pub name: String,
+    pub dir: String, // 传给 `cargo lpatch --dir` 的克隆目录
+}
+
+impl CiPatch {
+    /// 从 config.toml 中的 patch 路径推断克隆目录
+    ///
+    /// 克隆位于 `<dir>/<name>`，workspace 中的 crate 还会多出子路径，
+    /// 因此取名为 `name` 的路径组件之前的部分；找不到时退回到父目录
+    pub fn from_patch_path(name: &str, path: &str) -> Self {
+        let components: Vec<_> = Path::new(path)
+            .components()
+            .filter(|c| !matches!(c, Component::CurDir))
+            .collect();
+        let end = components
+            .iter()
+            .position(|c| c.as_os_str() == name)
+            .unwrap_or(components.len().saturating_sub(1));
+        let dir: std::path::PathBuf = components[..end].iter().collect();
+        let dir = dir.to_string_lossy().replace('\\', "/");
+
+        CiPatch {
+            name: name.to_string(),
+            dir: if dir.is_empty() { ".".to_string() } else { dir },
+        }
+    }
+}
+
+/// 生成 CI 配置，克隆目录按 `.cargo/config.toml` 的哈希缓存
+pub fn generate(format: CiFormat, patches: &[CiPatch]) -> String {
+    let dirs: BTreeSet<&str> = patches.iter().map(|p| p.dir.as_str()).collect();
+    let commands: Vec<String> = patches
+        .iter()
+        .map(|p| format!("cargo lpatch --name {} --dir {}", p.name, p.dir))
+        .collect();
+
+    match format {
+        CiFormat::Github => generate_github(&dirs, &commands),
+        CiFormat::Gitlab => generate_gitlab(&dirs, &commands),
+        CiFormat::Circleci => generate_circleci(&dirs, &commands),
+    }
+}
+
+fn generate_github(dirs: &BTreeSet<&str>, commands: &[String]) -> String {
+    let mut out = String::new();
+    out.push_str("# Generated by cargo-lpatch generate-ci-config\n");
+    out.push_str("name: lpatch\n\non:\n  push:\n  pull_request:\n\n");
+    out.push_str("jobs:\n  build:\n    runs-on: ubuntu-latest\n    steps:\n");
+    out.push_str("      - uses: actions/checkout@v4\n");
+    out.push_str("      - uses: dtolnay/rust-toolchain@stable\n");
+    out.push_str("      - name: Cache patched crates\n");
+    out.push_str("        uses: actions/cache@v4\n");
+    out.push_str("        with:\n          path: |\n");
+    for dir in dirs {
+        out.push_str(&format!("            {dir}\n"));
+    }
+    out.push_str("          key: lpatch-${{ runner.os }}-${{ hashFiles('.cargo/config.toml') }}\n");
+    out.push_str("      - name: Install cargo-lpatch\n");
+    out.push_str("        run: cargo install cargo-lpatch --locked\n");
+    out.push_str("      - name: Restore local patches\n");
+    out.push_str("        run: |\n");
+    for command in commands {
+        out.push_str(&format!("          {command}\n"));
+    }
+    out.push_str("      - name: Build\n");
+    out.push_str("        run: cargo build\n");
+    out
+}
+
+fn generate_gitlab(dirs: &BTreeSet<&str>, commands: &[String]) -> String {
+    let mut out = String::new();
+    out.push_str("# Generated by cargo-lpatch generate-ci-config\n");
+    out.push_str("lpatch-build:\n  image: rust:latest\n");
+    out.push_str("  cache:\n    key:\n      files:\n        - .cargo/config.toml\n");
+    out.push_str("    paths:\n");
+    for dir in dirs {
+        out.push_str(&format!("      - {dir}\n"));
+    }
+    out.push_str("  script:\n");
+    out.push_str("    - cargo install cargo-lpatch --locked\n");
+    for command in commands {
+        out.push_str(&format!("    - {command}\n"));
+    }
+    out.push_str("    - cargo build\n");
+    out
+}
+
+fn generate_circleci(dirs: &BTreeSet<&str>, commands: &[String]) -> String {
+    let mut out = String::new();
+    out.push_str("# Generated by cargo-lpatch generate-ci-config\n");
+    out.push_str("version: 2.1\n\njobs:\n  build:\n");
+    out.push_str("    docker:\n      - image: cimg/rust:1.85\n");
+    out.push_str("    steps:\n      - checkout\n");
+    out.push_str("      - restore_cache:\n");
+    out.push_str("          key: lpatch-{{ checksum \".cargo/config.toml\" }}\n");
+    out.push_str("      - run: cargo install cargo-lpatch --locked\n");
+    out.push_str("      - run:\n          name: Restore local patches\n          command: |\n");
+    for command in commands {
+        out.push_str(&format!("            {command}\n"));
+    }
+    out.push_str("      - save_cache:\n");
+    out.push_str("          key: lpatch-{{ checksum \".cargo/config.toml\" }}\n");
+    out.push_str("          paths:\n");
+    for dir in dirs {
+        out.push_str(&format!("            - {dir}\n"));
+    }
+    out.push_str("      - run: cargo build\n");
+    out.push_str("\nworkflows:\n  build:\n    jobs:\n      - build\n");
+    out
+}
+
+/// 访问补丁仓库的方式，决定 CI 中需要的凭据
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemoteAccess {
+    /// 需要 SSH 私钥
+    Ssh,
+    /// 私有仓库需要 `GIT_TOKEN`
+    Https,
+    /// 本地路径或 `file://`，CI 中无法重建
+    Local,
+}
+
+/// 一个补丁所在仓库的远程地址
+#[derive(Debug, Clone)]
+pub struct CiRemote {
+    pub name: String,
+    pub url: String,
+    pub access: RemoteAccess,
+    /// SSH 远程的主机名，用于写入 known_hosts
+    pub host: Option<String>,
+}
+
+impl RemoteAccess {
+    pub fn of(url: &str) -> Self {
+        if url.starts_with("http://") || url.starts_with("https://") {
+            RemoteAccess::Https
+        } else if url.starts_with("ssh://") || (!url.contains("://") && url.contains('@')) {
+            RemoteAccess::Ssh
+        } else {
+            RemoteAccess::Local
+        }
+    }
+}
+
+/// 生成在 CI 中配置凭据的说明和片段
+pub fn secret_instructions(format: CiFormat, remotes: &[CiRemote]) -> String {
+    let of = |access| -> Vec<&CiRemote> { remotes.iter().filter(|r| r.access == access).collect() };
+    let (ssh, https, local) = (
+        of(RemoteAccess::Ssh),
+        of(RemoteAccess::Https),
+        of(RemoteAccess::Local),
+    );
+
+    let mut out = String::new();
+    out.push_str("# Generated by cargo-lpatch add-ci-secret\n");
+    for remote in &local {
+        out.push_str(&format!(
+            "# WARNING: {} is cloned from {}, which CI cannot reach\n",
+            remote.name, remote.url
+        ));
+    }
+    if ssh.is_empty() && https.is_empty() {
+        out.push_str("# No credentials are needed to re-create the patches\n");
+        return out;
+    }
+
+    if !ssh.is_empty() {
+        let names: Vec<&str> = ssh.iter().map(|r| r.name.as_str()).collect();
+        let hosts: BTreeSet<&str> = ssh.iter().filter_map(|r| r.host.as_deref()).collect();
+        out.push_str(&format!(
+            "#\n# SSH access is needed for: {}\n",
+            names.join(", ")
+        ));
+        out.push_str(&format!(
+            "# Add a private key with read access to these repositories as the secret SSH_PRIVATE_KEY{}.\n",
+            match format {
+                CiFormat::Github => " (Settings > Secrets and variables > Actions)",
+                CiFormat::Gitlab => " (Settings > CI/CD > Variables)",
+                CiFormat::Circleci => " (Project Settings > Environment Variables)",
+            }
+        ));
+        out.push_str(&ssh_snippet(format, &hosts));
+    }
+
+    if !https.is_empty() {
+        let names: Vec<&str> = https.iter().map(|r| r.name.as_str()).collect();
+        out.push_str(&format!(
+            "#\n# HTTPS access is used for: {}\n",
+            names.join(", ")
+        ));
+        out.push_str("# If any of these repositories are private, add a token with read access as the secret GIT_TOKEN.\n");
+        out.push_str(&token_snippet(format));
+    }
+    out
+}
+
+fn ssh_snippet(format: CiFormat, hosts: &BTreeSet<&str>) -> String {
+    let mut commands = vec![
+        "eval \"$(ssh-agent -s)\"".to_string(),
+        "echo \"$SSH_PRIVATE_KEY\" | tr -d '\\r' | ssh-add -".to_string(),
+        "mkdir -p ~/.ssh".to_string(),
+    ];
+    for host in hosts {
+        commands.push(format!("ssh-keyscan {host} >> ~/.ssh/known_hosts"));
+    }
+
+    let mut out = String::new();
+    match format {
+        CiFormat::Github => {
+            // 后续步骤需要通过 GITHUB_ENV 拿到 agent 的 socket
+            commands.push("echo \"SSH_AUTH_SOCK=$SSH_AUTH_SOCK\" >> \"$GITHUB_ENV\"".to_string());
+            out.push_str("      - name: Set up SSH key for patched repositories\n");
+            out.push_str(
+                "        env:\n          SSH_PRIVATE_KEY: ${{ secrets.SSH_PRIVATE_KEY }}\n",
+            );
+            out.push_str("        run: |\n");
+            for command in &commands {
+                out.push_str(&format!("          {command}\n"));
+            }
+        }
+        CiFormat::Gitlab => {
+            out.push_str("  before_script:\n");
+            for command in &commands {
+                out.push_str(&format!("    - {command}\n"));
+            }
+        }
+        CiFormat::Circleci => {
+            commands
+                .push("echo \"export SSH_AUTH_SOCK=$SSH_AUTH_SOCK\" >> \"$BASH_ENV\"".to_string());
+            out.push_str("      - run:\n          name: Set up SSH key for patched repositories\n");
+            out.push_str("          command: |\n");
+            for command in &commands {
+                out.push_str(&format!("            {command}\n"));
+            }
+        }
+    }
+    out
+}
+
+fn token_snippet(format: CiFormat) -> String {
+    match format {
+        CiFormat::Github => concat!(
+            "      - name: Restore local patches\n",
+            "        env:\n",
+            "          GIT_TOKEN: ${{ secrets.GIT_TOKEN }}\n",
+        )
+        .to_string(),
+        // GitLab 和 CircleCI 会把项目变量自动导出到作业环境中
+        CiFormat::Gitlab | CiFormat::Circleci => {
+            "# GIT_TOKEN is exported to every job automatically, no changes to the pipeline are needed\n"
+                .to_string()
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 失败时的清理记录：本次运行新建的目录以及写入前的配置内容
+#[derive(Debug, Default)]
+pub struct FailureCleanup {
+    enabled: bool,
+    created_dirs: Vec<PathBuf>,
+    /// 写入前的配置文件：`None` 表示尚未备份，`Some(None)` 表示原先不存在
+    config_backup: Option<(PathBuf, Option<String>)>,
+}
+
+impl FailureCleanup {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            ..Self::default()
+        }
+    }
+
+    /// 记录即将创建的目录，只记录其中第一个尚不存在的上级目录
+    pub fn track_new_dir(&mut self, dir: &Path) {
+        if !self.enabled || dir.exists() {
+            return;
+        }
+
+        let mut topmost = dir;
+        while let Some(parent) = topmost.parent() {
+            if parent.as_os_str().is_empty() || parent.exists() {
+                break;
+            }
+            topmost = parent;
+        }
+        if !self
+            .created_dirs
+            .iter()
+            .any(|tracked| topmost.starts_with(tracked))
+        {
+            self.created_dirs.push(topmost.to_path_buf());
+        }
+    }
+
+    /// 在写入配置之前保存其原始内容（同一次运行只备份一次）
+    pub fn backup_config(&mut self, config_path: &Path) -> Result<()> {
+        if !self.enabled || self.config_backup.is_some() {
+            return Ok(());
+        }
+
+        let content = if config_path.exists() {
+            Some(fs::read_to_string(config_path).with_context(|| {
+                format!("Failed to back up config file: {}", config_path.display())
+            })?)
+        } else {
+            None
+        };
+        self.config_backup = Some((config_path.to_path_buf(), content));
+        Ok(())
+    }
+
+    /// 恢复配置并删除本次运行新建的目录
+    pub fn rollback(&mut self) {
+        if !self.enabled {
+            return;
+        }
+
+        warn!("↩️  Operation failed, cleaning up (--clean-on-failure)");
+
+        if let Some((config_path, content)) = self.config_backup.take() {
+            let result = match content {
+                Some(content) => fs::write(&config_path, content),
+                None if config_path.exists() => fs::remove_file(&config_path),
+                None => Ok(()),
+            };
+            match result {
+                Ok(()) => info!("  📄 Restored {}", config_path.display()),
+                Err(e) => error!("  ❌ Failed to restore {}: {e}", config_path.display()),
+            }
+        }
+
+        for dir in self.created_dirs.drain(..).rev() {
+            if !dir.exists() {
+                continue;
+            }
+            match fs::remove_dir_all(&dir) {
+                Ok(()) => info!("  🗑️  Removed {}", dir.display()),
+                Err(e) => error!("  ❌ Failed to remove {}: {e}", dir.display()),
+            }
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// 导出符号：修饰名 -> 还原后的名称
+pub type SymbolTable = BTreeMap<String, String>;
+
+/// 原始版本与补丁版本之间的导出符号差异
+#[derive(Debug, Default, Serialize)]
+pub struct CompatReport {
+    pub crate_name: String,
+    pub base: String,
+    pub artifacts: Vec<ArtifactDiff>,
+}
+
+/// 单个产物（cdylib 或 staticlib）的符号差异
+#[derive(Debug, Serialize)]
+pub struct ArtifactDiff {
+    pub kind: String,
+    pub added: Vec<Symbol>,
+    pub removed: Vec<Symbol>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Symbol {
+    pub mangled: String,
+    pub demangled: String,
+}
+
+impl CompatReport {
+    pub fn is_compatible(&self) -> bool {
+        self.artifacts.iter().all(|a| a.removed.is_empty())
+    }
+}
+
+/// 读取 `[lib] crate-type` 中需要比较二进制兼容性的类型
+pub fn binary_crate_types(crate_path: &Path) -> Result<Vec<String>> {
+    let manifest = crate_path.join("Cargo.toml");
+    let content = fs::read_to_string(&manifest)
+        .with_context(|| format!("Failed to read {}", manifest.display()))?;
+    let value: toml::Value = toml::from_str(&content)
+        .with_context(|| format!("Failed to parse {}", manifest.display()))?;
+
+    Ok(value
+        .get("lib")
+        .and_then(|lib| lib.get("crate-type"))
+        .and_then(|types| types.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|t| t.as_str())
+        .filter(|t| matches!(*t, "cdylib" | "staticlib"))
+        .map(str::to_string)
+        .collect())
+}
+
+/// 构建 crate 并返回 crate 类型到产物路径的映射
+pub fn build_artifacts(crate_path: &Path, target_dir: &Path) -> Result<BTreeMap<String, PathBuf>> {
+    info!("🔨 Building {}...", crate_path.display());
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let output = Command::new(cargo)
+        .args(["build", "--release", "--lib", "--message-format=json"])
+        .arg("--target-dir")
+        .arg(target_dir)
+        .current_dir(crate_path)
+        .output()
+        .context("Failed to run cargo build")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "cargo build failed in {}:\n{}",
+            crate_path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    // 从 compiler-artifact 消息中找到本 crate 的 cdylib/staticlib 产物
+    let manifest_path = fs::canonicalize(crate_path.join("Cargo.toml"))?;
+    let mut artifacts = BTreeMap::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let Ok(message) = serde_json::from_str::<serde_json::Value>(line) else {
+            continue;
+        };
+        if message["reason"] != "compiler-artifact"
+            || message["manifest_path"].as_str().map(Path::new) != Some(manifest_path.as_path())
+        {
+            continue;
+        }
+
+        let kinds = message["target"]["kind"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+        let files = message["filenames"].as_array().cloned().unwrap_or_default();
+        for kind in kinds.iter().filter_map(|k| k.as_str()) {
+            let extension_matches = |file: &str| match kind {
+                "cdylib" => [".so", ".dylib", ".dll"]
+                    .iter()
+                    .any(|ext| file.ends_with(ext)),
+                "staticlib" => [".a", ".lib"].iter().any(|ext| file.ends_with(ext)),
+                _ => false,
+            };
+            if let Some(file) = files
+                .iter()
+                .filter_map(|f| f.as_str())
+                .find(|f| extension_matches(f))
+            {
+                artifacts.insert(kind.to_string(), PathBuf::from(file));
+            }
+        }
+    }
+
+    Ok(artifacts)
+}
+
+/// 通过 `nm` 读取产物中已定义的全局符号
+pub fn exported_symbols(artifact: &Path, kind: &str) -> Result<SymbolTable> {
+    let mangled = run_nm(artifact, kind, false)?;
+    let demangled = run_nm(artifact, kind, true)?;
+
+    // 两次输出顺序一致，只有名称是否还原的区别
+    Ok(mangled
+        .into_iter()
+        .zip(demangled)
+        .collect::<BTreeMap<_, _>>())
+}
+
+fn run_nm(artifact: &Path, kind: &str, demangle: bool) -> Result<Vec<String>> {
+    let mut command = Command::new("nm");
+    command.args(["--defined-only", "--extern-only", "--no-sort"]);
+    if kind == "cdylib" {
+        command.arg("--dynamic");
+    }
+    if demangle {
+        command.arg("--demangle");
+    }
+    let output = command
+        .arg(artifact)
+        .output()
+        .context("Failed to run 'nm', make sure binutils is installed and in PATH")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "nm failed on {}: {}",
+            artifact.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    // 行格式为 `<地址> <类型> <名称>`，staticlib 中还会出现 `member.o:` 这样的分隔行
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, ' ');
+            let (_addr, _kind, name) = (parts.next()?, parts.next()?, parts.next()?);
+            Some(name.to_string())
+        })
+        .collect())
+}
+
+/// 比较两组符号
+pub fn diff_symbols(kind: &str, original: &SymbolTable, patched: &SymbolTable) -> ArtifactDiff {
+    let collect = |from: &SymbolTable, other: &SymbolTable| {
+        from.iter()
+            .filter(|(mangled, _)| !other.contains_key(*mangled))
+            .map(|(mangled, demangled)| Symbol {
+                mangled: mangled.clone(),
+                demangled: demangled.clone(),
+            })
+            .collect()
+    };
+
+    ArtifactDiff {
+        kind: kind.to_string(),
+        added: collect(patched, original),
+        removed: collect(original, patched),
+    }
+}
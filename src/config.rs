@@ -1,21 +1,24 @@
-use anyhow::{Context, Result};
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashSet;
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use toml_edit::{DocumentMut, InlineTable, Item, Table};
 
-#[derive(Debug, Serialize, Deserialize, Default)]
-pub struct CargoConfig {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub patch: Option<HashMap<String, HashMap<String, PatchConfig>>>,
-
-    #[serde(flatten)]
-    pub other: HashMap<String, toml::Value>,
-}
+/// 是否在本次运行第一次修改文件前保留 `.bak` 副本（`--no-backup` 关闭）
+static BACKUP_ENABLED: AtomicBool = AtomicBool::new(true);
+/// 本次运行中已经备份过的文件
+static BACKED_UP: Mutex<Option<HashSet<PathBuf>>> = Mutex::new(None);
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct PatchConfig {
-    pub path: String,
+/// `.cargo/config.toml` 文档
+///
+/// 基于 toml_edit 进行原地修改，保存时保留注释、键顺序以及与 patch 无关的表
+#[derive(Debug, Default)]
+pub struct CargoConfig {
+    doc: DocumentMut,
+    /// 编辑的是项目 `Cargo.toml` 时为其路径，`None` 表示 `.cargo/config.toml`
+    manifest_path: Option<PathBuf>,
 }
 
 impl CargoConfig {
@@ -31,14 +34,50 @@ impl CargoConfig {
         }
     }
 
+    /// 只读加载配置，文件不存在时返回空配置而不创建目录
+    pub fn load() -> Result<Self> {
+        let config_path = Self::get_config_path();
+
+        if config_path.exists() {
+            Self::load_from_file(&config_path)
+        } else {
+            Ok(Self::default())
+        }
+    }
+
     fn load_from_file(path: &Path) -> Result<Self> {
         let content = fs::read_to_string(path)
             .with_context(|| format!("Failed to read config file: {}", path.display()))?;
 
-        let config: CargoConfig =
-            toml::from_str(&content).with_context(|| "Failed to parse config.toml")?;
+        let doc: DocumentMut = content
+            .parse()
+            .with_context(|| "Failed to parse config.toml")?;
+
+        Ok(Self {
+            doc,
+            manifest_path: None,
+        })
+    }
+
+    /// 加载项目 `Cargo.toml`，patch 写入其中的 `[patch]` 表
+    pub fn load_manifest(manifest_path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(manifest_path).with_context(|| {
+            format!(
+                "Failed to read Cargo.toml file: {}",
+                manifest_path.display()
+            )
+        })?;
+        let doc: DocumentMut = content.parse().with_context(|| {
+            format!(
+                "Failed to parse Cargo.toml file: {}",
+                manifest_path.display()
+            )
+        })?;
 
-        Ok(config)
+        Ok(Self {
+            doc,
+            manifest_path: Some(manifest_path.to_path_buf()),
+        })
     }
 
     pub fn create_new() -> Result<Self> {
@@ -55,46 +94,49 @@ impl CargoConfig {
         Ok(Self::default())
     }
 
-    pub fn add_patch(&mut self, crate_name: &str, local_path: &Path) -> Result<()> {
-        self.add_patch_with_source(crate_name, local_path, "crates-io")
-    }
-
     pub fn add_patch_with_source(
         &mut self,
         crate_name: &str,
         local_path: &Path,
         patch_source: &str,
     ) -> Result<()> {
-        // 确保 patch 表存在
-        if self.patch.is_none() {
-            self.patch = Some(HashMap::new());
-        }
+        let relative_path = self.relative_path(local_path)?;
 
-        let patch_table = self.patch.as_mut().unwrap();
+        // 确保 patch 表存在，`[patch]` 本身不需要单独的表头
+        let patch_table = self
+            .doc
+            .entry("patch")
+            .or_insert_with(|| {
+                let mut table = Table::new();
+                table.set_implicit(true);
+                Item::Table(table)
+            })
+            .as_table_like_mut()
+            .ok_or_else(|| anyhow!("'patch' in config.toml is not a table"))?;
 
         // 确保指定的 patch 源表存在
-        if !patch_table.contains_key(patch_source) {
-            patch_table.insert(patch_source.to_string(), HashMap::new());
-        }
-
-        let source_patches = patch_table.get_mut(patch_source).unwrap();
-
-        // 将路径转换为相对路径（相对于当前工作目录）
-        let current_dir = std::env::current_dir().context("Failed to get current directory")?;
-
-        let relative_path = if local_path.is_absolute() {
-            match local_path.strip_prefix(&current_dir) {
-                Ok(rel_path) => rel_path.to_path_buf(),
-                Err(_) => local_path.to_path_buf(), // 如果无法创建相对路径，使用绝对路径
-            }
-        } else {
-            local_path.to_path_buf()
-        };
+        let source_patches = patch_table
+            .entry(patch_source)
+            .or_insert(Item::Table(Table::new()))
+            .as_table_like_mut()
+            .ok_or_else(|| anyhow!("'patch.{patch_source}' in config.toml is not a table"))?;
 
         let path_str = relative_path.to_string_lossy().to_string();
 
-        // 添加或更新 patch 配置
-        source_patches.insert(crate_name.to_string(), PatchConfig { path: path_str });
+        // 添加或更新 patch 配置，已有条目只更新 path，保留其他键
+        match source_patches
+            .get_mut(crate_name)
+            .and_then(Item::as_table_like_mut)
+        {
+            Some(entry) => {
+                entry.insert("path", toml_edit::value(path_str));
+            }
+            None => {
+                let mut entry = InlineTable::new();
+                entry.insert("path", path_str.into());
+                source_patches.insert(crate_name, toml_edit::value(entry));
+            }
+        }
 
         info!(
             "➕ Added patch for '{}' -> '{}' (source: {})",
@@ -106,19 +148,225 @@ impl CargoConfig {
         Ok(())
     }
 
+    /// 将路径转换为相对路径：Cargo.toml 中的 path 相对于清单所在目录，
+    /// `.cargo/config.toml` 中的 path 相对于 `.cargo` 的上级目录，
+    /// 后者可能是上层 workspace 根而不是当前工作目录
+    fn relative_path(&self, local_path: &Path) -> Result<PathBuf> {
+        let current_dir = std::env::current_dir().context("Failed to get current directory")?;
+        let base_dir = match &self.manifest_path {
+            Some(manifest_path) => manifest_path.parent().map(Path::to_path_buf),
+            None => Self::get_config_dir().parent().map(Path::to_path_buf),
+        }
+        .unwrap_or_else(|| current_dir.clone());
+
+        Ok(relative_to(&base_dir, &current_dir, local_path))
+    }
+
+    /// 写入 `[source.<name>] local-registry = "<path>"`，已有同名源时覆盖
+    pub fn set_local_registry(&mut self, name: &str, registry_dir: &Path) -> Result<()> {
+        let path_str = self
+            .relative_path(registry_dir)?
+            .to_string_lossy()
+            .to_string();
+        let source_table = self
+            .doc
+            .entry("source")
+            .or_insert_with(|| {
+                let mut table = Table::new();
+                table.set_implicit(true);
+                Item::Table(table)
+            })
+            .as_table_like_mut()
+            .ok_or_else(|| anyhow!("'source' in config.toml is not a table"))?;
+
+        let mut entry = Table::new();
+        entry.insert("local-registry", toml_edit::value(path_str.clone()));
+        source_table.insert(name, Item::Table(entry));
+        info!("➕ Added source '{name}' -> local registry '{path_str}'");
+        Ok(())
+    }
+
+    /// 从所有 patch 源中删除指定 crate 的 patch，返回被删除项所在的源
+    ///
+    /// 删除后为空的源表会一并移除
+    pub fn remove_patch(&mut self, crate_name: &str) -> Vec<String> {
+        let Some(patch_table) = self.doc.get_mut("patch").and_then(Item::as_table_like_mut) else {
+            return Vec::new();
+        };
+
+        let mut sources: Vec<String> = patch_table
+            .iter_mut()
+            .filter_map(|(source, entries)| {
+                entries
+                    .as_table_like_mut()?
+                    .remove(crate_name)
+                    .map(|_| source.get().to_string())
+            })
+            .collect();
+        sources.sort();
+
+        for source in &sources {
+            if patch_table
+                .get(source)
+                .and_then(Item::as_table_like)
+                .is_some_and(|entries| entries.is_empty())
+            {
+                patch_table.remove(source);
+            }
+        }
+        if patch_table.is_empty() {
+            self.doc.remove("patch");
+        }
+
+        for source in &sources {
+            info!("➖ Removed patch for '{crate_name}' (source: {source})");
+        }
+        sources
+    }
+
+    /// 移除整个 `[patch]` 表，返回被移除的条目数
+    pub fn clear_all_patches(&mut self) -> usize {
+        let Some(patch) = self.doc.remove("patch") else {
+            return 0;
+        };
+
+        patch
+            .as_table_like()
+            .map(|patch_table| {
+                patch_table
+                    .iter()
+                    .filter_map(|(_, entries)| entries.as_table_like())
+                    .map(|entries| entries.len())
+                    .sum()
+            })
+            .unwrap_or(0)
+    }
+
+    /// 列出所有 patch 项，返回按源和名称排序的 (source, crate_name, path)
+    ///
+    /// 没有 `path` 的条目（例如指向 git 仓库的 patch）不是本地补丁，会被忽略
+    pub fn list_patches(&self) -> Vec<(String, String, String)> {
+        let Some(patch_table) = self.doc.get("patch").and_then(Item::as_table_like) else {
+            return Vec::new();
+        };
+
+        let mut patches: Vec<_> = patch_table
+            .iter()
+            .filter_map(|(source, entries)| Some((source, entries.as_table_like()?)))
+            .flat_map(|(source, entries)| {
+                entries.iter().filter_map(move |(name, entry)| {
+                    let path = entry.as_table_like()?.get("path")?.as_str()?;
+                    Some((source.to_string(), name.to_string(), path.to_string()))
+                })
+            })
+            .collect();
+        patches.sort();
+        patches
+    }
+
+    /// patch 源表在 config.toml 中的表头，URL 源需要加引号
+    pub fn patch_table_header(source: &str) -> String {
+        if source
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            format!("[patch.{source}]")
+        } else {
+            format!("[patch.\"{source}\"]")
+        }
+    }
+
+    /// `cargo lpatch init` 写入的初始配置，附带注释掉的 patch 示例
+    pub fn template(dir: &str) -> String {
+        let dir = dir.trim_end_matches('/');
+        format!(
+            "# Local patches managed by cargo-lpatch.\n\
+             # Run `cargo lpatch --name <CRATE> --dir {dir}` to add entries, or write them by hand:\n\
+             #\n\
+             # [patch.crates-io]\n\
+             # serde = {{ path = \"{dir}/serde\" }}\n"
+        )
+    }
+
+    /// 配置所属的项目根目录（即 `.cargo` 目录的上一级）
+    pub fn project_dir() -> PathBuf {
+        let config_dir = Self::get_config_dir();
+        config_dir
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or(config_dir)
+    }
+
+    /// 将 patch 中的路径解析为实际路径（相对路径以项目根目录为基准）
+    pub fn resolve_patch_path(path: &str) -> PathBuf {
+        let path = Path::new(path);
+        if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            Self::project_dir().join(path)
+        }
+    }
+
+    /// 关闭保存前的 `.bak` 备份
+    pub fn disable_backups() {
+        BACKUP_ENABLED.store(false, Ordering::Relaxed);
+    }
+
+    /// 先写入同目录下的临时文件再重命名，避免写到一半时留下损坏的配置
     pub fn save(&self) -> Result<()> {
-        let config_path = Self::get_config_path();
-        let toml_string =
-            toml::to_string_pretty(self).context("Failed to serialize config to TOML")?;
+        let config_path = self
+            .manifest_path
+            .clone()
+            .unwrap_or_else(Self::get_config_path);
+        Self::backup_once(&config_path)?;
 
-        fs::write(&config_path, toml_string)
-            .with_context(|| format!("Failed to write config file: {}", config_path.display()))?;
+        let file_name = config_path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| "config.toml".to_string());
+        let tmp_path =
+            config_path.with_file_name(format!(".{}.{}.tmp", file_name, std::process::id()));
+        fs::write(&tmp_path, self.doc.to_string())
+            .with_context(|| format!("Failed to write config file: {}", tmp_path.display()))?;
+        if let Err(e) = fs::rename(&tmp_path, &config_path) {
+            let _ = fs::remove_file(&tmp_path);
+            return Err(e).with_context(|| {
+                format!("Failed to write config file: {}", config_path.display())
+            });
+        }
 
         info!("💾 Saved configuration to {}", config_path.display());
         Ok(())
     }
 
-    fn get_config_dir() -> PathBuf {
+    /// 本次运行第一次修改某个文件前，将原内容复制到 `<文件名>.bak`
+    fn backup_once(path: &Path) -> Result<()> {
+        if !BACKUP_ENABLED.load(Ordering::Relaxed) || !path.exists() {
+            return Ok(());
+        }
+        let mut backed_up = BACKED_UP.lock().unwrap_or_else(|e| e.into_inner());
+        if !backed_up
+            .get_or_insert_with(HashSet::new)
+            .insert(path.to_path_buf())
+        {
+            return Ok(());
+        }
+
+        let mut backup_name = path.as_os_str().to_owned();
+        backup_name.push(".bak");
+        let backup_path = PathBuf::from(backup_name);
+        fs::copy(path, &backup_path).with_context(|| {
+            format!(
+                "Failed to back up {} to {}",
+                path.display(),
+                backup_path.display()
+            )
+        })?;
+        debug!("Backed up {} to {}", path.display(), backup_path.display());
+        Ok(())
+    }
+
+    pub fn get_config_dir() -> PathBuf {
         // 尝试获取当前工作目录的 .cargo 目录
         let current_dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
         let local_cargo_dir = current_dir.join(".cargo");
@@ -141,7 +389,140 @@ impl CargoConfig {
         local_cargo_dir
     }
 
-    fn get_config_path() -> PathBuf {
+    pub fn get_config_path() -> PathBuf {
         Self::get_config_dir().join("config.toml")
     }
+
+    /// 查找 `[registries.<name>]` 中配置的 index 地址
+    ///
+    /// 与 Cargo 一致，环境变量 `CARGO_REGISTRIES_<NAME>_INDEX` 优先，
+    /// 其次是项目的 `.cargo/config.toml`，最后是 `$CARGO_HOME/config.toml`
+    pub fn registry_index(name: &str) -> Result<Option<String>> {
+        let env_name = format!(
+            "CARGO_REGISTRIES_{}_INDEX",
+            name.to_uppercase().replace('-', "_")
+        );
+        if let Ok(index) = std::env::var(&env_name) {
+            return Ok(Some(index));
+        }
+
+        let cargo_home = std::env::var_os("CARGO_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cargo")));
+        let candidates = std::iter::once(Self::get_config_path())
+            .chain(cargo_home.map(|home| home.join("config.toml")));
+        for path in candidates.filter(|path| path.exists()) {
+            let config = Self::load_from_file(&path)?;
+            if let Some(index) = config
+                .doc
+                .get("registries")
+                .and_then(|registries| registries.get(name))
+                .and_then(|registry| registry.get("index"))
+                .and_then(|index| index.as_str())
+            {
+                return Ok(Some(index.to_string()));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// 将 `current_dir` 下的 `local_path` 表示为相对于 `base_dir` 的路径
+///
+/// 先按字面消去 `.` 和 `..`，`--path ../x` 这样的参数才能匹配上 `base_dir`；
+/// 不在 `base_dir` 下时使用规范化后的绝对路径
+fn relative_to(base_dir: &Path, current_dir: &Path, local_path: &Path) -> PathBuf {
+    let absolute = normalize(&current_dir.join(local_path));
+    match absolute.strip_prefix(normalize(base_dir)) {
+        Ok(rel_path) => rel_path.to_path_buf(),
+        Err(_) => absolute,
+    }
+}
+
+/// 不访问文件系统的路径规范化，根目录之上的 `..` 被忽略
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push(component);
+                }
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    #[test]
+    fn patch_added_from_a_nested_subdir_resolves_from_the_workspace_root() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().canonicalize().unwrap();
+        test_support::workspace(&root, &["crates/app"]);
+        let nested = root.join("tools/scripts");
+        fs::create_dir_all(&nested).unwrap();
+        fs::create_dir_all(root.join("patches/serde")).unwrap();
+
+        test_support::in_dir(&nested, || {
+            assert_eq!(CargoConfig::get_config_dir(), root.join(".cargo"));
+            let mut config = CargoConfig::load_or_create().unwrap();
+            config
+                .add_patch_with_source("serde", Path::new("../../patches/serde"), "crates-io")
+                .unwrap();
+            config.save().unwrap();
+        });
+
+        let config = CargoConfig::load_from_file(&root.join(".cargo/config.toml")).unwrap();
+        let patches = config.list_patches();
+        assert_eq!(patches.len(), 1);
+        let (_, _, path) = &patches[0];
+        assert_eq!(path, "patches/serde");
+        assert_eq!(
+            root.join(path).canonicalize().unwrap(),
+            root.join("patches/serde")
+        );
+    }
+
+    #[test]
+    fn paths_are_relative_to_the_workspace_config_from_a_member_dir() {
+        let root = Path::new("/ws");
+        let member = Path::new("/ws/crates/member");
+
+        assert_eq!(
+            relative_to(root, member, Path::new("patches/serde")),
+            Path::new("crates/member/patches/serde")
+        );
+        assert_eq!(
+            relative_to(root, member, Path::new("../../patches/serde")),
+            Path::new("patches/serde")
+        );
+        assert_eq!(
+            relative_to(root, member, Path::new("./../other/./serde")),
+            Path::new("crates/other/serde")
+        );
+        assert_eq!(
+            relative_to(root, member, Path::new("/ws/patches/serde")),
+            Path::new("patches/serde")
+        );
+    }
+
+    #[test]
+    fn paths_outside_the_workspace_stay_absolute_without_dot_dot() {
+        let root = Path::new("/ws");
+        let member = Path::new("/ws/crates/member");
+
+        let outside = relative_to(root, member, Path::new("../../../elsewhere/serde"));
+        assert_eq!(outside, Path::new("/elsewhere/serde"));
+        assert!(!outside
+            .components()
+            .any(|component| component == Component::ParentDir));
+    }
 }
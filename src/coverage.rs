@@ -0,0 +1,182 @@
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// 一次 `cargo llvm-cov` 运行的结果（来自 lcov 输出）
+#[derive(Debug, Default)]
+pub struct LcovData {
+    /// 源文件 -> 可执行行号 -> 执行次数
+    pub files: HashMap<PathBuf, BTreeMap<u32, u64>>,
+    pub summary: CoverageSummary,
+}
+
+/// 行和分支覆盖率汇总
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct CoverageSummary {
+    pub lines_covered: u64,
+    pub lines_total: u64,
+    pub line_percent: f64,
+    /// 只有以 `--branch` 收集（需要 nightly）时才有分支数据
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch_percent: Option<f64>,
+}
+
+/// 补丁新增但测试没有执行到的行
+#[derive(Debug, Serialize)]
+pub struct UncoveredLines {
+    pub file: String,
+    pub lines: Vec<u32>,
+}
+
+/// `coverage` 的结果
+#[derive(Debug, Serialize)]
+pub struct CoverageReport {
+    pub crate_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base: Option<String>,
+    pub patched: CoverageSummary,
+    /// base 版本的覆盖率，无法确定 base 或其测试无法运行时为空
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub baseline: Option<CoverageSummary>,
+    pub uncovered: Vec<UncoveredLines>,
+}
+
+/// 在 `crate_path` 运行 `cargo llvm-cov` 并解析 lcov 输出
+pub fn run_llvm_cov(crate_path: &Path, target_dir: &Path) -> Result<LcovData> {
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    fs::create_dir_all(target_dir)?;
+    let lcov_path = target_dir.join("lcov.info");
+
+    let status = Command::new(cargo)
+        .arg("llvm-cov")
+        .arg("--manifest-path")
+        .arg(crate_path.join("Cargo.toml"))
+        .arg("--target-dir")
+        .arg(target_dir)
+        .arg("--lcov")
+        .arg("--output-path")
+        .arg(&lcov_path)
+        .status()
+        .context("Failed to run cargo llvm-cov (install it with 'cargo install cargo-llvm-cov')")?;
+    if !status.success() {
+        return Err(anyhow!(
+            "cargo llvm-cov failed in {} (is cargo-llvm-cov installed?)",
+            crate_path.display()
+        ));
+    }
+
+    let content = fs::read_to_string(&lcov_path)
+        .with_context(|| format!("Failed to read {}", lcov_path.display()))?;
+    Ok(parse_lcov(&content))
+}
+
+/// 解析 lcov 格式，只关心 `SF`、`DA` 以及分支计数 `BRF`/`BRH`
+fn parse_lcov(content: &str) -> LcovData {
+    let mut data = LcovData::default();
+    let mut current: Option<PathBuf> = None;
+    let (mut branches_total, mut branches_hit) = (0u64, 0u64);
+
+    for line in content.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            if line == "end_of_record" {
+                current = None;
+            }
+            continue;
+        };
+        match key {
+            "SF" => current = Some(PathBuf::from(value)),
+            "DA" => {
+                let mut fields = value.split(',');
+                let (Some(lineno), Some(count)) = (
+                    fields.next().and_then(|f| f.parse::<u32>().ok()),
+                    fields.next().and_then(|f| f.parse::<u64>().ok()),
+                ) else {
+                    continue;
+                };
+                if let Some(file) = &current {
+                    data.files
+                        .entry(file.clone())
+                        .or_default()
+                        .insert(lineno, count);
+                }
+            }
+            "BRF" => branches_total += value.parse::<u64>().unwrap_or(0),
+            "BRH" => branches_hit += value.parse::<u64>().unwrap_or(0),
+            _ => {}
+        }
+    }
+
+    let lines = data.files.values().flat_map(BTreeMap::values);
+    let lines_total = lines.clone().count() as u64;
+    let lines_covered = lines.filter(|&&count| count > 0).count() as u64;
+    data.summary = CoverageSummary {
+        lines_covered,
+        lines_total,
+        line_percent: percent(lines_covered, lines_total),
+        branch_percent: (branches_total > 0).then(|| percent(branches_hit, branches_total)),
+    };
+    data
+}
+
+fn percent(hit: u64, total: u64) -> f64 {
+    if total == 0 {
+        100.0
+    } else {
+        hit as f64 * 100.0 / total as f64
+    }
+}
+
+/// 找出补丁新增、可执行但执行次数为 0 的行
+///
+/// `added` 的路径相对仓库根目录，lcov 中为绝对路径，比较前统一规范化
+pub fn uncovered_added_lines(
+    data: &LcovData,
+    repo_root: &Path,
+    added: &HashMap<PathBuf, Vec<u32>>,
+) -> Vec<UncoveredLines> {
+    let files: HashMap<PathBuf, &BTreeMap<u32, u64>> = data
+        .files
+        .iter()
+        .map(|(path, lines)| (fs::canonicalize(path).unwrap_or(path.clone()), lines))
+        .collect();
+
+    let mut uncovered: Vec<UncoveredLines> = added
+        .iter()
+        .filter_map(|(relative, added_lines)| {
+            let absolute = repo_root.join(relative);
+            let lines = files.get(&fs::canonicalize(&absolute).unwrap_or(absolute))?;
+            let missed: Vec<u32> = added_lines
+                .iter()
+                .copied()
+                .filter(|lineno| lines.get(lineno) == Some(&0))
+                .collect();
+            (!missed.is_empty()).then(|| UncoveredLines {
+                file: relative.display().to_string(),
+                lines: missed,
+            })
+        })
+        .collect();
+    uncovered.sort_by(|a, b| a.file.cmp(&b.file));
+    uncovered
+}
+
+/// 将行号列表压缩为 `3-5, 9` 的形式
+pub fn format_ranges(lines: &[u32]) -> String {
+    let mut ranges: Vec<String> = Vec::new();
+    let mut iter = lines.iter().copied().peekable();
+    while let Some(start) = iter.next() {
+        let mut end = start;
+        while iter.peek() == Some(&(end + 1)) {
+            end = iter.next().unwrap_or(end);
+        }
+        ranges.push(if start == end {
+            start.to_string()
+        } else {
+            format!("{start}-{end}")
+        });
+    }
+    ranges.join(", ")
+}
@@ -1,6 +1,60 @@
+use crate::resolve_cache::ResolveCache;
+use crate::retry;
 use anyhow::{anyhow, Result};
-use reqwest::Client;
+use reqwest::{Client, StatusCode};
 use serde::Deserialize;
+use std::fmt;
+use std::time::Duration;
+
+/// crates.io 的爬虫策略要求 User-Agent 能识别工具并附带联系方式
+const USER_AGENT: &str = concat!(
+    "cargo-lpatch/",
+    env!("CARGO_PKG_VERSION"),
+    " (+https://github.com/rcore-os/cargo-lpatch)"
+);
+
+/// 一次查询请求的失败原因
+#[derive(Debug)]
+enum RequestError {
+    Http(reqwest::Error),
+    /// HTTP 429，附带 `Retry-After` 中要求的等待时间
+    RateLimited(Option<Duration>),
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestError::Http(e) => write!(f, "{e}"),
+            RequestError::RateLimited(_) => write!(f, "rate limited (HTTP 429 Too Many Requests)"),
+        }
+    }
+}
+
+impl std::error::Error for RequestError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RequestError::Http(e) => Some(e),
+            RequestError::RateLimited(_) => None,
+        }
+    }
+}
+
+impl RequestError {
+    fn retry(&self) -> retry::Retry {
+        match self {
+            RequestError::Http(e) => retry::is_transient_http_error(e).into(),
+            RequestError::RateLimited(Some(wait)) => retry::Retry::After(*wait),
+            RequestError::RateLimited(None) => retry::Retry::Backoff,
+        }
+    }
+}
+
+/// 解析以秒为单位的 `Retry-After`，HTTP 日期格式不支持，按指数退避处理
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    let value = response.headers().get(reqwest::header::RETRY_AFTER)?;
+    let seconds = value.to_str().ok()?.trim().parse::<u64>().ok()?;
+    Some(Duration::from_secs(seconds))
+}
 
 #[derive(Debug, Deserialize)]
 struct CrateResponse {
@@ -8,44 +62,212 @@ struct CrateResponse {
     crate_info: CrateInfo,
 }
 
+/// sparse registry 根目录下的 `config.json`
+#[derive(Debug, Deserialize)]
+struct RegistryConfig {
+    api: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 struct CrateInfo {
+    name: String,
     repository: Option<String>,
 }
 
+/// crates.io 上解析得到的 crate 信息
+#[derive(Debug, Clone)]
+pub struct ResolvedCrate {
+    /// crates.io 上的规范名称（大小写可能与用户输入不同）
+    pub name: String,
+    pub repository_url: String,
+}
+
 pub struct CratesIoClient {
     client: Client,
+    registry: String,
     base_url: String,
+    auth_token: Option<String>,
+    basic_auth: Option<(String, String)>,
+    retries: u32,
+    cache: Option<ResolveCache>,
 }
 
 impl CratesIoClient {
     pub fn new() -> Self {
         Self {
             client: Client::new(),
+            registry: "crates.io".to_string(),
             base_url: "https://crates.io/api/v1".to_string(),
+            auth_token: None,
+            basic_auth: None,
+            retries: retry::DEFAULT_RETRIES,
+            cache: None,
         }
     }
 
-    pub async fn get_repository_url(&self, crate_name: &str) -> Result<String> {
-        let url = format!("{}/crates/{}", self.base_url, crate_name);
+    /// 为 `[registries.<name>]` 中声明的其他 registry 创建客户端
+    ///
+    /// Web API 地址来自 sparse index 中 `config.json` 的 `api` 字段，
+    /// token 来自环境变量 `CARGO_REGISTRIES_<NAME>_TOKEN`
+    pub async fn for_registry(name: &str, index: &str) -> Result<Self> {
+        let Some(index_url) = index.strip_prefix("sparse+") else {
+            return Err(anyhow!(
+                "Registry '{}' uses a git index ({}), only sparse registries (sparse+https://...) can be queried for repository URLs",
+                name,
+                index
+            ));
+        };
 
-        info!("Querying crates.io for crate: {crate_name}");
+        let mut client = Self::new();
+        client.registry = name.to_string();
+        let token_var = format!(
+            "CARGO_REGISTRIES_{}_TOKEN",
+            name.to_uppercase().replace('-', "_")
+        );
+        if let Ok(token) = std::env::var(&token_var) {
+            debug!("🔑 Using registry token from {token_var}");
+            client = client.with_auth_token(&token);
+        }
 
-        let response = self
+        let config_url = format!("{}/config.json", index_url.trim_end_matches('/'));
+        let mut request = client
             .client
-            .get(&url)
-            .header("User-Agent", "cargo-lpatch/0.1.0")
-            .send()
-            .await?;
+            .get(&config_url)
+            .header("User-Agent", USER_AGENT);
+        if let Some(token) = &client.auth_token {
+            request = request.bearer_auth(token);
+        }
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            return Err(anyhow!(
+                "Failed to fetch {}: HTTP {}",
+                config_url,
+                response.status()
+            ));
+        }
+
+        let config: RegistryConfig = response.json().await?;
+        let api = config
+            .api
+            .ok_or_else(|| anyhow!("Registry '{}' does not provide a web API", name))?;
+        client.base_url = format!("{}/api/v1", api.trim_end_matches('/'));
+        Ok(client)
+    }
+
+    /// 使用 `Authorization: Bearer <token>` 认证，优先于 basic auth
+    pub fn with_auth_token(mut self, token: &str) -> Self {
+        self.auth_token = Some(token.to_string());
+        self
+    }
+
+    /// 使用 HTTP basic auth 认证，仅在未设置 token 时生效
+    pub fn with_basic_auth(mut self, username: &str, password: &str) -> Self {
+        self.basic_auth = Some((username.to_string(), password.to_string()));
+        self
+    }
+
+    /// 设置查询遇到暂时性网络错误时的最多重试次数
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// 启用查询结果的磁盘缓存，`ttl` 内的重复查询不再访问 registry
+    ///
+    /// 缓存文件按 registry 区分，需要在选定 registry 之后调用
+    pub fn with_cache(mut self, ttl: Duration) -> Self {
+        match ResolveCache::open(&self.registry, ttl) {
+            Ok(cache) => self.cache = Some(cache),
+            Err(e) => debug!("Lookup cache disabled: {e}"),
+        }
+        self
+    }
+
+    /// 根据环境变量中的 registry 配置设置认证信息
+    ///
+    /// `CARGO_REGISTRY_TOKEN` 优先，其次是 `CARGO_REGISTRY_USERNAME` + `CARGO_REGISTRY_PASSWORD`
+    pub fn with_env_auth(mut self) -> Self {
+        if let Ok(token) = std::env::var("CARGO_REGISTRY_TOKEN") {
+            debug!("🔑 Using registry token from CARGO_REGISTRY_TOKEN");
+            self = self.with_auth_token(&token);
+        }
+        if let (Ok(username), Ok(password)) = (
+            std::env::var("CARGO_REGISTRY_USERNAME"),
+            std::env::var("CARGO_REGISTRY_PASSWORD"),
+        ) {
+            debug!("🔑 Using registry basic auth for user: {username}");
+            self = self.with_basic_auth(&username, &password);
+        }
+        self
+    }
+
+    /// 查询 crate 的规范名称和仓库地址
+    ///
+    /// crates.io 对名称大小写不敏感，查询时统一使用小写，并返回响应中的规范名称
+    pub async fn resolve_crate(&self, crate_name: &str) -> Result<ResolvedCrate> {
+        if let Some(resolved) = self.cache.as_ref().and_then(|cache| cache.get(crate_name)) {
+            info!(
+                "🗄️  Using cached {} lookup for crate: {crate_name}",
+                self.registry
+            );
+            return Ok(resolved);
+        }
+
+        let resolved = self.query_crate(crate_name).await?;
+        if let Some(cache) = &self.cache {
+            // 缓存只是优化，写入失败不影响本次查询
+            if let Err(e) = cache.put(crate_name, &resolved) {
+                warn!("⚠️  Failed to update the lookup cache: {e}");
+            }
+        }
+        Ok(resolved)
+    }
+
+    async fn query_crate(&self, crate_name: &str) -> Result<ResolvedCrate> {
+        let url = format!("{}/crates/{}", self.base_url, crate_name.to_lowercase());
+
+        info!("Querying {} for crate: {crate_name}", self.registry);
+
+        // 超时、连接失败、429 和 5xx 时重试，其他 HTTP 错误状态（404、认证失败等）直接返回
+        let response = retry::retry_with_backoff_async(
+            &format!("Querying {}", self.registry),
+            self.retries,
+            RequestError::retry,
+            || async {
+                let mut request = self.client.get(&url).header("User-Agent", USER_AGENT);
+                if let Some(token) = &self.auth_token {
+                    request = request.bearer_auth(token);
+                } else if let Some((username, password)) = &self.basic_auth {
+                    request = request.basic_auth(username, Some(password));
+                }
+                let response = request.send().await.map_err(RequestError::Http)?;
+                if response.status() == StatusCode::TOO_MANY_REQUESTS {
+                    return Err(RequestError::RateLimited(retry_after(&response)));
+                }
+                if response.status().is_server_error() {
+                    return Err(RequestError::Http(response.error_for_status().unwrap_err()));
+                }
+                Ok(response)
+            },
+        )
+        .await?;
 
         if response.status().is_success() {
             let crate_response: CrateResponse = response.json().await?;
 
+            let canonical_name = crate_response.crate_info.name;
+            if canonical_name != crate_name {
+                info!("🔤 Resolved '{crate_name}' to canonical crate name '{canonical_name}'");
+            }
+
             match crate_response.crate_info.repository {
                 Some(repo_url) => {
                     // 处理一些常见的仓库 URL 格式
                     let cleaned_url = self.clean_repository_url(&repo_url)?;
-                    Ok(cleaned_url)
+                    Ok(ResolvedCrate {
+                        name: canonical_name,
+                        repository_url: cleaned_url,
+                    })
                 }
                 None => Err(anyhow!(
                     "Crate '{}' does not have a repository URL",
@@ -93,3 +315,85 @@ impl CratesIoClient {
             || url.contains("git@")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resolve_cache;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// 对任何请求都返回 `body` 的 HTTP 服务，记录收到的请求路径
+    async fn mock_registry(body: &'static str) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let paths = Arc::new(Mutex::new(Vec::new()));
+        let recorded = paths.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    if n == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buf[..n]);
+                }
+                let request = String::from_utf8_lossy(&request);
+                if let Some(path) = request.split_whitespace().nth(1) {
+                    recorded.lock().unwrap().push(path.to_string());
+                }
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        (format!("http://{addr}/api/v1"), paths)
+    }
+
+    fn test_client(base_url: String) -> CratesIoClient {
+        let mut client = CratesIoClient::new().with_retries(0);
+        client.client = Client::builder().no_proxy().build().unwrap();
+        client.base_url = base_url;
+        client
+    }
+
+    const SERDE: &str =
+        r#"{"crate":{"name":"serde","repository":"https://github.com/serde-rs/serde"}}"#;
+
+    #[tokio::test]
+    async fn differently_cased_name_resolves_to_canonical_crate() {
+        let (base_url, paths) = mock_registry(SERDE).await;
+        let resolved = test_client(base_url).resolve_crate("Serde").await.unwrap();
+
+        assert_eq!(resolved.name, "serde");
+        assert_eq!(
+            resolved.repository_url,
+            "https://github.com/serde-rs/serde.git"
+        );
+        assert_eq!(*paths.lock().unwrap(), ["/api/v1/crates/serde"]);
+    }
+
+    #[tokio::test]
+    async fn cached_lookup_is_case_insensitive() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (base_url, paths) = mock_registry(SERDE).await;
+        let mut client = test_client(base_url);
+        client.cache = Some(ResolveCache::new(
+            tmp.path().join("crates-io.json"),
+            resolve_cache::DEFAULT_TTL,
+        ));
+
+        let first = client.resolve_crate("Serde").await.unwrap();
+        let second = client.resolve_crate("SERDE").await.unwrap();
+        assert_eq!(first.name, "serde");
+        assert_eq!(second.name, "serde");
+        assert_eq!(second.repository_url, first.repository_url);
+        assert_eq!(paths.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,141 @@
+use crate::config::CargoConfig;
+use crate::git::GitOperations;
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// 指向同一上游仓库的一组克隆
+#[derive(Debug, Serialize)]
+pub struct DedupGroup {
+    pub url: String,
+    /// 保留下来的克隆
+    pub keeper: PathBuf,
+    pub duplicates: Vec<DuplicateClone>,
+}
+
+/// 可以合并到 keeper 中的重复克隆
+#[derive(Debug, Serialize)]
+pub struct DuplicateClone {
+    pub clone: PathBuf,
+    /// 需要改写的 patch：(source, crate_name, 合并后的新路径)
+    pub patches: Vec<(String, String, PathBuf)>,
+    /// 不能安全合并的原因
+    pub blocked: Option<String>,
+}
+
+/// 以 origin URL 识别同一仓库，忽略 `.git` 后缀和结尾的 `/`
+pub fn normalize_url(url: &str) -> String {
+    url.trim_end_matches('/')
+        .trim_end_matches(".git")
+        .to_ascii_lowercase()
+}
+
+/// 找出共享同一 origin 的克隆，并计算合并方案
+///
+/// 保留 patch 最多的克隆（数量相同时取路径最小的），
+/// 只有工作区干净且与 keeper 检出同一提交的克隆才会被合并
+pub fn plan(cargo_config: &CargoConfig) -> Result<Vec<DedupGroup>> {
+    let git_ops = GitOperations::new();
+
+    // origin URL -> 克隆根目录 -> 该克隆中的 patch (source, name, 相对克隆根目录的子路径)
+    type ClonePatches = BTreeMap<PathBuf, Vec<(String, String, PathBuf)>>;
+    let mut by_url: BTreeMap<String, (String, ClonePatches)> = BTreeMap::new();
+    for (source, name, path) in cargo_config.list_patches() {
+        let patch_path = CargoConfig::resolve_patch_path(&path);
+        let Ok(root) = git_ops.repo_root(&patch_path) else {
+            continue;
+        };
+        let Ok(url) = git_ops.origin_url(&root) else {
+            continue;
+        };
+        let root = root.canonicalize().unwrap_or(root);
+        let patch_path = patch_path.canonicalize().unwrap_or(patch_path);
+        let subpath = patch_path
+            .strip_prefix(&root)
+            .map(PathBuf::from)
+            .unwrap_or_default();
+
+        by_url
+            .entry(normalize_url(&url))
+            .or_insert_with(|| (url, BTreeMap::new()))
+            .1
+            .entry(root)
+            .or_default()
+            .push((source, name, subpath));
+    }
+
+    let mut groups = Vec::new();
+    for (url, clones) in by_url.into_values() {
+        if clones.len() < 2 {
+            continue;
+        }
+
+        let keeper = clones
+            .iter()
+            .max_by(|(a_root, a), (b_root, b)| a.len().cmp(&b.len()).then(b_root.cmp(a_root)))
+            .map(|(root, _)| root.clone())
+            .unwrap();
+        let keeper_head = git_ops.head_commit(&keeper).ok();
+
+        let duplicates = clones
+            .into_iter()
+            .filter(|(root, _)| *root != keeper)
+            .map(|(clone, patches)| {
+                let patches: Vec<_> = patches
+                    .into_iter()
+                    .map(|(source, name, subpath)| {
+                        let new_path = if subpath.as_os_str().is_empty() {
+                            keeper.clone()
+                        } else {
+                            keeper.join(subpath)
+                        };
+                        (source, name, new_path)
+                    })
+                    .collect();
+                let blocked = blocked_reason(&git_ops, &clone, keeper_head, &patches);
+                DuplicateClone {
+                    clone,
+                    patches,
+                    blocked,
+                }
+            })
+            .collect();
+
+        groups.push(DedupGroup {
+            url,
+            keeper,
+            duplicates,
+        });
+    }
+
+    Ok(groups)
+}
+
+fn blocked_reason(
+    git_ops: &GitOperations,
+    clone: &std::path::Path,
+    keeper_head: Option<git2::Oid>,
+    patches: &[(String, String, PathBuf)],
+) -> Option<String> {
+    match git_ops.is_dirty(clone) {
+        Ok(false) => {}
+        Ok(true) => return Some("has uncommitted changes".to_string()),
+        Err(e) => return Some(format!("failed to read status: {e}")),
+    }
+
+    let head = git_ops.head_commit(clone).ok();
+    if head.is_none() || head != keeper_head {
+        return Some("checked out at a different commit than the kept clone".to_string());
+    }
+
+    patches
+        .iter()
+        .find(|(_, _, new_path)| !new_path.join("Cargo.toml").exists())
+        .map(|(_, name, new_path)| {
+            format!(
+                "'{name}' not found at {} in the kept clone",
+                new_path.display()
+            )
+        })
+}
@@ -0,0 +1,119 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+
+use crate::config::CargoConfig;
+
+/// 诊断问题的严重程度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// `doctor` 发现的一个问题及建议的修复方式
+#[derive(Debug, Clone, Serialize)]
+pub struct DiagnosticIssue {
+    pub severity: Severity,
+    pub message: String,
+    pub suggestion: String,
+}
+
+impl DiagnosticIssue {
+    fn new(severity: Severity, message: String, suggestion: String) -> Self {
+        Self {
+            severity,
+            message,
+            suggestion,
+        }
+    }
+}
+
+/// 检查配置中的补丁是否与磁盘上的 crate 一致
+///
+/// 配置文件本身能否解析由调用方在加载时检查
+pub fn diagnose(config: &CargoConfig) -> Vec<DiagnosticIssue> {
+    let mut issues = Vec::new();
+    let mut sources_by_crate: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+    for (source, name, path) in config.list_patches() {
+        sources_by_crate
+            .entry(name.clone())
+            .or_default()
+            .push(source.clone());
+
+        let patch_path = CargoConfig::resolve_patch_path(&path);
+        if !patch_path.exists() {
+            issues.push(DiagnosticIssue::new(
+                Severity::Error,
+                format!("Patch for '{name}' ({source}) points to missing path '{path}'"),
+                format!("Re-create it with 'cargo lpatch --name {name}' or remove it with 'cargo lpatch unpatch {name}'"),
+            ));
+            continue;
+        }
+
+        let manifest_path = patch_path.join("Cargo.toml");
+        let package_name = fs::read_to_string(&manifest_path)
+            .ok()
+            .and_then(|content| content.parse::<toml::Table>().ok())
+            .map(|manifest| {
+                manifest
+                    .get("package")
+                    .and_then(|package| package.get("name"))
+                    .and_then(|name| name.as_str())
+                    .map(str::to_string)
+            });
+        match package_name {
+            None => issues.push(DiagnosticIssue::new(
+                Severity::Error,
+                format!(
+                    "'{}' has no readable Cargo.toml",
+                    manifest_path.parent().unwrap_or(&patch_path).display()
+                ),
+                format!("Point the patch for '{name}' at the directory containing the crate's Cargo.toml"),
+            )),
+            Some(None) => issues.push(DiagnosticIssue::new(
+                Severity::Error,
+                format!("'{path}' is not a package (no [package] name), a workspace root?"),
+                format!("Re-run 'cargo lpatch --name {name}' to locate '{name}' inside the workspace"),
+            )),
+            Some(Some(package_name)) if package_name != name => {
+                issues.push(DiagnosticIssue::new(
+                    Severity::Error,
+                    format!("Patch for '{name}' points to package '{package_name}' at '{path}'"),
+                    format!("Fix the path of '{name}' in {}", CargoConfig::get_config_path().display()),
+                ))
+            }
+            Some(Some(_)) => {}
+        }
+    }
+
+    for (name, sources) in sources_by_crate {
+        if sources.len() > 1 {
+            issues.push(DiagnosticIssue::new(
+                Severity::Warning,
+                format!(
+                    "'{name}' is patched in multiple sources: {}",
+                    sources.join(", ")
+                ),
+                format!(
+                    "Keep only the source your dependency graph uses and remove the other entries from {}",
+                    CargoConfig::get_config_path().display()
+                ),
+            ));
+        }
+    }
+
+    issues
+}
@@ -0,0 +1,134 @@
+use crate::config::CargoConfig;
+use crate::timings::Timings;
+use serde::Serialize;
+use std::fmt;
+use std::path::PathBuf;
+
+/// lpatch 主流程中会对磁盘产生修改的一个动作
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum PlannedAction {
+    CreateDir {
+        path: PathBuf,
+    },
+    Clone {
+        url: String,
+        path: PathBuf,
+        reference: Option<String>,
+    },
+    Pull {
+        path: PathBuf,
+    },
+    Checkout {
+        path: PathBuf,
+        reference: String,
+    },
+    WritePatch {
+        config: PathBuf,
+        crate_name: String,
+        source: String,
+        path: PathBuf,
+    },
+    RemoveReplacement {
+        manifest: PathBuf,
+        spec: String,
+    },
+}
+
+impl fmt::Display for PlannedAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlannedAction::CreateDir { path } => write!(f, "create directory {}", path.display()),
+            PlannedAction::Clone {
+                url,
+                path,
+                reference,
+            } => {
+                write!(f, "clone {url} into {}", path.display())?;
+                if let Some(reference) = reference {
+                    write!(f, " at {reference}")?;
+                }
+                Ok(())
+            }
+            PlannedAction::Pull { path } => write!(f, "pull latest changes in {}", path.display()),
+            PlannedAction::Checkout { path, reference } => {
+                write!(f, "check out {reference} in {}", path.display())
+            }
+            PlannedAction::WritePatch {
+                config,
+                crate_name,
+                source,
+                path,
+            } => write!(
+                f,
+                "write {} {crate_name} = {{ path = \"{}\" }} to {}",
+                CargoConfig::patch_table_header(source),
+                path.display(),
+                config.display()
+            ),
+            PlannedAction::RemoveReplacement { manifest, spec } => {
+                write!(
+                    f,
+                    "remove [replace] entry '{spec}' from {}",
+                    manifest.display()
+                )
+            }
+        }
+    }
+}
+
+/// 补丁将指向的 crate 路径
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum PlannedCratePath {
+    /// 已在（已有的）克隆中找到 crate
+    Resolved { path: PathBuf },
+    /// 仓库尚未克隆，无法检测 workspace，暂时假定 crate 位于仓库根目录
+    RequiresClone { assumed: PathBuf },
+}
+
+impl fmt::Display for PlannedCratePath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlannedCratePath::Resolved { path } => write!(f, "{}", path.display()),
+            PlannedCratePath::RequiresClone { assumed } => write!(
+                f,
+                "{} (requires clone to detect workspace members)",
+                assumed.display()
+            ),
+        }
+    }
+}
+
+/// lpatch 主流程执行（或在 `--dry-run` 下将要执行）的动作列表
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DryRunPlan {
+    #[serde(skip)]
+    pub dry_run: bool,
+    /// 仓库的克隆位置
+    pub clone_path: Option<PathBuf>,
+    /// crate 在克隆中的位置
+    pub crate_path: Option<PlannedCratePath>,
+    pub actions: Vec<PlannedAction>,
+    /// `--trace-timings` 时各阶段的耗时
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timings: Option<Timings>,
+}
+
+impl DryRunPlan {
+    pub fn new(dry_run: bool) -> Self {
+        Self {
+            dry_run,
+            ..Default::default()
+        }
+    }
+
+    /// 记录一个动作，返回是否应当真正执行
+    pub fn record(&mut self, action: PlannedAction) -> bool {
+        if self.dry_run {
+            info!("🔍 Would {action}");
+        }
+        self.actions.push(action);
+        !self.dry_run
+    }
+}
@@ -1,18 +1,88 @@
+use crate::retry;
+use crate::ssh_config::{ssh_host, SshConfigParser};
 use anyhow::{Context, Result};
 use git2::build::{CheckoutBuilder, RepoBuilder};
-use git2::{Cred, CredentialType, FetchOptions, RemoteCallbacks, Repository};
+use git2::{
+    BranchType, Cred, CredentialType, DescribeFormatOptions, DescribeOptions, DiffFormat,
+    Direction, FetchOptions, IndexEntryExtendedFlag, IndexEntryFlag, Oid, ProxyOptions,
+    RemoteCallbacks, Repository, Status, StatusOptions, TreeWalkMode, TreeWalkResult,
+};
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use log::{debug, info, warn};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
 use std::env;
+use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 
+/// 单个提交的摘要信息
+#[derive(Debug, Clone, Serialize)]
+pub struct CommitSummary {
+    pub id: String,
+    pub summary: String,
+    pub author: String,
+}
+
+/// 两个提交之间的差异统计
+#[derive(Debug, Clone, Copy)]
+pub struct DiffStat {
+    pub files_changed: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
+/// 工作区中单个文件的状态，`code` 与 `git status --short` 的两列状态码一致
+#[derive(Debug, Clone, Serialize)]
+pub struct FileStatus {
+    pub code: String,
+    pub path: String,
+}
+
+/// 将本地分支快进到上游分支的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FastForwardOutcome {
+    /// 已经是最新
+    UpToDate,
+    /// 已从 `from` 快进到 `to`
+    FastForwarded { from: Oid, to: Oid },
+    /// 本地分支与上游已分叉，需要手动合并
+    Diverged,
+    /// 工作区有未提交的修改，未做快进
+    LocalChanges,
+    /// 未处于分支上（检出的是 tag 或 rev），或分支没有上游
+    NoUpstream,
+}
+
+/// git 依赖中指定的引用
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GitReference {
+    Branch(String),
+    Tag(String),
+    Rev(String),
+}
+
+impl std::fmt::Display for GitReference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GitReference::Branch(branch) => write!(f, "branch '{branch}'"),
+            GitReference::Tag(tag) => write!(f, "tag '{tag}'"),
+            GitReference::Rev(rev) => write!(f, "rev '{rev}'"),
+        }
+    }
+}
+
 pub struct GitOperations {
     username: String,
     credential_helper: Option<String>,
     http_sslverify: bool,
+    http_proxy: Option<String>, // Git 配置中的 http.proxy，优先于环境变量
     ssh_agent_tried: Arc<AtomicBool>,
+    depth: Option<u32>, // 浅克隆深度，None 表示不限制，0 表示获取完整历史
+    no_checkout: bool,  // 克隆时不检出文件，由稀疏检出决定写入哪些文件
+    progress: Option<MultiProgress>, // 并发克隆时共享的进度条容器
+    retries: u32,       // 克隆遇到暂时性网络错误时的重试次数
 }
 
 impl GitOperations {
@@ -21,7 +91,12 @@ impl GitOperations {
             username: "git".into(),
             credential_helper: None,
             http_sslverify: true,
+            http_proxy: None,
             ssh_agent_tried: Arc::new(AtomicBool::new(false)),
+            depth: None,
+            no_checkout: false,
+            progress: None,
+            retries: retry::DEFAULT_RETRIES,
         };
 
         if let Ok(config) = git2::Config::open_default() {
@@ -38,15 +113,79 @@ impl GitOperations {
                 s.http_sslverify = ssl_verify;
                 debug!("  🔒 SSL verify: {ssl_verify}");
             }
+            if let Ok(proxy) = config.get_string("http.proxy") {
+                if !proxy.is_empty() {
+                    debug!("  🌐 HTTP proxy from Git config");
+                    s.http_proxy = Some(proxy);
+                }
+            }
         } else {
             warn!("⚠️  No global Git configuration found, using defaults");
         }
         s
     }
 
+    /// 设置浅克隆深度，同时作用于后续的 fetch；深度为 0 时会补全浅克隆的历史
+    pub fn with_depth(mut self, depth: Option<u32>) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// 设置克隆遇到暂时性网络错误时的最多重试次数
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// 将进度条加入共享的 `MultiProgress`，多个克隆并发时各自的进度条不会互相覆盖
+    pub fn with_progress(mut self, progress: MultiProgress) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// 克隆时只建立索引而不写入文件，之后由 [`Self::sparse_checkout`] 检出需要的目录
+    pub fn with_no_checkout(mut self) -> Self {
+        self.no_checkout = true;
+        self
+    }
+
+    fn apply_depth(&self, fetch_options: &mut FetchOptions<'_>, shallow_repo: bool) {
+        match self.depth {
+            Some(0) if shallow_repo => {
+                // 等同于 libgit2 的 GIT_FETCH_DEPTH_UNSHALLOW
+                fetch_options.depth(i32::MAX);
+            }
+            Some(0) | None => {}
+            Some(depth) => {
+                fetch_options.depth(depth.min(i32::MAX as u32) as i32);
+            }
+        }
+    }
+
+    /// 访问 `url` 时使用的代理，只对 http(s) 地址生效
+    ///
+    /// libgit2 通过 CONNECT 隧道使用代理，明文 http 地址仍会直接连接服务器
+    fn proxy_for(&self, url: &str) -> Option<String> {
+        select_proxy(url, self.http_proxy.as_deref(), |var| env::var(var).ok())
+    }
+
+    fn proxy_options(&self, url: &str) -> Option<ProxyOptions<'static>> {
+        let proxy = self.proxy_for(url)?;
+        debug!("🌐 Using a proxy for {url}");
+        let mut options = ProxyOptions::new();
+        options.url(&proxy);
+        Some(options)
+    }
+
+    /// 仓库是否为浅克隆
+    pub fn is_shallow(&self, repo_path: &Path) -> bool {
+        Repository::discover(repo_path).is_ok_and(|repo| repo.is_shallow())
+    }
+
     /// 尝试 SSH 密钥认证（使用系统配置的 SSH 设置）
     fn try_ssh_key_auth(
         ssh_agent_tried: Arc<AtomicBool>,
+        url: &str,
         username: &str,
     ) -> Result<Cred, git2::Error> {
         debug!("🔑 Trying SSH authentication for user: {username}");
@@ -64,7 +203,7 @@ impl GitOperations {
         }
 
         // 2. 尝试使用系统中配置的 SSH 密钥文件（按系统标准路径查找）
-        let ssh_key_paths = GitOperations::get_ssh_key_paths();
+        let ssh_key_paths = GitOperations::get_ssh_key_paths(ssh_host(url).as_deref());
 
         for (private_key, public_key) in ssh_key_paths {
             if private_key.exists() {
@@ -130,9 +269,22 @@ impl GitOperations {
     }
 
     /// 获取系统标准 SSH 密钥路径（遵循系统惯例）
-    fn get_ssh_key_paths() -> Vec<(PathBuf, PathBuf)> {
+    ///
+    /// `~/.ssh/config` 中为 `host` 配置的 `IdentityFile` 排在默认密钥之前
+    fn get_ssh_key_paths(host: Option<&str>) -> Vec<(PathBuf, PathBuf)> {
         let mut key_paths = Vec::new();
 
+        if let Some(identity) = host.and_then(SshConfigParser::find_identity_for_host) {
+            debug!(
+                "🔑 ~/.ssh/config sets IdentityFile {} for {}",
+                identity.display(),
+                host.unwrap_or_default()
+            );
+            let mut public_key = identity.clone().into_os_string();
+            public_key.push(".pub");
+            key_paths.push((identity, PathBuf::from(public_key)));
+        }
+
         // 获取用户主目录（使用系统环境变量）
         let home_dir = if cfg!(windows) {
             env::var("USERPROFILE").unwrap_or_else(|_| {
@@ -161,7 +313,7 @@ impl GitOperations {
         key_paths
     }
 
-    fn remote_callbacks(&self) -> RemoteCallbacks {
+    fn remote_callbacks(&self) -> RemoteCallbacks<'_> {
         let mut callbacks = RemoteCallbacks::new();
         let ssh_agent_tried = Arc::clone(&self.ssh_agent_tried);
         callbacks.credentials(move |url, username_from_url, allowed_types| {
@@ -169,6 +321,7 @@ impl GitOperations {
             if allowed_types.contains(CredentialType::SSH_KEY) {
                 return Self::try_ssh_key_auth(
                     ssh_agent_tried.clone(),
+                    url,
                     username_from_url.unwrap_or(&self.username),
                 );
             } else if allowed_types.contains(CredentialType::USER_PASS_PLAINTEXT) {
@@ -176,19 +329,39 @@ impl GitOperations {
             }
             Cred::default()
         });
-        callbacks.certificate_check(|_cert, _valid| {
-            // 在生产环境中，应该遵循系统 Git 配置中的 http.sslVerify 设置
-            // 用户可以通过以下命令配置：
-            // git config --global http.sslVerify true/false
-            // 这里为了兼容性暂时接受证书，实际项目中应该根据系统配置来决定
-            Ok(git2::CertificateCheckStatus::CertificateOk)
+        // 遵循 Git 配置中的 http.sslVerify：默认交给 libgit2 校验证书，
+        // 只有用户显式关闭校验时才接受无效证书
+        let ssl_verify = self.http_sslverify;
+        callbacks.certificate_check(move |_cert, host| {
+            if ssl_verify {
+                Ok(git2::CertificateCheckStatus::CertificatePassthrough)
+            } else {
+                warn!("⚠️  Skipping certificate verification for {host} (http.sslVerify = false)");
+                Ok(git2::CertificateCheckStatus::CertificateOk)
+            }
         });
         callbacks
     }
 
-    pub fn clone(&self, url: &str, target_path: &Path) -> Result<()> {
+    /// 克隆仓库，指定 `branch` 时直接检出该分支
+    pub fn clone(&self, url: &str, target_path: &Path, branch: Option<&str>) -> Result<()> {
+        self.clone_repository(url, target_path, branch, false)
+    }
+
+    /// 克隆为裸仓库，用作多个 worktree 共享的对象库
+    pub fn clone_bare(&self, url: &str, target_path: &Path) -> Result<()> {
+        self.clone_repository(url, target_path, None, true)
+    }
+
+    fn clone_repository(
+        &self,
+        url: &str,
+        target_path: &Path,
+        branch: Option<&str>,
+        bare: bool,
+    ) -> Result<()> {
         info!("🔄 Cloning {} to {}...", url, target_path.display());
-        let multi_pb = MultiProgress::new();
+        let multi_pb = self.progress.clone().unwrap_or_default();
         // 创建传输进度条
         let transfer_pb = multi_pb.add(ProgressBar::new(100));
         transfer_pb.set_style(
@@ -219,70 +392,98 @@ impl GitOperations {
         );
         checkout_pb.set_message("Checking out");
 
-        let mut cb = self.remote_callbacks();
+        // 克隆失败时 libgit2 可能留下不完整的目录，重试前需要清理
+        let existed = target_path.exists();
+        let attempt = || {
+            let mut cb = self.remote_callbacks();
 
-        // 改进的传输进度回调
-        let transfer_pb_clone = transfer_pb.clone();
-        let resolving_pb_clone = resolving_pb.clone();
-        cb.transfer_progress(move |stats| {
-            if stats.total_objects() == 0 || stats.received_objects() == stats.total_objects() {
-                transfer_pb_clone.finish_with_message("✅ Download complete");
-            } else if stats.received_objects() > 0 {
-                // 显示传输进度
-                transfer_pb_clone.set_length(stats.total_objects() as u64);
-                transfer_pb_clone.set_position(stats.received_objects() as u64);
+            // 改进的传输进度回调
+            let transfer_pb_clone = transfer_pb.clone();
+            let resolving_pb_clone = resolving_pb.clone();
+            cb.transfer_progress(move |stats| {
+                if stats.total_objects() == 0 || stats.received_objects() == stats.total_objects() {
+                    transfer_pb_clone.finish_with_message("✅ Download complete");
+                } else if stats.received_objects() > 0 {
+                    // 显示传输进度
+                    transfer_pb_clone.set_length(stats.total_objects() as u64);
+                    transfer_pb_clone.set_position(stats.received_objects() as u64);
 
-                let bytes_msg = if stats.received_bytes() > 1024 * 1024 {
-                    format!("{:.1} MB", stats.received_bytes() as f64 / 1024.0 / 1024.0)
-                } else if stats.received_bytes() > 1024 {
-                    format!("{:.1} KB", stats.received_bytes() as f64 / 1024.0)
-                } else {
-                    format!("{} bytes", stats.received_bytes())
-                };
-                transfer_pb_clone.set_message(format!("Downloading ({bytes_msg})"));
-            }
+                    let bytes_msg = if stats.received_bytes() > 1024 * 1024 {
+                        format!("{:.1} MB", stats.received_bytes() as f64 / 1024.0 / 1024.0)
+                    } else if stats.received_bytes() > 1024 {
+                        format!("{:.1} KB", stats.received_bytes() as f64 / 1024.0)
+                    } else {
+                        format!("{} bytes", stats.received_bytes())
+                    };
+                    transfer_pb_clone.set_message(format!("Downloading ({bytes_msg})"));
+                }
 
-            if stats.total_deltas() == 0 || stats.indexed_deltas() == stats.total_deltas() {
-                resolving_pb_clone.finish_with_message("✅ Resolution complete");
-            } else if stats.indexed_deltas() > 0 {
-                // 显示解压进度
-                resolving_pb_clone.set_length(stats.total_deltas() as u64);
-                resolving_pb_clone.set_position(stats.indexed_deltas() as u64);
-                let p = stats.indexed_deltas() as f64 / stats.total_deltas() as f64 * 100.0;
-                resolving_pb_clone.set_message(format!("Resolving ({p:.1}%)"));
-            }
+                if stats.total_deltas() == 0 || stats.indexed_deltas() == stats.total_deltas() {
+                    resolving_pb_clone.finish_with_message("✅ Resolution complete");
+                } else if stats.indexed_deltas() > 0 {
+                    // 显示解压进度
+                    resolving_pb_clone.set_length(stats.total_deltas() as u64);
+                    resolving_pb_clone.set_position(stats.indexed_deltas() as u64);
+                    let p = stats.indexed_deltas() as f64 / stats.total_deltas() as f64 * 100.0;
+                    resolving_pb_clone.set_message(format!("Resolving ({p:.1}%)"));
+                }
 
-            true
-        });
+                true
+            });
 
-        // 改进的检出进度回调
-        let mut co = CheckoutBuilder::new();
-        let checkout_pb_clone = checkout_pb.clone();
-        co.progress(move |_path, cur, total| {
-            if total > 0 {
-                checkout_pb_clone.set_length(total as u64);
-                checkout_pb_clone.set_position(cur as u64);
+            // 改进的检出进度回调
+            let mut co = CheckoutBuilder::new();
+            let checkout_pb_clone = checkout_pb.clone();
+            co.progress(move |_path, cur, total| {
+                if total > 0 {
+                    checkout_pb_clone.set_length(total as u64);
+                    checkout_pb_clone.set_position(cur as u64);
 
-                if cur == total {
-                    checkout_pb_clone.finish_with_message("Checkout complete");
+                    if cur == total {
+                        checkout_pb_clone.finish_with_message("Checkout complete");
+                    }
                 }
+            });
+
+            if self.no_checkout {
+                co.dry_run();
             }
-        });
 
-        let mut fo = FetchOptions::new();
-        fo.remote_callbacks(cb);
+            let mut fo = FetchOptions::new();
+            fo.remote_callbacks(cb);
+            self.apply_depth(&mut fo, false);
+            if let Some(proxy) = self.proxy_options(url) {
+                fo.proxy_options(proxy);
+            }
 
-        let mut builder = RepoBuilder::new();
-        builder.fetch_options(fo).with_checkout(co);
+            let mut builder = RepoBuilder::new();
+            builder.fetch_options(fo).with_checkout(co).bare(bare);
+            if let Some(branch) = branch {
+                builder.branch(branch);
+            }
+            let result = builder.clone(url, target_path);
+            if result.is_err() && !existed && target_path.exists() {
+                let _ = fs::remove_dir_all(target_path);
+            }
+            result
+        };
 
-        match builder.clone(url, target_path) {
+        match retry::retry_with_backoff(
+            &format!("Cloning {url}"),
+            self.retries,
+            retry::is_transient_git_error,
+            attempt,
+        ) {
             Ok(_) => {
                 // 确保所有进度条都完成
                 transfer_pb.finish_with_message("✅ Download complete");
                 resolving_pb.finish_with_message("✅ Resolution complete");
                 checkout_pb.finish_with_message("✅ Checkout complete");
                 info!("✅ Clone completed successfully");
-                multi_pb.clear().unwrap();
+                // 只移除自己的进度条，共享容器中可能还有其他克隆
+                for pb in [&transfer_pb, &resolving_pb, &checkout_pb] {
+                    multi_pb.remove(pb);
+                }
                 Ok(())
             }
             Err(e) => {
@@ -325,21 +526,34 @@ impl GitOperations {
                     }
                     _ => format!("Git clone failed for {url}: {e}"),
                 };
-                multi_pb.clear().unwrap();
+                for pb in [&transfer_pb, &resolving_pb, &checkout_pb] {
+                    multi_pb.remove(pb);
+                }
                 Err(anyhow::anyhow!(error_msg))
             }
         }
     }
 
-    pub fn pull(&self, repo_path: &Path) -> Result<()> {
+    /// 拉取当前分支的上游更新，能快进时快进本地分支和工作区
+    ///
+    /// 本地分支已分叉或工作区有未提交修改时只 fetch，不改动用户的工作
+    pub fn pull(&self, repo_path: &Path) -> Result<FastForwardOutcome> {
         info!("🔄 Pulling latest changes in {}...", repo_path.display());
 
         let repo = Repository::open(repo_path)
             .with_context(|| format!("Failed to open repository at {}", repo_path.display()))?;
 
-        // 获取当前分支
+        // 获取当前分支对应的远程分支（本地分支名可能与上游不同，例如 worktree 中的分支）
         let head = repo.head()?;
-        let branch_name = head.shorthand().unwrap_or("HEAD");
+        let upstream_merge = head
+            .name()
+            .and_then(|name| repo.branch_upstream_merge(name).ok())
+            .and_then(|merge| merge.as_str().map(str::to_string));
+        let branch_name = upstream_merge
+            .as_deref()
+            .and_then(|merge| merge.strip_prefix("refs/heads/"))
+            .or_else(|| head.shorthand())
+            .unwrap_or("HEAD");
 
         // 获取远程仓库 (通常是 origin)
         let mut remote = repo
@@ -350,7 +564,10 @@ impl GitOperations {
         let mut callbacks = self.remote_callbacks();
 
         // 创建拉取进度条
-        let pull_pb = ProgressBar::new(100);
+        let pull_pb = match &self.progress {
+            Some(progress) => progress.add(ProgressBar::new(100)),
+            None => ProgressBar::new(100),
+        };
         pull_pb.set_style(
             ProgressStyle::default_bar()
                 .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos:>7}/{len:7} objects ({msg})")
@@ -381,44 +598,790 @@ impl GitOperations {
 
         let mut fetch_options = FetchOptions::new();
         fetch_options.remote_callbacks(callbacks);
+        self.apply_depth(&mut fetch_options, repo.is_shallow());
+        if let Some(proxy) = self.proxy_options(remote.url().unwrap_or_default()) {
+            fetch_options.proxy_options(proxy);
+        }
 
         // 获取远程更新
         let fetch_result = remote.fetch(&[branch_name], Some(&mut fetch_options), None);
 
-        match fetch_result {
-            Ok(_) => {
-                pull_pb.finish_with_message("✅ Fetch complete");
-
-                // 获取远程分支的 OID
-                let fetch_head = repo.fetchhead_foreach(|ref_name, remote_url, _oid, is_merge| {
-                    let remote_url_str = String::from_utf8_lossy(remote_url);
-                    info!("📥 Fetched {ref_name} from {remote_url_str}");
-                    if is_merge {
-                        // 这里可以进行合并操作，但为了简单起见，我们只提示用户
-                        info!(
-                            "💡 Note: You may need to manually merge changes in {}",
-                            repo_path.display()
-                        );
+        if let Err(e) = fetch_result {
+            pull_pb.abandon_with_message("❌ Fetch failed");
+            return Err(anyhow::anyhow!("Failed to fetch from remote: {}", e));
+        }
+        pull_pb.finish_with_message("✅ Fetch complete");
+
+        // 从 FETCH_HEAD 中找到需要合并的提交
+        let mut merge_target = None;
+        repo.fetchhead_foreach(|ref_name, remote_url, oid, is_merge| {
+            let remote_url_str = String::from_utf8_lossy(remote_url);
+            debug!("📥 Fetched {ref_name} from {remote_url_str}");
+            if is_merge && merge_target.is_none() {
+                merge_target = Some(*oid);
+            }
+            true
+        })?;
+
+        let outcome = match merge_target {
+            Some(target) => self.fast_forward_to(&repo, repo_path, target)?,
+            None => FastForwardOutcome::NoUpstream,
+        };
+        match outcome {
+            FastForwardOutcome::UpToDate => info!("✅ Already up to date"),
+            FastForwardOutcome::FastForwarded { from, to } => info!(
+                "✅ Fast-forwarded {} to {}",
+                &from.to_string()[..7],
+                &to.to_string()[..7]
+            ),
+            FastForwardOutcome::Diverged => warn!(
+                "⚠️  Local branch in {} has diverged from upstream, leaving it untouched (merge manually)",
+                repo_path.display()
+            ),
+            FastForwardOutcome::LocalChanges => warn!(
+                "⚠️  {} has uncommitted changes, fetched but did not update the working tree",
+                repo_path.display()
+            ),
+            FastForwardOutcome::NoUpstream => {
+                info!("📌 Not on a tracking branch (pinned tag/rev), fetched only")
+            }
+        }
+
+        Ok(outcome)
+    }
+
+    /// 按配置的 refspec 获取远程更新，不修改任何工作区
+    pub fn fetch(&self, repo_path: &Path) -> Result<()> {
+        let repo = Repository::open(repo_path)
+            .with_context(|| format!("Failed to open repository at {}", repo_path.display()))?;
+        let mut remote = repo
+            .find_remote("origin")
+            .context("Failed to find 'origin' remote")?;
+
+        let mut fetch_options = FetchOptions::new();
+        fetch_options.remote_callbacks(self.remote_callbacks());
+        self.apply_depth(&mut fetch_options, repo.is_shallow());
+        if let Some(proxy) = self.proxy_options(remote.url().unwrap_or_default()) {
+            fetch_options.proxy_options(proxy);
+        }
+        remote
+            .fetch(&[] as &[&str], Some(&mut fetch_options), None)
+            .with_context(|| format!("Failed to fetch into {}", repo_path.display()))?;
+        Ok(())
+    }
+
+    /// 在 `repo_path` 中添加位于 `worktree_path` 的 worktree
+    ///
+    /// worktree 检出名为 `name` 的本地分支，它跟踪远程的 `branch`（默认为远程的默认分支）。
+    /// 本地分支已存在时沿用它，保留之前的提交
+    pub fn worktree_add(
+        &self,
+        repo_path: &Path,
+        worktree_path: &Path,
+        name: &str,
+        branch: Option<&str>,
+    ) -> Result<()> {
+        let repo = Repository::open(repo_path)
+            .with_context(|| format!("Failed to open repository at {}", repo_path.display()))?;
+
+        let upstream = match branch {
+            Some(branch) => branch.to_string(),
+            None => repo
+                .find_reference("refs/remotes/origin/HEAD")
+                .ok()
+                .and_then(|head| head.symbolic_target().map(str::to_string))
+                .and_then(|target| {
+                    target
+                        .strip_prefix("refs/remotes/origin/")
+                        .map(str::to_string)
+                })
+                .or_else(|| repo.head().ok()?.shorthand().map(str::to_string))
+                .ok_or_else(|| anyhow::anyhow!("Cannot determine the default branch"))?,
+        };
+        let commit = repo
+            .revparse_single(&format!("refs/remotes/origin/{upstream}"))
+            .or_else(|_| repo.revparse_single(&format!("refs/heads/{upstream}")))
+            .and_then(|object| object.peel_to_commit())
+            .with_context(|| {
+                format!(
+                    "Could not find branch '{upstream}' in {}",
+                    repo_path.display()
+                )
+            })?;
+
+        let mut local = match repo.find_branch(name, BranchType::Local) {
+            Ok(local) => local,
+            Err(_) => repo.branch(name, &commit, false)?,
+        };
+        if repo
+            .find_reference(&format!("refs/remotes/origin/{upstream}"))
+            .is_ok()
+        {
+            local.set_upstream(Some(&format!("origin/{upstream}")))?;
+        }
+
+        // 之前的 worktree 目录已被删除时，先清理残留的记录
+        if let Ok(stale) = repo.find_worktree(name) {
+            stale.prune(Some(git2::WorktreePruneOptions::new().working_tree(false)))?;
+        }
+
+        let mut options = git2::WorktreeAddOptions::new();
+        options.reference(Some(local.get()));
+        repo.worktree(name, worktree_path, Some(&options))
+            .with_context(|| format!("Failed to add worktree at {}", worktree_path.display()))?;
+        info!(
+            "🌳 Added worktree {} on branch '{name}' (tracking origin/{upstream})",
+            worktree_path.display()
+        );
+        Ok(())
+    }
+
+    /// 解析补丁的基准提交：优先使用用户指定的版本，否则使用最近的 tag，最后回退到上游分支
+    pub fn resolve_base(&self, repo_path: &Path, base: Option<&str>) -> Result<Oid> {
+        let repo = Repository::open(repo_path)
+            .with_context(|| format!("Failed to open repository at {}", repo_path.display()))?;
+
+        if let Some(base) = base {
+            let object = repo
+                .revparse_single(base)
+                .with_context(|| format!("Failed to resolve base revision '{base}'"))?;
+            return Ok(object.peel_to_commit()?.id());
+        }
+
+        let mut describe_opts = DescribeOptions::new();
+        describe_opts.describe_tags();
+        if let Ok(describe) = repo.describe(&describe_opts) {
+            let mut format_opts = DescribeFormatOptions::new();
+            format_opts.abbreviated_size(0);
+            if let Ok(tag) = describe.format(Some(&format_opts)) {
+                debug!("🏷️  Using nearest tag '{tag}' as base");
+                let object = repo.revparse_single(&tag)?;
+                return Ok(object.peel_to_commit()?.id());
+            }
+        }
+
+        let object = repo.revparse_single("@{upstream}").map_err(|_| {
+            anyhow::anyhow!(
+                "Could not determine a base commit in {} (no tags and no upstream branch), please specify one explicitly",
+                repo_path.display()
+            )
+        })?;
+        let oid = object.peel_to_commit()?.id();
+        Ok(oid)
+    }
+
+    /// 获取从 base（不含）到 HEAD 的提交记录，按时间倒序排列
+    pub fn get_log(&self, repo_path: &Path, base: Option<Oid>) -> Result<Vec<CommitSummary>> {
+        let repo = Repository::open(repo_path)
+            .with_context(|| format!("Failed to open repository at {}", repo_path.display()))?;
+
+        let mut revwalk = repo.revwalk()?;
+        revwalk.push_head()?;
+        if let Some(base) = base {
+            revwalk.hide(base)?;
+        }
+
+        let mut commits = Vec::new();
+        for oid in revwalk {
+            let commit = repo.find_commit(oid?)?;
+            commits.push(CommitSummary {
+                id: commit.id().to_string(),
+                summary: commit.summary().unwrap_or_default().to_string(),
+                author: commit.author().name().unwrap_or_default().to_string(),
+            });
+        }
+
+        Ok(commits)
+    }
+
+    /// 计算 base 与 HEAD 之间的差异统计
+    pub fn diff_stats(&self, repo_path: &Path, base: Oid) -> Result<DiffStat> {
+        let repo = Repository::open(repo_path)
+            .with_context(|| format!("Failed to open repository at {}", repo_path.display()))?;
+
+        let base_tree = repo.find_commit(base)?.tree()?;
+        let head_tree = repo.head()?.peel_to_tree()?;
+        let diff = repo.diff_tree_to_tree(Some(&base_tree), Some(&head_tree), None)?;
+        let stats = diff.stats()?;
+
+        Ok(DiffStat {
+            files_changed: stats.files_changed(),
+            insertions: stats.insertions(),
+            deletions: stats.deletions(),
+        })
+    }
+
+    /// 返回工作区（含未提交的修改）相对 base 新增的行，键为相对仓库根目录的文件路径
+    pub fn added_lines(&self, repo_path: &Path, base: Oid) -> Result<HashMap<PathBuf, Vec<u32>>> {
+        let repo = Repository::open(repo_path)
+            .with_context(|| format!("Failed to open repository at {}", repo_path.display()))?;
+
+        let base_tree = repo.find_commit(base)?.tree()?;
+        let diff = repo.diff_tree_to_workdir_with_index(Some(&base_tree), None)?;
+        let mut added: HashMap<PathBuf, Vec<u32>> = HashMap::new();
+        diff.foreach(
+            &mut |_, _| true,
+            None,
+            None,
+            Some(&mut |delta, _hunk, line| {
+                if let (Some(path), Some(lineno)) = (delta.new_file().path(), line.new_lineno()) {
+                    if line.origin() == '+' {
+                        added.entry(path.to_path_buf()).or_default().push(lineno);
                     }
-                    true
+                }
+                true
+            }),
+        )?;
+        Ok(added)
+    }
+
+    /// 以 unified diff 格式返回工作区中尚未暂存的修改（相当于 `git diff`）
+    pub fn workdir_diff(&self, repo_path: &Path) -> Result<String> {
+        let repo = Repository::discover(repo_path)
+            .with_context(|| format!("Failed to open repository at {}", repo_path.display()))?;
+
+        let diff = repo.diff_index_to_workdir(None, None)?;
+        let mut patch = String::new();
+        diff.print(DiffFormat::Patch, |_delta, _hunk, line| {
+            // 文件头和 hunk 头自带内容，其余行需要补上 +/-/空格 前缀
+            if matches!(line.origin(), '+' | '-' | ' ') {
+                patch.push(line.origin());
+            }
+            patch.push_str(&String::from_utf8_lossy(line.content()));
+            true
+        })?;
+        Ok(patch)
+    }
+
+    /// 检查工作区是否有未提交的修改（忽略被 .gitignore 的文件）
+    pub fn is_dirty(&self, repo_path: &Path) -> Result<bool> {
+        let repo = Repository::discover(repo_path)
+            .with_context(|| format!("Failed to open repository at {}", repo_path.display()))?;
+
+        let mut status_opts = StatusOptions::new();
+        status_opts.include_untracked(true).include_ignored(false);
+        let statuses = repo.statuses(Some(&mut status_opts))?;
+        let skipped = Self::skip_worktree_paths(&repo)?;
+        Ok(statuses
+            .iter()
+            .any(|entry| !Self::is_sparse_removal(&skipped, &entry)))
+    }
+
+    /// 列出克隆根目录 `clone_root` 中有变化的文件（不含被忽略的文件）
+    ///
+    /// 不向上查找仓库：不在 git 仓库中的补丁（create-mock、extract 生成的目录）
+    /// 返回空列表，而不是宿主项目仓库的状态
+    pub fn file_statuses(&self, clone_root: &Path) -> Result<Vec<FileStatus>> {
+        let repo = match Repository::open(clone_root) {
+            Ok(repo) => repo,
+            Err(e) if e.code() == git2::ErrorCode::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(e).with_context(|| {
+                    format!("Failed to open repository at {}", clone_root.display())
+                })
+            }
+        };
+
+        let mut status_opts = StatusOptions::new();
+        status_opts
+            .include_untracked(true)
+            .recurse_untracked_dirs(true)
+            .include_ignored(false);
+        let statuses = repo.statuses(Some(&mut status_opts))?;
+        let skipped = Self::skip_worktree_paths(&repo)?;
+
+        Ok(statuses
+            .iter()
+            .filter(|entry| !Self::is_sparse_removal(&skipped, entry))
+            .map(|entry| FileStatus {
+                code: Self::short_status(entry.status()),
+                path: entry.path().unwrap_or_default().to_string(),
+            })
+            .collect())
+    }
+
+    fn short_status(status: Status) -> String {
+        if status.is_conflicted() {
+            return "UU".into();
+        }
+        if status.is_wt_new() {
+            return "??".into();
+        }
+
+        let index = if status.is_index_new() {
+            'A'
+        } else if status.is_index_modified() {
+            'M'
+        } else if status.is_index_deleted() {
+            'D'
+        } else if status.is_index_renamed() {
+            'R'
+        } else if status.is_index_typechange() {
+            'T'
+        } else {
+            ' '
+        };
+        let worktree = if status.is_wt_modified() {
+            'M'
+        } else if status.is_wt_deleted() {
+            'D'
+        } else if status.is_wt_renamed() {
+            'R'
+        } else if status.is_wt_typechange() {
+            'T'
+        } else {
+            ' '
+        };
+        format!("{index}{worktree}")
+    }
+
+    /// 在 HEAD 的文件树中查找 `[package] name` 为 `crate_name` 的 crate 所在目录
+    ///
+    /// 不依赖工作区中的文件，因此可以在稀疏检出之前使用；位于仓库根目录时返回空字符串
+    pub fn find_crate_dir(&self, repo_path: &Path, crate_name: &str) -> Result<Option<String>> {
+        let repo = Repository::open(repo_path)
+            .with_context(|| format!("Failed to open repository at {}", repo_path.display()))?;
+        let tree = repo.head()?.peel_to_tree()?;
+
+        let mut found = None;
+        let walk = tree.walk(TreeWalkMode::PreOrder, |dir, entry| {
+            if entry.name() != Some("Cargo.toml") {
+                return TreeWalkResult::Ok;
+            }
+            let Ok(blob) = entry
+                .to_object(&repo)
+                .and_then(|object| object.peel_to_blob())
+            else {
+                return TreeWalkResult::Ok;
+            };
+            let name = std::str::from_utf8(blob.content())
+                .ok()
+                .and_then(|content| content.parse::<toml::Table>().ok())
+                .and_then(|manifest| {
+                    manifest
+                        .get("package")?
+                        .get("name")?
+                        .as_str()
+                        .map(str::to_string)
                 });
+            if name.as_deref() == Some(crate_name) {
+                found = Some(dir.trim_end_matches('/').to_string());
+                return TreeWalkResult::Abort;
+            }
+            TreeWalkResult::Ok
+        });
+        // 找到后中止遍历，libgit2 会将其报告为错误
+        match walk {
+            Err(_) if found.is_some() => Ok(found),
+            walk => walk.map(|()| found).map_err(Into::into),
+        }
+    }
+
+    /// 强制检出 HEAD 的全部文件（用于以 [`Self::with_no_checkout`] 克隆的仓库）
+    pub fn checkout_head(&self, repo_path: &Path) -> Result<()> {
+        let repo = Repository::open(repo_path)
+            .with_context(|| format!("Failed to open repository at {}", repo_path.display()))?;
+        repo.checkout_head(Some(CheckoutBuilder::new().force()))?;
+        Ok(())
+    }
+
+    /// 启用稀疏检出，只保留仓库根目录下的文件和 `subdir`
+    ///
+    /// 规则写入 `.git/info/sparse-checkout`（与 `git sparse-checkout` 的 cone 模式兼容），
+    /// 其余文件在索引中标记为 skip-worktree 并从工作区删除。同时把 fetch refspec
+    /// 收窄到当前分支，之后的 fetch 不再下载其他分支的对象
+    pub fn sparse_checkout(&self, repo_path: &Path, subdir: &str) -> Result<()> {
+        let repo = Repository::open(repo_path)
+            .with_context(|| format!("Failed to open repository at {}", repo_path.display()))?;
+
+        let mut config = repo.config()?;
+        config.set_bool("core.sparseCheckout", true)?;
+        config.set_bool("core.sparseCheckoutCone", true)?;
+        fs::write(
+            repo.path().join("info").join("sparse-checkout"),
+            format!("/*\n!/*/\n/{subdir}/\n"),
+        )
+        .context("Failed to write sparse-checkout patterns")?;
+
+        // 不检出文件的克隆没有写入索引
+        let head = repo.head()?;
+        let mut index = repo.index()?;
+        index.read_tree(&head.peel_to_tree()?)?;
+        index.write()?;
+
+        if let Some(branch) = head.shorthand().filter(|_| head.is_branch()) {
+            config.set_str(
+                "remote.origin.fetch",
+                &format!("+refs/heads/{branch}:refs/remotes/origin/{branch}"),
+            )?;
+        }
+
+        Self::reapply_sparse_checkout(&repo)?;
+        info!(
+            "🌱 Sparse checkout of '{subdir}' in {}",
+            repo_path.display()
+        );
+        Ok(())
+    }
+
+    /// 读取 `.git/info/sparse-checkout` 中检出的子目录，未启用稀疏检出时返回 `None`
+    fn sparse_subdir(repo: &Repository) -> Result<Option<String>> {
+        if !repo
+            .config()?
+            .get_bool("core.sparseCheckout")
+            .unwrap_or(false)
+        {
+            return Ok(None);
+        }
+        let patterns = fs::read_to_string(repo.path().join("info").join("sparse-checkout"))
+            .unwrap_or_default();
+        Ok(patterns
+            .lines()
+            .filter(|line| !matches!(*line, "/*" | "!/*/"))
+            .find_map(|line| line.strip_prefix('/')?.strip_suffix('/'))
+            .map(str::to_string))
+    }
+
+    /// 按稀疏检出规则检出文件并更新 skip-worktree 标记
+    ///
+    /// libgit2 本身不支持稀疏检出，每次 checkout 之后都需要重新执行
+    fn reapply_sparse_checkout(repo: &Repository) -> Result<()> {
+        let Some(subdir) = Self::sparse_subdir(repo)? else {
+            return Ok(());
+        };
+        let workdir = repo
+            .workdir()
+            .ok_or_else(|| anyhow::anyhow!("Sparse checkout requires a working directory"))?
+            .to_path_buf();
+        let prefix = format!("{subdir}/");
+        let included = |path: &str| !path.contains('/') || path.starts_with(&prefix);
 
-                match fetch_head {
-                    Ok(_) => info!("✅ Pull completed successfully"),
-                    Err(_) => {
-                        info!("⚠️  Fetch completed, but you may need to manually merge changes")
+        let mut checkout = CheckoutBuilder::new();
+        checkout.force().path(&subdir);
+        let mut index = repo.index()?;
+        for entry in index.iter() {
+            let path = String::from_utf8_lossy(&entry.path).into_owned();
+            if !path.contains('/') {
+                checkout.path(path);
+            }
+        }
+        repo.checkout_head(Some(&mut checkout))?;
+
+        // checkout 会重建索引，需要重新读取
+        index.read(true)?;
+        let entries: Vec<_> = index.iter().collect();
+        for mut entry in entries {
+            let path = String::from_utf8_lossy(&entry.path).into_owned();
+            if included(&path) {
+                continue;
+            }
+            entry.flags |= IndexEntryFlag::EXTENDED.bits();
+            entry.flags_extended |= IndexEntryExtendedFlag::SKIP_WORKTREE.bits();
+            index.add(&entry)?;
+
+            let file = workdir.join(&path);
+            if file.exists() {
+                fs::remove_file(&file)?;
+                // 删除因此变空的上级目录
+                for dir in file.ancestors().skip(1) {
+                    if dir == workdir || fs::remove_dir(dir).is_err() {
+                        break;
                     }
                 }
             }
-            Err(e) => {
-                pull_pb.abandon_with_message("❌ Fetch failed");
-                return Err(anyhow::anyhow!("Failed to fetch from remote: {}", e));
+        }
+        index.write()?;
+        Ok(())
+    }
+
+    /// 稀疏检出时被标记为 skip-worktree 的文件
+    fn skip_worktree_paths(repo: &Repository) -> Result<HashSet<Vec<u8>>> {
+        if Self::sparse_subdir(repo)?.is_none() {
+            return Ok(HashSet::new());
+        }
+        Ok(repo
+            .index()?
+            .iter()
+            .filter(|entry| {
+                IndexEntryExtendedFlag::from_bits_truncate(entry.flags_extended)
+                    .contains(IndexEntryExtendedFlag::SKIP_WORKTREE)
+            })
+            .map(|entry| entry.path)
+            .collect())
+    }
+
+    /// libgit2 会把 skip-worktree 的文件报告为已删除，这不是用户的修改
+    fn is_sparse_removal(skipped: &HashSet<Vec<u8>>, entry: &git2::StatusEntry<'_>) -> bool {
+        entry.status() == Status::WT_DELETED && skipped.contains(entry.path_bytes())
+    }
+
+    /// 计算当前分支相对于其上游分支领先和落后的提交数，没有上游分支时返回 `None`
+    ///
+    /// 只使用本地已有的远程引用，不会访问网络
+    pub fn ahead_behind_upstream(&self, repo_path: &Path) -> Result<Option<(usize, usize)>> {
+        let repo = Repository::discover(repo_path)
+            .with_context(|| format!("Failed to open repository at {}", repo_path.display()))?;
+
+        let head = repo.head()?;
+        let Some(local) = head.target() else {
+            return Ok(None);
+        };
+        let Some(upstream) = Self::upstream_oid(&repo)? else {
+            return Ok(None);
+        };
+
+        Ok(Some(repo.graph_ahead_behind(local, upstream)?))
+    }
+
+    /// 将当前分支快进到已获取的上游提交，不访问网络
+    pub fn fast_forward_upstream(&self, repo_path: &Path) -> Result<FastForwardOutcome> {
+        let repo = Repository::discover(repo_path)
+            .with_context(|| format!("Failed to open repository at {}", repo_path.display()))?;
+        match Self::upstream_oid(&repo)? {
+            Some(upstream) => self.fast_forward_to(&repo, repo_path, upstream),
+            None => Ok(FastForwardOutcome::NoUpstream),
+        }
+    }
+
+    /// 在工作区干净时将当前分支快进到 `target`，无法快进时不做任何修改
+    fn fast_forward_to(
+        &self,
+        repo: &Repository,
+        repo_path: &Path,
+        target: Oid,
+    ) -> Result<FastForwardOutcome> {
+        let head = repo.head()?;
+        if !head.is_branch() {
+            return Ok(FastForwardOutcome::NoUpstream);
+        }
+        let Some(local) = head.target() else {
+            return Ok(FastForwardOutcome::NoUpstream);
+        };
+
+        let annotated = repo.find_annotated_commit(target)?;
+        let (analysis, _) = repo.merge_analysis(&[&annotated])?;
+        if analysis.is_up_to_date() {
+            return Ok(FastForwardOutcome::UpToDate);
+        }
+        if !analysis.is_fast_forward() {
+            return Ok(FastForwardOutcome::Diverged);
+        }
+
+        // 只在干净的工作区上快进，避免覆盖用户的修改
+        if self.is_dirty(repo_path)? {
+            return Ok(FastForwardOutcome::LocalChanges);
+        }
+
+        let mut reference = head;
+        reference.set_target(target, "cargo-lpatch: fast-forward")?;
+        repo.checkout_head(Some(CheckoutBuilder::new().force()))?;
+        Self::reapply_sparse_checkout(repo)?;
+
+        Ok(FastForwardOutcome::FastForwarded {
+            from: local,
+            to: target,
+        })
+    }
+
+    /// 将指定提交的文件树导出到 `target_dir`，不影响仓库本身的工作区
+    pub fn export_tree(&self, repo_path: &Path, commit: Oid, target_dir: &Path) -> Result<()> {
+        let repo = Repository::discover(repo_path)
+            .with_context(|| format!("Failed to open repository at {}", repo_path.display()))?;
+        let tree = repo.find_commit(commit)?.tree()?;
+
+        let mut checkout = CheckoutBuilder::new();
+        checkout
+            .target_dir(target_dir)
+            .force()
+            .recreate_missing(true);
+        repo.checkout_tree(tree.as_object(), Some(&mut checkout))
+            .with_context(|| format!("Failed to export {} to {}", commit, target_dir.display()))?;
+        Ok(())
+    }
+
+    /// 获取 HEAD 指向的提交
+    pub fn head_commit(&self, repo_path: &Path) -> Result<Oid> {
+        let repo = Repository::discover(repo_path)
+            .with_context(|| format!("Failed to open repository at {}", repo_path.display()))?;
+        let oid = repo.head()?.peel_to_commit()?.id();
+        Ok(oid)
+    }
+
+    /// 获取补丁路径所在仓库的工作区根目录（补丁可能指向 workspace 中的子目录）
+    pub fn repo_root(&self, path: &Path) -> Result<PathBuf> {
+        let repo = Repository::discover(path)
+            .with_context(|| format!("Failed to open repository at {}", path.display()))?;
+        repo.workdir()
+            .map(Path::to_path_buf)
+            .ok_or_else(|| anyhow::anyhow!("{} is a bare repository", path.display()))
+    }
+
+    /// 获取当前分支上游分支（本地记录的远程引用）指向的提交
+    pub fn upstream_commit(&self, repo_path: &Path) -> Result<Option<Oid>> {
+        let repo = Repository::discover(repo_path)
+            .with_context(|| format!("Failed to open repository at {}", repo_path.display()))?;
+        Self::upstream_oid(&repo)
+    }
+
+    fn upstream_oid(repo: &Repository) -> Result<Option<Oid>> {
+        match repo.revparse_single("@{upstream}") {
+            Ok(object) => Ok(Some(object.peel_to_commit()?.id())),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// 获取 `to` 中有而 `from` 中没有的提交，按时间倒序排列
+    pub fn commits_between(
+        &self,
+        repo_path: &Path,
+        from: Oid,
+        to: Oid,
+    ) -> Result<Vec<CommitSummary>> {
+        let repo = Repository::discover(repo_path)
+            .with_context(|| format!("Failed to open repository at {}", repo_path.display()))?;
+
+        let mut revwalk = repo.revwalk()?;
+        revwalk.push(to)?;
+        revwalk.hide(from)?;
+
+        let mut commits = Vec::new();
+        for oid in revwalk {
+            let commit = repo.find_commit(oid?)?;
+            commits.push(CommitSummary {
+                id: commit.id().to_string(),
+                summary: commit.summary().unwrap_or_default().to_string(),
+                author: commit.author().name().unwrap_or_default().to_string(),
+            });
+        }
+        Ok(commits)
+    }
+
+    /// 列出本地仓库中的所有 tag
+    pub fn list_tags(&self, repo_path: &Path) -> Result<Vec<String>> {
+        let repo = Repository::open(repo_path)
+            .with_context(|| format!("Failed to open repository at {}", repo_path.display()))?;
+
+        let tags = repo.tag_names(None)?;
+        Ok(tags.iter().flatten().map(str::to_string).collect())
+    }
+
+    /// 列出 origin 上的所有 tag（类似 `git ls-remote --tags`，不会修改本地仓库）
+    pub fn list_remote_tags(&self, repo_path: &Path) -> Result<Vec<String>> {
+        let repo = Repository::open(repo_path)
+            .with_context(|| format!("Failed to open repository at {}", repo_path.display()))?;
+        let mut remote = repo
+            .find_remote("origin")
+            .context("Failed to find 'origin' remote")?;
+
+        let proxy = self.proxy_options(remote.url().unwrap_or_default());
+        let connection = remote
+            .connect_auth(Direction::Fetch, Some(self.remote_callbacks()), proxy)
+            .context("Failed to connect to 'origin'")?;
+
+        let mut tags: Vec<String> = connection
+            .list()?
+            .iter()
+            .filter_map(|head| head.name().strip_prefix("refs/tags/"))
+            // 附注 tag 会额外列出一个 `^{}` 结尾的解引用项
+            .filter(|name| !name.ends_with("^{}"))
+            .map(str::to_string)
+            .collect();
+        tags.sort();
+        tags.dedup();
+        Ok(tags)
+    }
+
+    /// 检出指定的分支、tag 或提交（强制覆盖工作区）
+    ///
+    /// 分支会创建跟踪 `origin/<branch>` 的本地分支；tag 和提交以分离 HEAD 的方式检出，
+    /// 提交支持缩写的 SHA
+    pub fn checkout_ref(&self, repo_path: &Path, reference: &GitReference) -> Result<()> {
+        let repo = Repository::open(repo_path)
+            .with_context(|| format!("Failed to open repository at {}", repo_path.display()))?;
+
+        let spec = match reference {
+            GitReference::Branch(branch) => format!("refs/remotes/origin/{branch}"),
+            GitReference::Tag(tag) => format!("refs/tags/{tag}"),
+            GitReference::Rev(rev) => rev.clone(),
+        };
+        let commit = repo
+            .revparse_single(&spec)
+            .and_then(|object| object.peel_to_commit())
+            .map_err(|e| {
+                anyhow::anyhow!(
+                    "Could not find {} in {}: {}",
+                    reference,
+                    repo_path.display(),
+                    e.message()
+                )
+            })?;
+
+        let mut checkout = CheckoutBuilder::new();
+        checkout.force();
+        repo.checkout_tree(commit.as_object(), Some(&mut checkout))?;
+
+        match reference {
+            GitReference::Branch(branch) => {
+                let mut local = match repo.find_branch(branch, BranchType::Local) {
+                    Ok(mut local) => {
+                        local
+                            .get_mut()
+                            .set_target(commit.id(), "cargo-lpatch: checkout")?;
+                        local
+                    }
+                    Err(_) => repo.branch(branch, &commit, true)?,
+                };
+                local.set_upstream(Some(&format!("origin/{branch}")))?;
+                repo.set_head(&format!("refs/heads/{branch}"))?;
+            }
+            GitReference::Tag(_) | GitReference::Rev(_) => {
+                repo.set_head_detached(commit.id())?;
             }
         }
 
+        Self::reapply_sparse_checkout(&repo)?;
+
+        info!(
+            "📌 Checked out {} ({})",
+            reference,
+            &commit.id().to_string()[..7]
+        );
         Ok(())
     }
 
+    /// 查找与 crate 版本对应的 tag（常见命名：`v1.0.0`、`1.0.0`、`name-v1.0.0`、`name-1.0.0`）
+    pub fn find_version_tag(
+        &self,
+        repo_path: &Path,
+        crate_name: &str,
+        version: &str,
+    ) -> Result<Option<String>> {
+        let tags = self.list_tags(repo_path)?;
+        let candidates = [
+            format!("v{version}"),
+            version.to_string(),
+            format!("{crate_name}-v{version}"),
+            format!("{crate_name}-{version}"),
+            format!("{crate_name}@{version}"),
+        ];
+        Ok(candidates
+            .into_iter()
+            .find(|candidate| tags.contains(candidate)))
+    }
+
+    /// 读取 origin 远程的 URL
+    pub fn origin_url(&self, repo_path: &Path) -> Result<String> {
+        let repo = Repository::open(repo_path)
+            .with_context(|| format!("Failed to open repository at {}", repo_path.display()))?;
+        let remote = repo
+            .find_remote("origin")
+            .context("Failed to find 'origin' remote")?;
+        remote
+            .url()
+            .map(str::to_string)
+            .ok_or_else(|| anyhow::anyhow!("'origin' remote has no URL"))
+    }
+
     #[allow(dead_code)]
     pub fn get_current_branch(&self, repo_path: &Path) -> Result<String> {
         let repo = Repository::open(repo_path)?;
@@ -434,3 +1397,237 @@ impl GitOperations {
         Repository::open(path).is_ok()
     }
 }
+
+/// 按 git 的顺序选择代理：Git 配置中的 `http.proxy` 优先，其次按协议读取
+/// `https_proxy`/`HTTPS_PROXY` 或 `http_proxy`/`HTTP_PROXY`，最后是 `all_proxy`/`ALL_PROXY`。
+/// 主机在 `no_proxy` 中时不使用环境变量中的代理
+fn select_proxy(
+    url: &str,
+    config_proxy: Option<&str>,
+    env_var: impl Fn(&str) -> Option<String>,
+) -> Option<String> {
+    let (scheme, _) = url.split_once("://")?;
+    if !matches!(scheme, "http" | "https") {
+        return None;
+    }
+    if let Some(proxy) = config_proxy {
+        return Some(proxy.to_string());
+    }
+
+    let parsed = url::Url::parse(url).ok()?;
+    let host = parsed.host_str()?;
+    let no_proxy = env_var("no_proxy").or_else(|| env_var("NO_PROXY"));
+    if no_proxy.is_some_and(|list| no_proxy_matches(&list, host)) {
+        return None;
+    }
+    let vars: &[&str] = if scheme == "https" {
+        &["https_proxy", "HTTPS_PROXY", "all_proxy", "ALL_PROXY"]
+    } else {
+        &["http_proxy", "HTTP_PROXY", "all_proxy", "ALL_PROXY"]
+    };
+    vars.iter()
+        .find_map(|var| env_var(var).filter(|value| !value.is_empty()))
+}
+
+/// 主机是否匹配 `no_proxy` 列表中的某一项（`*` 匹配所有主机，`.example.com` 与 `example.com` 等价）
+fn no_proxy_matches(list: &str, host: &str) -> bool {
+    list.split(',').map(str::trim).any(|entry| {
+        let entry = entry.trim_start_matches('.');
+        entry == "*"
+            || (!entry.is_empty() && (host == entry || host.ends_with(&format!(".{entry}"))))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    fn commit_count(repo_path: &Path) -> usize {
+        let repo = Repository::open(repo_path).unwrap();
+        let mut walk = repo.revwalk().unwrap();
+        walk.push_head().unwrap();
+        walk.count()
+    }
+
+    fn env<'a>(vars: &'a [(&'a str, &'a str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        move |name| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| value.to_string())
+        }
+    }
+
+    #[test]
+    fn no_proxy_matches_suffixes_and_wildcard() {
+        assert!(no_proxy_matches("example.com", "example.com"));
+        assert!(no_proxy_matches("example.com", "git.example.com"));
+        assert!(no_proxy_matches(
+            " foo.org , .example.com",
+            "git.example.com"
+        ));
+        assert!(!no_proxy_matches("example.com", "badexample.com"));
+        assert!(!no_proxy_matches("git.example.com", "example.com"));
+        assert!(!no_proxy_matches("", "example.com"));
+        assert!(no_proxy_matches("*", "anything.org"));
+    }
+
+    #[test]
+    fn proxy_selection_follows_git_precedence() {
+        let vars = [
+            ("https_proxy", "http://lower-https:1"),
+            ("HTTPS_PROXY", "http://upper-https:1"),
+            ("http_proxy", "http://lower-http:1"),
+            ("all_proxy", "http://all:1"),
+        ];
+        let url = "https://github.com/org/repo.git";
+
+        // http.proxy 优先于环境变量
+        assert_eq!(
+            select_proxy(url, Some("http://config:1"), env(&vars)).as_deref(),
+            Some("http://config:1")
+        );
+        assert_eq!(
+            select_proxy(url, None, env(&vars)).as_deref(),
+            Some("http://lower-https:1")
+        );
+        assert_eq!(
+            select_proxy(url, None, env(&vars[1..])).as_deref(),
+            Some("http://upper-https:1")
+        );
+        assert_eq!(
+            select_proxy("http://example.com/repo.git", None, env(&vars)).as_deref(),
+            Some("http://lower-http:1")
+        );
+        assert_eq!(
+            select_proxy(url, None, env(&vars[2..])).as_deref(),
+            Some("http://all:1")
+        );
+        // 空值视为未设置
+        assert_eq!(
+            select_proxy(
+                url,
+                None,
+                env(&[("https_proxy", ""), ("all_proxy", "http://all:1")])
+            )
+            .as_deref(),
+            Some("http://all:1")
+        );
+        // 只有 http(s) 地址使用代理
+        assert_eq!(
+            select_proxy(
+                "ssh://git@github.com/org/repo.git",
+                Some("http://config:1"),
+                env(&vars)
+            ),
+            None
+        );
+        assert_eq!(
+            select_proxy("git@github.com:org/repo.git", None, env(&vars)),
+            None
+        );
+    }
+
+    #[test]
+    fn no_proxy_bypasses_only_the_environment_proxy() {
+        let vars = [
+            ("https_proxy", "http://proxy:1"),
+            ("NO_PROXY", "github.com"),
+        ];
+        let url = "https://github.com/org/repo.git";
+        assert_eq!(select_proxy(url, None, env(&vars)), None);
+        assert_eq!(
+            select_proxy("https://gitlab.com/org/repo.git", None, env(&vars)).as_deref(),
+            Some("http://proxy:1")
+        );
+        assert_eq!(
+            select_proxy(url, Some("http://config:1"), env(&vars)).as_deref(),
+            Some("http://config:1")
+        );
+        // 小写的 no_proxy 优先
+        let vars = [
+            ("https_proxy", "http://proxy:1"),
+            ("no_proxy", "gitlab.com"),
+            ("NO_PROXY", "github.com"),
+        ];
+        assert_eq!(
+            select_proxy(url, None, env(&vars)).as_deref(),
+            Some("http://proxy:1")
+        );
+    }
+
+    #[test]
+    fn shallow_clone_limits_history() {
+        let tmp = tempfile::tempdir().unwrap();
+        test_support::crate_repo(&tmp.path().join("upstream/samp"), "samp", 5);
+        let daemon = test_support::GitDaemon::start(&tmp.path().join("upstream"));
+        let url = daemon.url("samp");
+
+        let shallow = tmp.path().join("shallow");
+        GitOperations::new()
+            .with_depth(Some(2))
+            .clone(&url, &shallow, None)
+            .unwrap();
+        let git_ops = GitOperations::new();
+        assert!(git_ops.is_shallow(&shallow));
+        assert_eq!(commit_count(&shallow), 2);
+
+        let full = tmp.path().join("full");
+        git_ops.clone(&url, &full, None).unwrap();
+        assert!(!git_ops.is_shallow(&full));
+        assert_eq!(commit_count(&full), 5);
+    }
+
+    #[test]
+    fn file_statuses_do_not_report_the_enclosing_repository() {
+        let tmp = tempfile::tempdir().unwrap();
+        let host = tmp.path().join("host");
+        test_support::crate_repo(&host, "host", 1);
+        fs::write(host.join("src/lib.rs"), "// dirty\n").unwrap();
+        let mock = host.join("crates/mock");
+        test_support::project(&mock, "[package]\nname = \"mock\"\n");
+
+        let git_ops = GitOperations::new();
+        assert!(git_ops.file_statuses(&mock).unwrap().is_empty());
+        let host_files: Vec<_> = git_ops
+            .file_statuses(&host)
+            .unwrap()
+            .into_iter()
+            .map(|file| file.path)
+            .collect();
+        assert!(host_files.contains(&"src/lib.rs".to_string()));
+    }
+
+    /// libgit2 只通过 CONNECT 隧道为 https 地址使用代理，因此这里克隆 https 地址
+    #[tokio::test(flavor = "multi_thread")]
+    async fn clone_requests_go_through_the_configured_proxy() {
+        use wiremock::{matchers::any, Mock, MockServer, ResponseTemplate};
+
+        let proxy = MockServer::start().await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(403))
+            .mount(&proxy)
+            .await;
+
+        let mut git_ops = GitOperations::new();
+        git_ops.http_proxy = Some(proxy.uri());
+        git_ops.retries = 0;
+        let tmp = tempfile::tempdir().unwrap();
+        let target = tmp.path().join("repo");
+        let result = tokio::task::spawn_blocking(move || {
+            git_ops.clone("https://git.example.invalid/org/repo.git", &target, None)
+        })
+        .await
+        .unwrap();
+        let error = format!("{:#}", result.unwrap_err());
+        assert!(
+            error.contains("proxy returned unexpected status: 403"),
+            "{error}"
+        );
+
+        let requests = proxy.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method.as_str(), "CONNECT");
+        assert!(requests[0].url.as_str().contains("git.example.invalid:443"));
+    }
+}
@@ -0,0 +1,122 @@
+use anyhow::{anyhow, Result};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+const API_BASE: &str = "https://api.github.com";
+
+#[derive(Debug, Deserialize)]
+struct SearchResponse<T> {
+    total_count: u64,
+    items: Vec<T>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Repository {
+    full_name: String,
+    html_url: String,
+    description: Option<String>,
+    stargazers_count: u64,
+    pushed_at: Option<String>,
+}
+
+/// 搜索到的可能已包含修复的仓库
+#[derive(Debug, Serialize)]
+pub struct RepositoryHit {
+    pub full_name: String,
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub stars: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pushed_at: Option<String>,
+    pub open_prs: u64,
+}
+
+pub struct GithubClient {
+    client: Client,
+    token: String,
+}
+
+impl GithubClient {
+    /// 使用 `GIT_TOKEN`（或 `GITHUB_TOKEN`）认证，搜索 API 需要 token
+    pub fn from_env() -> Result<Self> {
+        let token = std::env::var("GIT_TOKEN")
+            .or_else(|_| std::env::var("GITHUB_TOKEN"))
+            .map_err(|_| anyhow!("GIT_TOKEN must be set to query the GitHub API"))?;
+        Ok(Self {
+            client: Client::new(),
+            token,
+        })
+    }
+
+    async fn search<T: for<'de> Deserialize<'de>>(
+        &self,
+        kind: &str,
+        query: &str,
+        per_page: usize,
+    ) -> Result<SearchResponse<T>> {
+        let url = format!("{API_BASE}/search/{kind}");
+        let per_page = per_page.to_string();
+        let response = self
+            .client
+            .get(&url)
+            .query(&[("q", query), ("per_page", per_page.as_str())])
+            .header("User-Agent", "cargo-lpatch/0.1.0")
+            .header("Accept", "application/vnd.github+json")
+            .bearer_auth(&self.token)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(anyhow!(
+                "GitHub search failed: HTTP {} for query '{}'",
+                response.status(),
+                query
+            ));
+        }
+        Ok(response.json().await?)
+    }
+
+    /// 按 crate 名称和关键字搜索 Rust 仓库，返回前 `limit` 个结果及其打开的 PR 数
+    pub async fn search_patches(
+        &self,
+        crate_name: &str,
+        keyword: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<RepositoryHit>> {
+        let query = match keyword {
+            Some(keyword) => format!("{crate_name} {keyword} language:rust"),
+            None => format!("{crate_name} language:rust"),
+        };
+        info!("🔍 Searching GitHub for: {query}");
+        let repositories: SearchResponse<Repository> =
+            self.search("repositories", &query, limit).await?;
+        debug!(
+            "GitHub returned {} matching repositories",
+            repositories.total_count
+        );
+
+        let mut hits = Vec::new();
+        for repo in repositories.items.into_iter().take(limit) {
+            let pr_query = format!("repo:{} type:pr state:open", repo.full_name);
+            let open_prs = match self
+                .search::<serde_json::Value>("issues", &pr_query, 1)
+                .await
+            {
+                Ok(prs) => prs.total_count,
+                Err(e) => {
+                    warn!("⚠️  Failed to count open PRs of {}: {e}", repo.full_name);
+                    0
+                }
+            };
+            hits.push(RepositoryHit {
+                full_name: repo.full_name,
+                url: repo.html_url,
+                description: repo.description,
+                stars: repo.stargazers_count,
+                pushed_at: repo.pushed_at,
+                open_prs,
+            });
+        }
+        Ok(hits)
+    }
+}
@@ -0,0 +1,130 @@
+use anyhow::{anyhow, Context, Result};
+use std::collections::hash_map::DefaultHasher;
+use std::env;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use toml_edit::{value, DocumentMut};
+
+use crate::git::GitOperations;
+
+/// 全局克隆缓存目录的配置项，同名环境变量优先于用户配置
+pub const CACHE_DIR_KEY: &str = "CARGO_LPATCH_DIR";
+
+/// 用户级配置文件：`$XDG_CONFIG_HOME/cargo-lpatch/config.toml`，默认在 `~/.config` 下
+pub fn user_config_path() -> Result<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|home| home.join(".config")))
+        .ok_or_else(|| anyhow!("Cannot determine the user config directory (HOME is not set)"))?;
+    Ok(config_home.join("cargo-lpatch").join("config.toml"))
+}
+
+/// 用户级缓存目录：`$XDG_CACHE_HOME/cargo-lpatch`，默认在 `~/.cache` 下
+pub fn cache_home() -> Result<PathBuf> {
+    let cache_home = env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|home| home.join(".cache")))
+        .ok_or_else(|| anyhow!("Cannot determine the user cache directory (HOME is not set)"))?;
+    Ok(cache_home.join("cargo-lpatch"))
+}
+
+/// 默认的克隆缓存目录：`$XDG_CACHE_HOME/cargo-lpatch/repos`
+pub fn default_cache_dir() -> Result<PathBuf> {
+    Ok(cache_home()?.join("repos"))
+}
+
+fn load_user_config(path: &Path) -> Result<DocumentMut> {
+    if !path.exists() {
+        return Ok(DocumentMut::new());
+    }
+    fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?
+        .parse()
+        .with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+/// 在用户配置中启用全局缓存，返回缓存目录
+pub fn enable() -> Result<PathBuf> {
+    let config_path = user_config_path()?;
+    let cache_dir = default_cache_dir()?;
+
+    let mut doc = load_user_config(&config_path)?;
+    doc[CACHE_DIR_KEY] = value(cache_dir.to_string_lossy().as_ref());
+    if let Some(parent) = config_path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    fs::write(&config_path, doc.to_string())
+        .with_context(|| format!("Failed to write {}", config_path.display()))?;
+    Ok(cache_dir)
+}
+
+/// 从用户配置中移除全局缓存设置，返回之前是否启用
+///
+/// 缓存中的仓库和已有的 worktree 不会被删除
+pub fn disable() -> Result<bool> {
+    let config_path = user_config_path()?;
+    let mut doc = load_user_config(&config_path)?;
+    if doc.remove(CACHE_DIR_KEY).is_none() {
+        return Ok(false);
+    }
+    fs::write(&config_path, doc.to_string())
+        .with_context(|| format!("Failed to write {}", config_path.display()))?;
+    Ok(true)
+}
+
+/// 当前生效的缓存目录，未启用时返回 `None`
+pub fn cache_dir() -> Result<Option<PathBuf>> {
+    if let Some(dir) = env::var_os(CACHE_DIR_KEY).filter(|dir| !dir.is_empty()) {
+        return Ok(Some(PathBuf::from(dir)));
+    }
+    let doc = load_user_config(&user_config_path()?)?;
+    Ok(doc
+        .get(CACHE_DIR_KEY)
+        .and_then(|item| item.as_str())
+        .map(PathBuf::from))
+}
+
+/// 仓库在缓存中的目录，同一仓库的不同 URL 写法（大小写、`.git` 后缀）共用一个目录
+pub fn repo_dir(cache_dir: &Path, url: &str) -> PathBuf {
+    let normalized = crate::dedup::normalize_url(url);
+    let without_scheme = normalized
+        .split_once("://")
+        .map_or(normalized.as_str(), |(_, rest)| rest);
+    let slug: String = without_scheme
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    cache_dir.join(format!("{}.git", slug.trim_matches('-')))
+}
+
+/// 在缓存的仓库中为 `clone_path` 创建 worktree，缓存中还没有该仓库时先克隆
+pub fn add_worktree(
+    git_ops: &GitOperations,
+    cache_dir: &Path,
+    url: &str,
+    clone_path: &Path,
+    crate_name: &str,
+    branch: Option<&str>,
+) -> Result<()> {
+    let repo_path = repo_dir(cache_dir, url);
+    if repo_path.exists() {
+        info!("📦 Using cached repository {}", repo_path.display());
+        git_ops.fetch(&repo_path)?;
+    } else {
+        fs::create_dir_all(cache_dir)
+            .with_context(|| format!("Failed to create {}", cache_dir.display()))?;
+        git_ops.clone_bare(url, &repo_path)?;
+    }
+
+    // worktree 名称按目标路径区分，同一项目重复执行时得到相同的名称
+    let absolute = env::current_dir()?.join(clone_path);
+    let mut hasher = DefaultHasher::new();
+    absolute.hash(&mut hasher);
+    let name = format!("{crate_name}-{:08x}", hasher.finish() as u32);
+
+    git_ops.worktree_add(&repo_path, clone_path, &name, branch)
+}
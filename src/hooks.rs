@@ -0,0 +1,79 @@
+use anyhow::{anyhow, Context, Result};
+use git2::Repository;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 写入钩子脚本时使用的标记，用于识别可以安全覆盖的钩子
+const HOOK_MARKER: &str = "# installed by cargo-lpatch";
+
+/// 可安装的 git 钩子
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookKind {
+    /// 提交前运行 `cargo fmt --check`
+    PreCommit,
+    /// 推送前运行 `cargo test`
+    PrePush,
+}
+
+impl HookKind {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "pre-commit" => Ok(HookKind::PreCommit),
+            "pre-push" => Ok(HookKind::PrePush),
+            _ => Err(anyhow!(
+                "Unknown hook '{s}', expected 'pre-commit' or 'pre-push'"
+            )),
+        }
+    }
+
+    fn file_name(self) -> &'static str {
+        match self {
+            HookKind::PreCommit => "pre-commit",
+            HookKind::PrePush => "pre-push",
+        }
+    }
+
+    fn command(self) -> &'static str {
+        match self {
+            HookKind::PreCommit => "cargo fmt --all -- --check",
+            HookKind::PrePush => "cargo test",
+        }
+    }
+}
+
+/// 在 `repo_path` 所在仓库中安装钩子，返回钩子文件路径
+///
+/// 已存在且不是由本工具写入的钩子不会被覆盖
+pub fn install_hook(repo_path: &Path, kind: HookKind) -> Result<PathBuf> {
+    let repo = Repository::discover(repo_path)
+        .with_context(|| format!("Failed to open repository at {}", repo_path.display()))?;
+    let hooks_dir = repo.path().join("hooks");
+    fs::create_dir_all(&hooks_dir)
+        .with_context(|| format!("Failed to create directory '{}'", hooks_dir.display()))?;
+
+    let hook_path = hooks_dir.join(kind.file_name());
+    if let Ok(existing) = fs::read_to_string(&hook_path) {
+        if !existing.contains(HOOK_MARKER) {
+            return Err(anyhow!(
+                "{} already exists and was not installed by cargo-lpatch, refusing to overwrite",
+                hook_path.display()
+            ));
+        }
+    }
+
+    let script = format!(
+        "#!/bin/sh\n{HOOK_MARKER}\ncd \"$(git rev-parse --show-toplevel)\" || exit 1\nexec {}\n",
+        kind.command()
+    );
+    fs::write(&hook_path, script)
+        .with_context(|| format!("Failed to write {}", hook_path.display()))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&hook_path, fs::Permissions::from_mode(0o755))
+            .with_context(|| format!("Failed to make {} executable", hook_path.display()))?;
+    }
+
+    Ok(hook_path)
+}
@@ -0,0 +1,69 @@
+use anyhow::{anyhow, Result};
+use url::Url;
+
+/// 克隆主机的允许/拒绝规则
+#[derive(Debug, Default, Clone)]
+pub struct HostPolicy {
+    allow: Vec<String>,
+    deny: Vec<String>,
+}
+
+impl HostPolicy {
+    pub fn new(allow: Vec<String>, deny: Vec<String>) -> Self {
+        Self {
+            allow: allow.into_iter().map(|h| h.to_lowercase()).collect(),
+            deny: deny.into_iter().map(|h| h.to_lowercase()).collect(),
+        }
+    }
+
+    /// 检查是否允许从指定 URL 克隆，拒绝规则优先于允许规则
+    pub fn check(&self, url: &str) -> Result<()> {
+        if self.allow.is_empty() && self.deny.is_empty() {
+            return Ok(());
+        }
+
+        let host = Self::extract_host(url)
+            .ok_or_else(|| anyhow!("Could not determine host of '{}' to apply host rules", url))?;
+
+        if let Some(rule) = self.deny.iter().find(|rule| Self::matches(&host, rule)) {
+            return Err(anyhow!(
+                "Host '{}' is denied by rule '{}', refusing to clone {}",
+                host,
+                rule,
+                url
+            ));
+        }
+
+        if !self.allow.is_empty() && !self.allow.iter().any(|rule| Self::matches(&host, rule)) {
+            return Err(anyhow!(
+                "Host '{}' is not in the allowed host list ({}), refusing to clone {}",
+                host,
+                self.allow.join(", "),
+                url
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// 规则匹配主机本身及其子域名
+    fn matches(host: &str, rule: &str) -> bool {
+        host == rule || host.ends_with(&format!(".{rule}"))
+    }
+
+    /// 从 git URL 中提取主机名（支持 `git@host:path` 形式的 SSH URL）
+    fn extract_host(url: &str) -> Option<String> {
+        if let Ok(parsed) = Url::parse(url) {
+            return parsed.host_str().map(|h| h.to_lowercase());
+        }
+
+        // scp 风格：[user@]host:path
+        let (host_part, _) = url.split_once(':')?;
+        let host = host_part.rsplit('@').next()?;
+        if host.is_empty() {
+            None
+        } else {
+            Some(host.to_lowercase())
+        }
+    }
+}
@@ -0,0 +1,230 @@
+use crate::metadata::{CargoMetadata, MetadataPackage};
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// 写入 `[source.<name>]` 的源名称
+pub const SOURCE_NAME: &str = "local-registry";
+
+/// 本地 registry 中的一个 crate
+#[derive(Debug, Serialize)]
+pub struct RegistryCrate {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// `.crate` 文件的 SHA-256
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// `local-registry` 的结果
+#[derive(Debug, Serialize)]
+pub struct LocalRegistryReport {
+    pub path: String,
+    pub crates: Vec<RegistryCrate>,
+}
+
+/// index 文件中的一行，格式与 crates.io index 相同
+#[derive(Debug, Serialize, Deserialize)]
+struct IndexEntry {
+    name: String,
+    vers: String,
+    deps: Vec<IndexDependency>,
+    cksum: String,
+    features: BTreeMap<String, Vec<String>>,
+    yanked: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    links: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct IndexDependency {
+    /// 依赖在清单中使用的名称（重命名后的名称）
+    name: String,
+    req: String,
+    features: Vec<String>,
+    optional: bool,
+    default_features: bool,
+    target: Option<String>,
+    kind: String,
+    registry: Option<String>,
+    /// 重命名时为实际的包名
+    #[serde(skip_serializing_if = "Option::is_none")]
+    package: Option<String>,
+}
+
+impl IndexEntry {
+    fn new(package: &MetadataPackage, cksum: String) -> Self {
+        let deps = package
+            .dependencies
+            .iter()
+            .map(|dep| IndexDependency {
+                name: dep.rename.clone().unwrap_or_else(|| dep.name.clone()),
+                req: dep.req.clone(),
+                features: dep.features.clone(),
+                optional: dep.optional,
+                default_features: dep.uses_default_features,
+                target: dep.target.clone(),
+                kind: dep.kind.clone().unwrap_or_else(|| "normal".to_string()),
+                registry: dep.registry.clone(),
+                package: dep.rename.as_ref().map(|_| dep.name.clone()),
+            })
+            .collect();
+        Self {
+            name: package.name.clone(),
+            vers: package.version.clone(),
+            deps,
+            cksum,
+            features: package.features.clone(),
+            yanked: false,
+            links: package.links.clone(),
+        }
+    }
+}
+
+/// 打包 `crate_path` 处的 crate 并加入 `registry_dir`，返回其版本和校验和
+///
+/// `.crate` 文件由 `cargo package` 生成，`target_dir` 用于存放打包的中间产物
+pub fn add_crate(
+    registry_dir: &Path,
+    crate_path: &Path,
+    target_dir: &Path,
+) -> Result<(String, String)> {
+    let manifest_path = crate_path.join("Cargo.toml");
+    let package = CargoMetadata::load_package(&manifest_path)?;
+
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let output = Command::new(cargo)
+        .args(["package", "--no-verify", "--allow-dirty", "--manifest-path"])
+        .arg(&manifest_path)
+        .arg("--target-dir")
+        .arg(target_dir)
+        .output()
+        .context("Failed to run 'cargo package'")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "'cargo package' failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let file_name = format!("{}-{}.crate", package.name, package.version);
+    let packaged = target_dir.join("package").join(&file_name);
+    let data =
+        fs::read(&packaged).with_context(|| format!("Failed to read {}", packaged.display()))?;
+    fs::create_dir_all(registry_dir)?;
+    fs::write(registry_dir.join(&file_name), &data)
+        .with_context(|| format!("Failed to write {file_name} to {}", registry_dir.display()))?;
+
+    let cksum = sha256_hex(&data);
+    write_index_entry(registry_dir, &IndexEntry::new(&package, cksum.clone()))?;
+    Ok((package.version, cksum))
+}
+
+/// crates.io index 的目录结构：`1/a`、`2/ab`、`3/a/abc`、`ab/cd/abcd...`
+fn index_path(registry_dir: &Path, name: &str) -> PathBuf {
+    let name = name.to_lowercase();
+    let index = registry_dir.join("index");
+    match name.len() {
+        1 => index.join("1").join(&name),
+        2 => index.join("2").join(&name),
+        3 => index.join("3").join(&name[..1]).join(&name),
+        _ => index.join(&name[..2]).join(&name[2..4]).join(&name),
+    }
+}
+
+/// 写入 index 条目，替换同一版本已有的行并保留其他版本
+fn write_index_entry(registry_dir: &Path, entry: &IndexEntry) -> Result<()> {
+    let path = index_path(registry_dir, &entry.name);
+    let mut lines: Vec<String> = fs::read_to_string(&path)
+        .unwrap_or_default()
+        .lines()
+        .filter(|line| {
+            serde_json::from_str::<IndexEntry>(line)
+                .is_ok_and(|existing| existing.vers != entry.vers)
+        })
+        .map(str::to_string)
+        .collect();
+    lines.push(serde_json::to_string(entry)?);
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, lines.join("\n") + "\n")
+        .with_context(|| format!("Failed to write index file {}", path.display()))
+}
+
+/// SHA-256 摘要的十六进制表示，与 Cargo 的 `cksum` 一致
+fn sha256_hex(data: &[u8]) -> String {
+    const K: [u32; 64] = [
+        0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4,
+        0xab1c5ed5, 0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe,
+        0x9bdc06a7, 0xc19bf174, 0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f,
+        0x4a7484aa, 0x5cb0a9dc, 0x76f988da, 0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7,
+        0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967, 0x27b70a85, 0x2e1b2138, 0x4d2c6dfc,
+        0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85, 0xa2bfe8a1, 0xa81a664b,
+        0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070, 0x19a4c116,
+        0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+        0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7,
+        0xc67178f2,
+    ];
+    let mut h: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+        0x5be0cd19,
+    ];
+
+    // 填充：追加 0x80，补零到 56 (mod 64) 字节，最后是以位计的长度
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in message.chunks_exact(64) {
+        let mut w = [0u32; 64];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = h;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = hh
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(K[i])
+                .wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            hh = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (state, value) in h.iter_mut().zip([a, b, c, d, e, f, g, hh]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+
+    h.iter().map(|word| format!("{word:08x}")).collect()
+}
@@ -0,0 +1,74 @@
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+/// Cargo.lock 文件的结构
+#[derive(Debug, Deserialize)]
+pub struct CargoLock {
+    #[serde(default)]
+    pub package: Vec<LockedPackage>,
+}
+
+/// Cargo.lock 中锁定的一个包
+#[derive(Debug, Clone, Deserialize)]
+pub struct LockedPackage {
+    pub name: String,
+    pub version: String,
+    pub source: Option<String>,
+}
+
+impl CargoLock {
+    /// 加载与指定 Cargo.toml 同目录的 Cargo.lock
+    pub fn load_for_manifest(manifest_path: &Path) -> Result<Self> {
+        let lock_path = manifest_path.with_file_name("Cargo.lock");
+        if !lock_path.exists() {
+            return Err(anyhow!(
+                "Cargo.lock not found at {} (required by --locked/--frozen)",
+                lock_path.display()
+            ));
+        }
+
+        let content = fs::read_to_string(&lock_path)
+            .with_context(|| format!("Failed to read {}", lock_path.display()))?;
+        toml::from_str(&content).with_context(|| format!("Failed to parse {}", lock_path.display()))
+    }
+
+    /// 查找指定名称的锁定包，同名多版本时返回错误
+    pub fn find_package(&self, name: &str) -> Result<&LockedPackage> {
+        let matches: Vec<_> = self.package.iter().filter(|p| p.name == name).collect();
+        match matches.as_slice() {
+            [] => Err(anyhow!(
+                "Crate '{}' is not in Cargo.lock, the lockfile is missing it or stale (run 'cargo generate-lockfile')",
+                name
+            )),
+            [package] => Ok(package),
+            _ => Err(anyhow!(
+                "Cargo.lock contains multiple versions of '{}' ({}), cannot pick one under --locked",
+                name,
+                matches
+                    .iter()
+                    .map(|p| p.version.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
+    }
+}
+
+impl LockedPackage {
+    /// git 源锁定的提交（`git+URL?branch=x#<commit>` 中 `#` 后的部分）
+    pub fn git_commit(&self) -> Option<&str> {
+        let source = self.source.as_deref()?;
+        if !source.starts_with("git+") {
+            return None;
+        }
+        source.rsplit_once('#').map(|(_, commit)| commit)
+    }
+
+    pub fn is_git(&self) -> bool {
+        self.source
+            .as_deref()
+            .is_some_and(|s| s.starts_with("git+"))
+    }
+}
@@ -3,20 +3,68 @@ extern crate log;
 
 use anyhow::{anyhow, Context, Result};
 use clap::{Arg, Command};
+use indicatif::MultiProgress;
+use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
 use url::Url;
 
+mod audit;
+mod bump;
 mod cargo_toml;
+mod ci;
+mod cleanup;
+mod compat;
 mod config;
+mod coverage;
 mod crates_io;
+mod dedup;
+mod doctor;
+mod dry_run;
 mod git;
+mod github;
+mod global_cache;
+mod hooks;
+mod host_policy;
+mod local_registry;
+mod lockfile;
+mod metadata;
+mod metrics;
+mod mock;
+mod output;
+mod profile;
+mod provenance;
+mod resolve_cache;
+mod retry;
+mod scan;
+mod ssh_config;
+#[cfg(test)]
+mod test_support;
+mod timings;
+mod tree;
+mod watch;
 mod workspace;
 
-use cargo_toml::{CargoToml, DependencyType};
+use cargo_toml::{CargoToml, DependencyInfo, DependencySection, DependencyType};
+use cleanup::FailureCleanup;
 use config::CargoConfig;
 use crates_io::CratesIoClient;
-use git::GitOperations;
+use dry_run::{DryRunPlan, PlannedAction, PlannedCratePath};
+use git::{FastForwardOutcome, GitOperations, GitReference};
+use host_policy::HostPolicy;
+use lockfile::{CargoLock, LockedPackage};
+use output::{
+    CleanStaleReport, DedupReport, DependencyAnalysis, EnvExport, GlobalCacheReport, HookFailure,
+    HookInstall, HooksReport, InitReport, LinkReport, MockReport, OptionalDependency, OutputFormat,
+    PatchDiff, PatchEntry, PatchSourceEntry, PatchSourceReport, PatchUpdate, ReferenceReport,
+    ReplacementEntry, ResetReport, StatusEntry, UnpatchReport, UpdateCheck, UpdateReport,
+};
+use timings::{PhaseTimings, Timings, TimingsReport};
 use workspace::WorkspaceDetector;
 
 #[derive(Debug, Clone)]
@@ -25,19 +73,79 @@ pub struct CrateInfo {
     pub repository_url: String,
     pub is_git_ref: bool,
     pub original_git_url: Option<String>, // 存储原始的 git URL 用于 patch 配置
+    pub git_ref: Option<GitReference>,    // git 依赖中指定的 branch/tag/rev
+    pub registry: Option<String>,         // 来自非 crates.io registry 时的 registry 名称
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    // 初始化日志系统
-    env_logger::Builder::from_default_env()
-        .filter_level(log::LevelFilter::Info)
-        .format_timestamp(None)
-        .format_module_path(false)
-        .format_target(false)
-        .init();
+/// 串行化对 `.cargo/config.toml` 的读-改-写
+static CONFIG_WRITE_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+/// lpatch 主流程的运行选项
+#[derive(Debug, Clone, Default)]
+pub struct LpatchOptions {
+    pub dir: String,
+    pub host_policy: HostPolicy,
+    pub migrate_replace: bool,
+    pub show_dependents: bool,
+    pub locked: bool,           // 只按 Cargo.lock 中锁定的版本打补丁
+    pub frozen: bool,           // 在 locked 的基础上禁止网络访问
+    pub no_crates_io: bool,     // 禁止查询 crates.io，只接受 git 来源
+    pub depth: Option<u32>,     // 浅克隆深度
+    pub retries: Option<u32>,   // 网络操作的重试次数，None 时使用默认值
+    pub no_cache: bool,         // 不使用 crates.io 查询结果的磁盘缓存
+    pub cache_ttl: Option<u64>, // 查询缓存的有效期（秒），None 时使用默认值
+    pub dry_run: bool,          // 只打印将要执行的动作，不修改磁盘
+    /// 命令行中通过 --branch/--tag/--rev 指定的引用，优先于 Cargo.toml 中的设置
+    pub git_ref: Option<GitReference>,
+    pub sparse: bool, // 只检出 crate 所在目录（extract 子命令）
+    /// 并发执行时共享的进度条容器
+    pub progress: Option<MultiProgress>,
+    pub patch_target: PatchTarget,
+    /// --section 指定时只在对应的依赖表中查找
+    pub section: Option<DependencySection>,
+}
+
+impl LpatchOptions {
+    /// crates.io 查询缓存的有效期
+    fn lookup_cache_ttl(&self) -> Duration {
+        self.cache_ttl
+            .map_or(resolve_cache::DEFAULT_TTL, Duration::from_secs)
+    }
+}
+
+/// patch 条目写入的位置
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PatchTarget {
+    /// 项目的 `.cargo/config.toml`（默认，不需要提交）
+    #[default]
+    CargoConfig,
+    /// workspace 根目录 `Cargo.toml` 的 `[patch]` 表
+    CargoToml,
+}
+
+impl PatchTarget {
+    fn parse(s: &str) -> Self {
+        match s {
+            "cargo-toml" => PatchTarget::CargoToml,
+            _ => PatchTarget::CargoConfig,
+        }
+    }
+
+    /// 要编辑的文件路径；Cargo 只读取 workspace 根清单中的 `[patch]`
+    fn path(self, cargo_toml: Option<&CargoToml>) -> Result<PathBuf> {
+        match self {
+            PatchTarget::CargoConfig => Ok(CargoConfig::get_config_path()),
+            PatchTarget::CargoToml => match cargo_toml.and_then(|c| c.workspace_root.as_ref()) {
+                Some(root) => Ok(root.join("Cargo.toml")),
+                None => CargoToml::find_cargo_toml(),
+            },
+        }
+    }
+}
 
-    let matches = Command::new("cargo-lpatch")
+/// 构建完整的命令行定义，供参数解析和补全脚本生成共用
+fn build_cli() -> Command {
+    Command::new("cargo-lpatch")
         .about("Locally patch cargo dependencies by cloning and setting up local patches")
         .subcommand(
             Command::new("lpatch")
@@ -58,121 +166,3252 @@ async fn main() -> Result<()> {
                         .help("Directory to clone the crate into")
                         .default_value("crates"),
                 )
+                .arg(
+                    Arg::new("path")
+                        .long("path")
+                        .visible_alias("use-path")
+                        .value_name("CHECKOUT")
+                        .help("Patch against an existing local checkout instead of cloning")
+                        .requires("name")
+                        .conflicts_with_all(["branch", "tag", "rev", "locked", "frozen", "depth"]),
+                )
+                .arg(
+                    Arg::new("section")
+                        .long("section")
+                        .value_name("SECTION")
+                        .value_parser(["normal", "dev", "build"])
+                        .help("Only look for the crate in [dependencies], [dev-dependencies] or [build-dependencies]")
+                        .requires("name"),
+                )
                 .arg(
                     Arg::new("analyze")
                         .long("analyze")
                         .short('a')
                         .help("Analyze Cargo.toml dependencies and show their types")
                         .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .value_parser(["text", "json"])
+                        .help("Output format for --analyze (overrides --output)")
+                        .requires("analyze"),
+                )
+                .arg(
+                    Arg::new("strict")
+                        .long("strict")
+                        .help("With --analyze, exit with an error if no dependencies are found")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("all")
+                        .long("all")
+                        .help("Patch every git and version dependency in Cargo.toml")
+                        .conflicts_with_all(["name", "analyze", "branch", "tag", "rev"])
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("git-only")
+                        .long("git-only")
+                        .help("With --all, only patch git dependencies")
+                        .requires("all")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("versions-only")
+                        .long("versions-only")
+                        .help("With --all, only patch version (registry) dependencies")
+                        .requires("all")
+                        .conflicts_with("git-only")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("skip")
+                        .long("skip")
+                        .visible_alias("exclude")
+                        .value_name("CRATE_NAME")
+                        .help("With --all, do not patch this crate (can be repeated)")
+                        .requires("all")
+                        .action(clap::ArgAction::Append),
+                )
+                .arg(
+                    Arg::new("jobs")
+                        .long("jobs")
+                        .short('j')
+                        .value_name("N")
+                        .value_parser(clap::value_parser!(u32).range(1..))
+                        .default_value("1")
+                        .help("With --all, number of crates to clone in parallel"),
+                )
+                .arg(
+                    Arg::new("target")
+                        .long("target")
+                        .value_name("FILE")
+                        .value_parser(["cargo-config", "cargo-toml"])
+                        .default_value("cargo-config")
+                        .help("Write the patch to .cargo/config.toml or to the [patch] table of Cargo.toml"),
+                )
+                .arg(
+                    Arg::new("verbose")
+                        .long("verbose")
+                        .short('v')
+                        .help("Show debug output (-vv for trace output)")
+                        .global(true)
+                        .action(clap::ArgAction::Count),
+                )
+                .arg(
+                    Arg::new("quiet")
+                        .long("quiet")
+                        .short('q')
+                        .help("Only show warnings and errors")
+                        .global(true)
+                        .conflicts_with("verbose")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("no-backup")
+                        .long("no-backup")
+                        .help("Do not keep a .bak copy of config files before modifying them")
+                        .global(true)
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("env-file")
+                        .long("env-file")
+                        .value_name("PATH")
+                        .help("Load environment variables (KEY=VALUE per line) from a file before processing")
+                        .global(true),
+                )
+                .arg(
+                    Arg::new("trace-timings")
+                        .long("trace-timings")
+                        .help("Report how long each phase took")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("show-dependents")
+                        .long("show-dependents")
+                        .help("Show which dependencies pull in the patched crate (uses cargo metadata)")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("migrate-replace")
+                        .long("migrate-replace")
+                        .help("Migrate a matching [replace] entry in Cargo.toml to the new [patch] entry")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("branch")
+                        .long("branch")
+                        .value_name("BRANCH")
+                        .help("Check out this branch, overriding the Cargo.toml dependency spec")
+                        .conflicts_with_all(["tag", "rev"]),
+                )
+                .arg(
+                    Arg::new("tag")
+                        .long("tag")
+                        .value_name("TAG")
+                        .help("Check out this tag, overriding the Cargo.toml dependency spec")
+                        .conflicts_with("rev"),
+                )
+                .arg(
+                    Arg::new("rev")
+                        .long("rev")
+                        .value_name("REV")
+                        .help("Check out this commit, overriding the Cargo.toml dependency spec"),
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .help("Show what would be cloned and written without changing anything")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("depth")
+                        .long("depth")
+                        .value_name("N")
+                        .help("Create a shallow clone with only the last N commits (also used when pulling); 0 fetches the full history")
+                        .value_parser(clap::value_parser!(u32)),
+                )
+                .arg(
+                    Arg::new("retries")
+                        .long("retries")
+                        .value_name("N")
+                        .help("Retry crates.io queries and clones up to N times on transient network errors [default: 3]")
+                        .value_parser(clap::value_parser!(u32)),
+                )
+                .arg(
+                    Arg::new("no-cache")
+                        .long("no-cache")
+                        .help("Always query crates.io for repository URLs instead of using the on-disk lookup cache")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("cache-ttl")
+                        .long("cache-ttl")
+                        .value_name("SECONDS")
+                        .help("How long cached crates.io lookups stay valid [default: 86400]")
+                        .value_parser(clap::value_parser!(u64)),
+                )
+                .arg(
+                    Arg::new("no-crates-io")
+                        .long("no-crates-io")
+                        .help("Never contact crates.io, only accept git dependencies or git URLs")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("locked")
+                        .long("locked")
+                        .help("Patch exactly the version pinned in Cargo.lock, error if it is missing or stale")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("frozen")
+                        .long("frozen")
+                        .help("Like --locked, and also forbid network access (only existing clones and local repositories are used)")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("clean-on-failure")
+                        .long("clean-on-failure")
+                        .help("Restore the config and remove newly created directories if any step fails")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("allow-host")
+                        .long("allow-host")
+                        .value_name("HOST")
+                        .help("Only allow cloning from this host (repeatable)")
+                        .action(clap::ArgAction::Append),
+                )
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .value_name("FORMAT")
+                        .help("Output format of the command result (watch, watch-upstream and text generators only support human)")
+                        .value_parser(["human", "json"])
+                        .default_value("human")
+                        .global(true),
+                )
+                .arg(
+                    Arg::new("deny-host")
+                        .long("deny-host")
+                        .value_name("HOST")
+                        .help("Refuse to clone from this host, takes precedence over --allow-host (repeatable)")
+                        .action(clap::ArgAction::Append),
+                )
+                .subcommand(
+                    Command::new("list")
+                        .about("List all active local patches"),
+                )
+                .subcommand(
+                    Command::new("status")
+                        .about("Check that every patch is valid and show the git status of its repository"),
+                )
+                .subcommand(
+                    Command::new("diff")
+                        .about("Show uncommitted changes in patched crates")
+                        .arg(
+                            Arg::new("crate")
+                                .value_name("CRATE_NAME")
+                                .help("Only show changes of this crate (defaults to all patches)"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("doctor")
+                        .about("Check the patch setup for common problems"),
+                )
+                .subcommand(
+                    Command::new("unpatch")
+                        .about("Remove the local patch for a crate from .cargo/config.toml")
+                        .arg(
+                            Arg::new("crate")
+                                .value_name("CRATE_NAME")
+                                .help("Name of the patched crate")
+                                .required_unless_present("name")
+                                .conflicts_with("name"),
+                        )
+                        .arg(
+                            Arg::new("name")
+                                .long("name")
+                                .short('n')
+                                .value_name("CRATE_NAME")
+                                .help("Name of the patched crate"),
+                        )
+                        .arg(
+                            Arg::new("dir")
+                                .long("dir")
+                                .short('d')
+                                .value_name("DIRECTORY")
+                                .help("Directory the crate was cloned into")
+                                .default_value("crates"),
+                        )
+                        .arg(
+                            Arg::new("remove-clone")
+                                .long("remove-clone")
+                                .help("Also delete the cloned directory")
+                                .action(clap::ArgAction::SetTrue),
+                        ),
+                )
+                .subcommand(
+                    Command::new("completions")
+                        .about("Print a shell completion script to stdout")
+                        .hide(true)
+                        .arg(
+                            Arg::new("shell")
+                                .value_name("SHELL")
+                                .help("Shell to generate completions for")
+                                .value_parser(clap::value_parser!(clap_complete::Shell))
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    Command::new("init")
+                        .about("Create the clone directory and .cargo/config.toml for local patches")
+                        .arg(
+                            Arg::new("dir")
+                                .long("dir")
+                                .short('d')
+                                .value_name("DIRECTORY")
+                                .help("Directory crates will be cloned into")
+                                .default_value("crates"),
+                        )
+                        .arg(
+                            Arg::new("gitignore")
+                                .long("gitignore")
+                                .help("Also add the clone directory to .gitignore")
+                                .action(clap::ArgAction::SetTrue),
+                        ),
+                )
+                .subcommand(
+                    Command::new("reset")
+                        .about("Remove all patch entries from .cargo/config.toml")
+                        .arg(
+                            Arg::new("dir")
+                                .long("dir")
+                                .short('d')
+                                .value_name("DIRECTORY")
+                                .help("Directory the crates were cloned into")
+                                .default_value("crates"),
+                        )
+                        .arg(
+                            Arg::new("and-delete")
+                                .long("and-delete")
+                                .help("Also delete the cloned directories under --dir")
+                                .action(clap::ArgAction::SetTrue),
+                        ),
+                )
+                .subcommand(
+                    Command::new("clean-stale")
+                        .about("Delete clones under --dir that are no longer patched in .cargo/config.toml")
+                        .arg(
+                            Arg::new("dir")
+                                .long("dir")
+                                .short('d')
+                                .value_name("DIRECTORY")
+                                .help("Directory the crates were cloned into")
+                                .default_value("crates"),
+                        )
+                        .arg(
+                            Arg::new("all")
+                                .long("all")
+                                .help("Delete every stale clone without prompting")
+                                .action(clap::ArgAction::SetTrue),
+                        ),
+                )
+                .subcommand(
+                    Command::new("convert-all")
+                        .about("Clone every version dependency in Cargo.toml and patch it to the local clone")
+                        .arg(
+                            Arg::new("dir")
+                                .long("dir")
+                                .short('d')
+                                .value_name("DIRECTORY")
+                                .help("Directory to clone the crates into")
+                                .default_value("crates"),
+                        )
+                        .arg(
+                            Arg::new("exclude")
+                                .long("exclude")
+                                .value_name("CRATE_NAME")
+                                .help("Do not convert this crate (can be repeated)")
+                                .action(clap::ArgAction::Append),
+                        )
+                        .arg(
+                            Arg::new("jobs")
+                                .long("jobs")
+                                .short('j')
+                                .value_name("N")
+                                .value_parser(clap::value_parser!(u32).range(1..))
+                                .default_value("4")
+                                .help("Number of crates to clone in parallel"),
+                        )
+                        .arg(
+                            Arg::new("dry-run")
+                                .long("dry-run")
+                                .help("Show what would be cloned and patched without making changes")
+                                .action(clap::ArgAction::SetTrue),
+                        ),
+                )
+                .subcommand(
+                    Command::new("extract")
+                        .about("Patch a crate from a monorepo, checking out only its directory")
+                        .arg(
+                            Arg::new("name")
+                                .long("name")
+                                .short('n')
+                                .value_name("CRATE_NAME")
+                                .help("Name of the crate to patch (can be crate name or git URL)")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("dir")
+                                .long("dir")
+                                .short('d')
+                                .value_name("DIRECTORY")
+                                .help("Directory to clone the crate into")
+                                .default_value("crates"),
+                        )
+                        .arg(
+                            Arg::new("depth")
+                                .long("depth")
+                                .value_name("N")
+                                .value_parser(clap::value_parser!(u32))
+                                .default_value("1")
+                                .help("Clone only the last N commits"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("global-cache")
+                        .about("Share clones between projects through a global cache")
+                        .subcommand_required(true)
+                        .subcommand(
+                            Command::new("enable")
+                                .about("Clone into the shared cache and add per-project worktrees"),
+                        )
+                        .subcommand(
+                            Command::new("disable")
+                                .about("Clone separately into each project again"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("link")
+                        .about("Link an external clone into the clone directory and patch it")
+                        .arg(
+                            Arg::new("name")
+                                .long("name")
+                                .short('n')
+                                .value_name("CRATE_NAME")
+                                .help("Name of the crate to patch")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("target")
+                                .long("target")
+                                .short('t')
+                                .value_name("EXTERNAL_PATH")
+                                .help("Existing checkout to link to")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("dir")
+                                .long("dir")
+                                .short('d')
+                                .value_name("DIRECTORY")
+                                .help("Directory to create the link in")
+                                .default_value("crates"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("scan")
+                        .about("Find references to a crate's API in the project sources")
+                        .arg(
+                            Arg::new("name")
+                                .long("name")
+                                .short('n')
+                                .value_name("CRATE_NAME")
+                                .help("Name of the crate to look for")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("dir")
+                                .long("dir")
+                                .short('d')
+                                .value_name("DIRECTORY")
+                                .help("Clone directory to exclude from the scan")
+                                .default_value("crates"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("update")
+                        .about("Pull the latest upstream changes into patched clones")
+                        .arg(
+                            Arg::new("crate")
+                                .value_name("CRATE_NAME")
+                                .help("Only update this crate (defaults to all patches)"),
+                        )
+                        .arg(
+                            Arg::new("changelog")
+                                .long("changelog")
+                                .help("Print the recorded changelog entry of each updated crate")
+                                .action(clap::ArgAction::SetTrue),
+                        ),
+                )
+                .subcommand(
+                    Command::new("check-updates")
+                        .about("Fetch upstream changes and show how far each patch is behind, without pulling"),
+                )
+                .subcommand(
+                    Command::new("sync")
+                        .about("Fetch upstream for all patched clones and report how far each has diverged")
+                        .arg(
+                            Arg::new("merge")
+                                .long("merge")
+                                .help("Also fast-forward clones that are strictly behind upstream")
+                                .action(clap::ArgAction::SetTrue),
+                        ),
+                )
+                .subcommand(
+                    Command::new("show-patch-source")
+                        .about("Show which [patch.*] source a patch is under and whether Cargo uses it")
+                        .arg(
+                            Arg::new("name")
+                                .long("name")
+                                .short('n')
+                                .value_name("CRATE_NAME")
+                                .help("Name of the patched crate")
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    Command::new("auto-update")
+                        .about("Update all patched clones that have no uncommitted changes"),
+                )
+                .subcommand(
+                    Command::new("init-hooks")
+                        .about("Install git hooks in patched clones")
+                        .arg(
+                            Arg::new("name")
+                                .long("name")
+                                .short('n')
+                                .value_name("CRATE_NAME")
+                                .help("Crate whose clone gets the hook")
+                                .required_unless_present("all")
+                                .conflicts_with("all"),
+                        )
+                        .arg(
+                            Arg::new("all")
+                                .long("all")
+                                .help("Install the hook in every active patch clone")
+                                .action(clap::ArgAction::SetTrue),
+                        )
+                        .arg(
+                            Arg::new("hook")
+                                .long("hook")
+                                .value_name("HOOK")
+                                .help("pre-commit runs cargo fmt --check, pre-push runs cargo test")
+                                .value_parser(["pre-commit", "pre-push"])
+                                .default_value("pre-commit"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("dedup")
+                        .about("Consolidate patched clones that share the same origin repository")
+                        .arg(
+                            Arg::new("dry-run")
+                                .long("dry-run")
+                                .help("Only show what would be consolidated")
+                                .action(clap::ArgAction::SetTrue),
+                        ),
+                )
+                .subcommand(
+                    Command::new("tree")
+                        .about("Show patched crates and which of their dependencies are patched too")
+                        .arg(
+                            Arg::new("depth")
+                                .long("depth")
+                                .value_name("N")
+                                .help("How many levels of dependencies to show below each patch")
+                                .value_parser(clap::value_parser!(usize))
+                                .default_value("1"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("metrics")
+                        .about("Report aggregate statistics about all active patches"),
+                )
+                .subcommand(
+                    Command::new("watch")
+                        .about("Periodically pull a patched crate and report new commits")
+                        .arg(
+                            Arg::new("name")
+                                .long("name")
+                                .short('n')
+                                .value_name("CRATE_NAME")
+                                .help("Name of the patched crate")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("dir")
+                                .long("dir")
+                                .short('d')
+                                .value_name("DIRECTORY")
+                                .help("Directory the crate was cloned into")
+                                .default_value("crates"),
+                        )
+                        .arg(
+                            Arg::new("interval")
+                                .long("interval")
+                                .value_name("INTERVAL")
+                                .help("Polling interval in seconds, or with a unit (e.g. 5m)")
+                                .default_value("300"),
+                        )
+                        .arg(
+                            Arg::new("check")
+                                .long("check")
+                                .help("Run 'cargo check' after each update")
+                                .action(clap::ArgAction::SetTrue),
+                        ),
+                )
+                .subcommand(
+                    Command::new("watch-upstream")
+                        .about("Watch a patched crate's upstream repository for new tags")
+                        .arg(
+                            Arg::new("name")
+                                .long("name")
+                                .short('n')
+                                .value_name("CRATE_NAME")
+                                .help("Name of the patched crate")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("dir")
+                                .long("dir")
+                                .short('d')
+                                .value_name("DIRECTORY")
+                                .help("Directory the crate was cloned into")
+                                .default_value("crates"),
+                        )
+                        .arg(
+                            Arg::new("interval")
+                                .long("interval")
+                                .value_name("INTERVAL")
+                                .help("Polling interval, e.g. 30m or 1h")
+                                .default_value("1h"),
+                        )
+                        .arg(
+                            Arg::new("on-release")
+                                .long("on-release")
+                                .value_name("SCRIPT")
+                                .help("Command to run for each new tag (LPATCH_CRATE and LPATCH_TAG are set)"),
+                        )
+                        .arg(
+                            Arg::new("daemon")
+                                .long("daemon")
+                                .help("Keep polling until interrupted instead of checking once")
+                                .action(clap::ArgAction::SetTrue),
+                        ),
+                )
+                .subcommand(
+                    Command::new("export-env")
+                        .about("Print shell export commands for a patch session")
+                        .arg(
+                            Arg::new("target-dir")
+                                .long("target-dir")
+                                .value_name("DIRECTORY")
+                                .help("Target directory for patched builds (defaults to target/lpatch)"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("generate-ci-config")
+                        .about("Generate a CI workflow that re-creates the local patches and builds")
+                        .arg(
+                            Arg::new("format")
+                                .long("format")
+                                .value_name("FORMAT")
+                                .value_parser(["github", "gitlab", "circleci"])
+                                .default_value("github")
+                                .help("CI platform to generate configuration for"),
+                        )
+                        .arg(
+                            Arg::new("file")
+                                .long("file")
+                                .short('f')
+                                .value_name("FILE")
+                                .help("Write to this file instead of stdout"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("add-ci-secret")
+                        .about("Print the CI secrets and setup steps needed to re-create the patches")
+                        .arg(
+                            Arg::new("ci-provider")
+                                .long("ci-provider")
+                                .value_name("PROVIDER")
+                                .value_parser(["github", "gitlab", "circleci"])
+                                .default_value("github")
+                                .help("CI platform to format the instructions for"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("dependency-audit")
+                        .about("List transitive dependencies a patched crate adds compared to its published version")
+                        .arg(
+                            Arg::new("name")
+                                .long("name")
+                                .short('n')
+                                .value_name("CRATE_NAME")
+                                .help("Name of the patched crate")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("strict")
+                                .long("strict")
+                                .help("Exit with an error if any new dependency is found")
+                                .action(clap::ArgAction::SetTrue),
+                        ),
+                )
+                .subcommand(
+                    Command::new("version-bump")
+                        .about("Increment the version in a patched crate's Cargo.toml")
+                        .arg(
+                            Arg::new("name")
+                                .long("name")
+                                .short('n')
+                                .value_name("CRATE_NAME")
+                                .help("Name of the patched crate")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("bump")
+                                .long("bump")
+                                .value_name("PART")
+                                .value_parser(["patch", "minor", "major"])
+                                .default_value("patch")
+                                .help("Which part of the version to increment"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("profile-memory")
+                        .about("Compare the peak memory of cargo build with and without a patch")
+                        .arg(
+                            Arg::new("name")
+                                .long("name")
+                                .short('n')
+                                .value_name("CRATE_NAME")
+                                .help("Name of the patched crate")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("platform")
+                                .long("platform")
+                                .value_name("PLATFORM")
+                                .value_parser(["linux", "macos"])
+                                .help("How to sample memory: /proc on linux, ps on macos")
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    Command::new("search-patch")
+                        .about("Search GitHub for forks of a crate that may already contain a fix")
+                        .arg(
+                            Arg::new("name")
+                                .long("name")
+                                .short('n')
+                                .value_name("CRATE_NAME")
+                                .help("Name of the crate")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("keyword")
+                                .long("keyword")
+                                .short('k')
+                                .value_name("TEXT")
+                                .help("Words describing the fix, e.g. \"fix memory leak\""),
+                        )
+                        .arg(
+                            Arg::new("limit")
+                                .long("limit")
+                                .value_name("N")
+                                .value_parser(clap::value_parser!(u32).range(1..=100))
+                                .default_value("5")
+                                .help("Number of repositories to show"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("check-compat")
+                        .about("Compare exported symbols of a patched cdylib/staticlib crate with the original")
+                        .arg(
+                            Arg::new("name")
+                                .long("name")
+                                .short('n')
+                                .value_name("CRATE_NAME")
+                                .help("Name of the patched crate")
+                                .required(true),
+                        )
+                        .arg(Arg::new("base").long("base").value_name("REV").help(
+                            "Original revision (defaults to the nearest tag, then the upstream branch)",
+                        )),
+                )
+                .subcommand(
+                    Command::new("coverage")
+                        .about("Measure test coverage of a patched crate and report uncovered lines added by the patch")
+                        .arg(
+                            Arg::new("name")
+                                .long("name")
+                                .short('n')
+                                .value_name("CRATE_NAME")
+                                .help("Name of the patched crate")
+                                .required(true),
+                        )
+                        .arg(Arg::new("base").long("base").value_name("REV").help(
+                            "Original revision (defaults to the nearest tag, then the upstream branch)",
+                        )),
+                )
+                .subcommand(
+                    Command::new("local-registry")
+                        .about("Package every patched clone into a local registry and add it as a source in .cargo/config.toml")
+                        .arg(
+                            Arg::new("path")
+                                .long("path")
+                                .value_name("DIRECTORY")
+                                .help("Directory of the local registry")
+                                .default_value("local-registry"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("create-mock")
+                        .about("Generate a stub crate with todo!() bodies from a dependency's public API and patch it in")
+                        .arg(
+                            Arg::new("name")
+                                .long("name")
+                                .short('n')
+                                .value_name("CRATE_NAME")
+                                .help("Name of the dependency to mock")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("dir")
+                                .long("dir")
+                                .short('d')
+                                .value_name("DIRECTORY")
+                                .help("Directory to create the mock crate in")
+                                .default_value("mocks"),
+                        )
+                        .arg(
+                            Arg::new("toolchain")
+                                .long("toolchain")
+                                .value_name("TOOLCHAIN")
+                                .help("Toolchain used to generate rustdoc JSON (requires nightly)")
+                                .default_value("nightly"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("auto-pr-description")
+                        .about("Generate a PR description from the commits in a local patch")
+                        .arg(
+                            Arg::new("name")
+                                .long("name")
+                                .short('n')
+                                .value_name("CRATE_NAME")
+                                .help("Name of the patched crate")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("dir")
+                                .long("dir")
+                                .short('d')
+                                .value_name("DIRECTORY")
+                                .help("Directory the crate was cloned into")
+                                .default_value("crates"),
+                        )
+                        .arg(Arg::new("base").long("base").value_name("REV").help(
+                            "Base revision (defaults to the nearest tag, then the upstream branch)",
+                        ))
+                        .arg(
+                            Arg::new("write")
+                                .long("write")
+                                .short('w')
+                                .help("Also write the description to PR_BODY.md")
+                                .action(clap::ArgAction::SetTrue),
+                        ),
                 ),
         )
-        .get_matches();
+}
+
+/// 初始化日志系统
+///
+/// `-v`/`-q` 优先；都未指定时使用 `RUST_LOG`，未设置 `RUST_LOG` 时默认为 info
+fn init_logger(matches: Option<&clap::ArgMatches>) {
+    let verbose = matches.map_or(0, |m| m.get_count("verbose"));
+    let quiet = matches.is_some_and(|m| m.get_flag("quiet"));
+
+    let mut builder = env_logger::Builder::new();
+    builder
+        .format_timestamp(None)
+        .format_module_path(false)
+        .format_target(false);
+    match (verbose, quiet) {
+        (_, true) => builder.filter_level(log::LevelFilter::Warn),
+        (0, _) => match std::env::var("RUST_LOG") {
+            Ok(filters) => builder.parse_filters(&filters),
+            Err(_) => builder.filter_level(log::LevelFilter::Info),
+        },
+        (1, _) => builder.filter_level(log::LevelFilter::Debug),
+        _ => builder.filter_level(log::LevelFilter::Trace),
+    };
+    builder.init();
+}
+
+fn main() -> Result<()> {
+    let matches = build_cli().get_matches();
+    // env 文件中可能设置了 RUST_LOG，需要在初始化日志之前加载；
+    // set_var 不是线程安全的，也必须在 tokio 运行时启动工作线程之前完成
+    let env_file = matches
+        .subcommand_matches("lpatch")
+        .and_then(|m| m.get_one::<String>("env-file"));
+    let env_count = env_file
+        .map(|env_file| load_env_file(Path::new(env_file)))
+        .transpose()?;
+    init_logger(matches.subcommand_matches("lpatch"));
+    if let (Some(env_file), Some(count)) = (env_file, env_count) {
+        debug!("📄 Loaded {count} environment variables from {env_file}");
+    }
+
+    tokio::runtime::Runtime::new()?.block_on(run(matches))
+}
+
+async fn run(matches: clap::ArgMatches) -> Result<()> {
+    if let Some(lpatch_matches) = matches.subcommand_matches("lpatch") {
+        if lpatch_matches.get_flag("no-backup") {
+            CargoConfig::disable_backups();
+        }
+        let output = OutputFormat::parse(lpatch_matches.get_one::<String>("output").unwrap());
+        // 这些子命令输出的是 Markdown、CI 配置或补全脚本本身，没有对应的 JSON 形式；
+        // watch 和 watch-upstream 持续运行，不会产生一个结果文档
+        if let (
+            OutputFormat::Json,
+            Some((
+                command @ ("auto-pr-description"
+                | "completions"
+                | "generate-ci-config"
+                | "add-ci-secret"
+                | "watch"
+                | "watch-upstream"),
+                _,
+            )),
+        ) = (output, lpatch_matches.subcommand())
+        {
+            return Err(anyhow!("'{command}' does not support --output json"));
+        }
+
+        match lpatch_matches.subcommand() {
+            Some(("auto-pr-description", sub_matches)) => {
+                let name = sub_matches.get_one::<String>("name").unwrap();
+                let dir = sub_matches.get_one::<String>("dir").unwrap();
+                let base = sub_matches.get_one::<String>("base");
+                let write = sub_matches.get_flag("write");
+                return auto_pr_description(name, dir, base.map(String::as_str), write);
+            }
+            Some(("dependency-audit", sub_matches)) => {
+                let name = sub_matches.get_one::<String>("name").unwrap();
+                return dependency_audit(name, sub_matches.get_flag("strict"), output);
+            }
+            Some(("version-bump", sub_matches)) => {
+                let name = sub_matches.get_one::<String>("name").unwrap();
+                let bump = bump::Bump::parse(sub_matches.get_one::<String>("bump").unwrap());
+                return version_bump(name, bump, output);
+            }
+            Some(("profile-memory", sub_matches)) => {
+                let name = sub_matches.get_one::<String>("name").unwrap();
+                let platform =
+                    profile::Platform::parse(sub_matches.get_one::<String>("platform").unwrap());
+                return profile_memory(name, platform, output);
+            }
+            Some(("search-patch", sub_matches)) => {
+                let name = sub_matches.get_one::<String>("name").unwrap();
+                let keyword = sub_matches.get_one::<String>("keyword");
+                let limit = *sub_matches.get_one::<u32>("limit").unwrap() as usize;
+                let hits = github::GithubClient::from_env()?
+                    .search_patches(name, keyword.map(String::as_str), limit)
+                    .await?;
+                return output.reporter().patch_search(&hits);
+            }
+            Some(("check-compat", sub_matches)) => {
+                let name = sub_matches.get_one::<String>("name").unwrap();
+                let base = sub_matches.get_one::<String>("base");
+                return check_compat(name, base.map(String::as_str), output);
+            }
+            Some(("local-registry", sub_matches)) => {
+                let path = sub_matches.get_one::<String>("path").unwrap();
+                return build_local_registry(Path::new(path), output);
+            }
+            Some(("coverage", sub_matches)) => {
+                let name = sub_matches.get_one::<String>("name").unwrap();
+                let base = sub_matches.get_one::<String>("base");
+                return patch_coverage(name, base.map(String::as_str), output);
+            }
+            Some(("create-mock", sub_matches)) => {
+                let name = sub_matches.get_one::<String>("name").unwrap();
+                let dir = sub_matches.get_one::<String>("dir").unwrap();
+                let toolchain = sub_matches.get_one::<String>("toolchain").unwrap();
+                return create_mock(name, dir, toolchain, output);
+            }
+            Some(("list", _)) => {
+                return list_patches(output);
+            }
+            Some(("diff", sub_matches)) => {
+                let name = sub_matches.get_one::<String>("crate");
+                return show_diff(name.map(String::as_str), output);
+            }
+            Some(("doctor", _)) => {
+                return run_doctor(output);
+            }
+            Some(("status", _)) => {
+                return show_status(output);
+            }
+            Some(("unpatch", sub_matches)) => {
+                let name = sub_matches
+                    .get_one::<String>("crate")
+                    .or_else(|| sub_matches.get_one::<String>("name"))
+                    .unwrap();
+                let dir = sub_matches.get_one::<String>("dir").unwrap();
+                return unpatch(name, dir, sub_matches.get_flag("remove-clone"), output);
+            }
+            Some(("completions", sub_matches)) => {
+                let shell = *sub_matches
+                    .get_one::<clap_complete::Shell>("shell")
+                    .unwrap();
+                return generate_completions(shell);
+            }
+            Some(("init", sub_matches)) => {
+                let dir = sub_matches.get_one::<String>("dir").unwrap();
+                return init_project(dir, sub_matches.get_flag("gitignore"), output);
+            }
+            Some(("reset", sub_matches)) => {
+                let dir = sub_matches.get_one::<String>("dir").unwrap();
+                return reset_patches(dir, sub_matches.get_flag("and-delete"), output);
+            }
+            Some(("clean-stale", sub_matches)) => {
+                let dir = sub_matches.get_one::<String>("dir").unwrap();
+                return clean_stale(dir, sub_matches.get_flag("all"), output);
+            }
+            Some(("convert-all", sub_matches)) => {
+                let options = LpatchOptions {
+                    dir: sub_matches.get_one::<String>("dir").unwrap().clone(),
+                    dry_run: sub_matches.get_flag("dry-run"),
+                    ..Default::default()
+                };
+                let exclude: Vec<String> = sub_matches
+                    .get_many::<String>("exclude")
+                    .map(|names| names.cloned().collect())
+                    .unwrap_or_default();
+                let jobs = *sub_matches.get_one::<u32>("jobs").unwrap() as usize;
+                return convert_all(&options, &exclude, jobs, output).await;
+            }
+            Some(("extract", sub_matches)) => {
+                let name = sub_matches.get_one::<String>("name").unwrap();
+                let options = LpatchOptions {
+                    dir: sub_matches.get_one::<String>("dir").unwrap().clone(),
+                    depth: sub_matches.get_one::<u32>("depth").copied(),
+                    sparse: true,
+                    ..Default::default()
+                };
+                let mut timings = PhaseTimings::new(false);
+                let mut cleanup = FailureCleanup::new(false);
+                let plan = run_lpatch(name, &options, &mut timings, &mut cleanup).await?;
+                return output.reporter().extracted(&plan);
+            }
+            Some(("global-cache", sub_matches)) => {
+                return configure_global_cache(
+                    sub_matches.subcommand_name() == Some("enable"),
+                    output,
+                );
+            }
+            Some(("link", sub_matches)) => {
+                let name = sub_matches.get_one::<String>("name").unwrap();
+                let target = sub_matches.get_one::<String>("target").unwrap();
+                let dir = sub_matches.get_one::<String>("dir").unwrap();
+                return link_external_clone(name, Path::new(target), dir, output);
+            }
+            Some(("scan", sub_matches)) => {
+                let name = sub_matches.get_one::<String>("name").unwrap();
+                let dir = sub_matches.get_one::<String>("dir").unwrap();
+                return scan_crate_references(name, dir, output);
+            }
+            Some(("update", sub_matches)) => {
+                let name = sub_matches.get_one::<String>("crate");
+                return update_patches(
+                    name.map(String::as_str),
+                    sub_matches.get_flag("changelog"),
+                    output,
+                );
+            }
+            Some(("check-updates", _)) => {
+                return check_updates(output);
+            }
+            Some(("sync", sub_matches)) => {
+                return sync_patches(sub_matches.get_flag("merge"), output);
+            }
+            Some(("show-patch-source", sub_matches)) => {
+                let name = sub_matches.get_one::<String>("name").unwrap();
+                return show_patch_source(name, output);
+            }
+            Some(("auto-update", _)) => {
+                return auto_update(output);
+            }
+            Some(("init-hooks", sub_matches)) => {
+                let name = sub_matches.get_one::<String>("name");
+                let hook = sub_matches.get_one::<String>("hook").unwrap();
+                return init_hooks(name.map(String::as_str), hook, output);
+            }
+            Some(("dedup", sub_matches)) => {
+                return dedup_clones(sub_matches.get_flag("dry-run"), output);
+            }
+            Some(("tree", sub_matches)) => {
+                let depth = *sub_matches.get_one::<usize>("depth").unwrap();
+                return show_patch_tree(depth, output);
+            }
+            Some(("metrics", _)) => {
+                return show_metrics(output);
+            }
+            Some(("watch", sub_matches)) => {
+                let name = sub_matches.get_one::<String>("name").unwrap();
+                let dir = sub_matches.get_one::<String>("dir").unwrap();
+                let interval =
+                    watch::parse_interval(sub_matches.get_one::<String>("interval").unwrap())?;
+                let clone_path = PathBuf::from(dir).join(name);
+                if !clone_path.exists() {
+                    return Err(anyhow!(
+                        "No local patch found for '{}' at '{}'",
+                        name,
+                        clone_path.display()
+                    ));
+                }
+                return watch::watch_pull(
+                    name,
+                    &clone_path,
+                    interval,
+                    sub_matches.get_flag("check"),
+                    CargoConfig::project_dir(),
+                )
+                .await;
+            }
+            Some(("watch-upstream", sub_matches)) => {
+                let name = sub_matches.get_one::<String>("name").unwrap();
+                let dir = sub_matches.get_one::<String>("dir").unwrap();
+                let interval =
+                    watch::parse_interval(sub_matches.get_one::<String>("interval").unwrap())?;
+                let on_release = sub_matches.get_one::<String>("on-release");
+                let clone_path = PathBuf::from(dir).join(name);
+                if !clone_path.exists() {
+                    return Err(anyhow!(
+                        "No local patch found for '{}' at '{}'",
+                        name,
+                        clone_path.display()
+                    ));
+                }
+                return watch::watch_upstream(
+                    name,
+                    &clone_path,
+                    interval,
+                    on_release.map(String::as_str),
+                    sub_matches.get_flag("daemon"),
+                )
+                .await;
+            }
+            Some(("generate-ci-config", sub_matches)) => {
+                let format = sub_matches.get_one::<String>("format").unwrap();
+                let file = sub_matches.get_one::<String>("file");
+                return generate_ci_config(format, file.map(String::as_str));
+            }
+            Some(("add-ci-secret", sub_matches)) => {
+                let provider = sub_matches.get_one::<String>("ci-provider").unwrap();
+                return add_ci_secret(provider);
+            }
+            Some(("export-env", sub_matches)) => {
+                let target_dir = sub_matches.get_one::<String>("target-dir");
+                return export_env(target_dir.map(String::as_str), output);
+            }
+            _ => {}
+        }
+
+        let name = lpatch_matches.get_one::<String>("name");
+        let dir = lpatch_matches.get_one::<String>("dir").unwrap();
+        let analyze = lpatch_matches.get_flag("analyze");
+        let trace_timings = lpatch_matches.get_flag("trace-timings");
+        let host_policy = HostPolicy::new(
+            lpatch_matches
+                .get_many::<String>("allow-host")
+                .map(|hosts| hosts.cloned().collect())
+                .unwrap_or_default(),
+            lpatch_matches
+                .get_many::<String>("deny-host")
+                .map(|hosts| hosts.cloned().collect())
+                .unwrap_or_default(),
+        );
+        let options = LpatchOptions {
+            dir: dir.clone(),
+            host_policy,
+            migrate_replace: lpatch_matches.get_flag("migrate-replace"),
+            show_dependents: lpatch_matches.get_flag("show-dependents"),
+            locked: lpatch_matches.get_flag("locked") || lpatch_matches.get_flag("frozen"),
+            frozen: lpatch_matches.get_flag("frozen"),
+            no_crates_io: lpatch_matches.get_flag("no-crates-io"),
+            depth: lpatch_matches.get_one::<u32>("depth").copied(),
+            retries: lpatch_matches.get_one::<u32>("retries").copied(),
+            no_cache: lpatch_matches.get_flag("no-cache"),
+            cache_ttl: lpatch_matches.get_one::<u64>("cache-ttl").copied(),
+            dry_run: lpatch_matches.get_flag("dry-run"),
+            git_ref: lpatch_matches
+                .get_one::<String>("rev")
+                .cloned()
+                .map(GitReference::Rev)
+                .or_else(|| {
+                    lpatch_matches
+                        .get_one::<String>("tag")
+                        .cloned()
+                        .map(GitReference::Tag)
+                })
+                .or_else(|| {
+                    lpatch_matches
+                        .get_one::<String>("branch")
+                        .cloned()
+                        .map(GitReference::Branch)
+                }),
+            sparse: false,
+            progress: None,
+            patch_target: PatchTarget::parse(lpatch_matches.get_one::<String>("target").unwrap()),
+            section: lpatch_matches
+                .get_one::<String>("section")
+                .map(|s| DependencySection::parse(s)),
+        };
+
+        if analyze {
+            let output = match lpatch_matches.get_one::<String>("format") {
+                Some(format) => OutputFormat::parse(format),
+                None => output,
+            };
+            analyze_dependencies(lpatch_matches.get_flag("strict"), output).await?;
+        } else if let (Some(name), Some(checkout)) =
+            (name, lpatch_matches.get_one::<String>("path"))
+        {
+            patch_checkout(name, Path::new(checkout), &options, output)?;
+        } else if let Some(name) = name {
+            let clean_on_failure = lpatch_matches.get_flag("clean-on-failure");
+            patch_one(name, &options, trace_timings, clean_on_failure, output).await?;
+        } else if lpatch_matches.get_flag("all") {
+            let skip: Vec<String> = lpatch_matches
+                .get_many::<String>("skip")
+                .map(|names| names.cloned().collect())
+                .unwrap_or_default();
+            let jobs = *lpatch_matches.get_one::<u32>("jobs").unwrap() as usize;
+            let clean_on_failure = lpatch_matches.get_flag("clean-on-failure");
+            if jobs > 1 && clean_on_failure {
+                // 回滚会恢复整个配置文件，覆盖其他并发任务写入的补丁
+                return Err(anyhow!(
+                    "--clean-on-failure cannot be combined with --jobs > 1"
+                ));
+            }
+            patch_all(
+                &options,
+                AllFilter::from_flags(
+                    lpatch_matches.get_flag("git-only"),
+                    lpatch_matches.get_flag("versions-only"),
+                ),
+                &skip,
+                jobs,
+                trace_timings,
+                clean_on_failure,
+                output,
+            )
+            .await?;
+        } else if std::io::stdin().is_terminal() && std::io::stderr().is_terminal() {
+            // 未指定 --name 时在终端中交互选择要补丁的依赖
+            let name = select_dependency()?;
+            let clean_on_failure = lpatch_matches.get_flag("clean-on-failure");
+            patch_one(&name, &options, trace_timings, clean_on_failure, output).await?;
+        } else {
+            // 非交互环境中无法选择，显示帮助
+            error!("Either --name, --all or --analyze must be specified.");
+            error!("Interactive selection is only available when stdin is a terminal.");
+            error!("Use --help for more information.");
+            std::process::exit(1);
+        }
+    }
+
+    Ok(())
+}
+
+/// 为单个 crate 创建补丁，失败时按需回滚
+async fn patch_one(
+    name: &str,
+    options: &LpatchOptions,
+    trace_timings: bool,
+    clean_on_failure: bool,
+    output: OutputFormat,
+) -> Result<()> {
+    let mut timings = PhaseTimings::new(trace_timings);
+    let mut cleanup = FailureCleanup::new(clean_on_failure);
+    let mut result = run_lpatch(name, options, &mut timings, &mut cleanup).await;
+    if result.is_err() {
+        cleanup.rollback();
+    }
+    report_run(name, &mut result, timings.summary(), output)?;
+    result.map(|_| ())
+}
+
+/// 输出一次 lpatch 的结果：dry-run 时输出计划，`--trace-timings` 的耗时附在计划中，
+/// 没有计划（正常执行或失败）时单独输出
+fn report_run(
+    name: &str,
+    result: &mut Result<DryRunPlan>,
+    timings: Option<Timings>,
+    output: OutputFormat,
+) -> Result<()> {
+    match result {
+        Ok(plan) if plan.dry_run => {
+            plan.timings = timings;
+            output.reporter().plan(plan)
+        }
+        _ => match timings {
+            Some(timings) => output.reporter().timings(&TimingsReport {
+                name: name.to_string(),
+                timings,
+            }),
+            None => Ok(()),
+        },
+    }
+}
+
+/// 在 Cargo.toml 中查找依赖，`section` 为空且依赖出现在多个表中时取第一个并提示
+fn lookup_dependency(
+    cargo_toml: &CargoToml,
+    name: &str,
+    section: Option<DependencySection>,
+) -> Option<DependencyInfo> {
+    if let Some(section) = section {
+        return cargo_toml.find_dependency_in_section(name, section);
+    }
+
+    let mut sections: Vec<_> = cargo_toml
+        .get_all_dependencies()
+        .into_iter()
+        .filter(|dep| dep.name == name)
+        .map(|dep| dep.section)
+        .collect();
+    sections.sort();
+    sections.dedup();
+    if sections.len() > 1 {
+        let tables: Vec<_> = sections.iter().map(|s| s.table_name()).collect();
+        info!(
+            "💡 '{}' appears in [{}], use --section to choose one",
+            name,
+            tables.join("], [")
+        );
+    }
+    cargo_toml.find_dependency(name)
+}
+
+/// 不克隆，直接将补丁指向已有的本地检出（`--path`）
+///
+/// 写入前检查目录存在且确实包含该 crate
+fn patch_checkout(
+    name: &str,
+    checkout: &Path,
+    options: &LpatchOptions,
+    output: OutputFormat,
+) -> Result<()> {
+    if !checkout.is_dir() {
+        return Err(anyhow!(
+            "Checkout '{}' does not exist or is not a directory",
+            checkout.display()
+        ));
+    }
+
+    let cargo_toml = CargoToml::find_and_load().ok();
+    let dependency_info = cargo_toml
+        .as_ref()
+        .and_then(|cargo_toml| lookup_dependency(cargo_toml, name, options.section));
+    // 与克隆时相同的规则选择 patch 源
+    let (crate_name, patch_source) = match dependency_info {
+        Some(dep) => match dep.dep_type.patch_source() {
+            Some(patch_source) => (dep.name, patch_source),
+            None => {
+                return Err(anyhow!(
+                    "Path dependency '{}' cannot be patched as it's already local",
+                    dep.name
+                ));
+            }
+        },
+        None if is_git_url(name) => {
+            let git_url = normalize_local_git_url(name)?;
+            (extract_crate_name_from_git_url(&git_url)?, git_url)
+        }
+        None => (name.to_string(), cargo_toml::patch_source(None, None)),
+    };
+
+    let crate_path =
+        WorkspaceDetector::find_crate_path(checkout, &crate_name).with_context(|| {
+            format!(
+                "Checkout '{}' does not contain crate '{}'",
+                checkout.display(),
+                crate_name
+            )
+        })?;
+    if crate_path != checkout {
+        info!(
+            "🎯 Found crate '{}' in workspace at: {}",
+            crate_name,
+            crate_path.display()
+        );
+    }
+
+    let patch_file = options.patch_target.path(cargo_toml.as_ref())?;
+    let mut plan = DryRunPlan::new(options.dry_run);
+    plan.clone_path = Some(checkout.to_path_buf());
+    plan.crate_path = Some(PlannedCratePath::Resolved {
+        path: crate_path.clone(),
+    });
+    if !plan.record(PlannedAction::WritePatch {
+        config: patch_file.clone(),
+        crate_name: crate_name.clone(),
+        source: patch_source.clone(),
+        path: crate_path.clone(),
+    }) {
+        info!("🔍 Dry run, no changes made");
+        return output.reporter().plan(&plan);
+    }
+
+    let mut cargo_config = match options.patch_target {
+        PatchTarget::CargoConfig => CargoConfig::load_or_create()?,
+        PatchTarget::CargoToml => CargoConfig::load_manifest(&patch_file)?,
+    };
+    cargo_config.add_patch_with_source(&crate_name, &crate_path, &patch_source)?;
+    cargo_config.save()?;
+
+    info!("✅ Successfully set up local patch for '{crate_name}'");
+    info!("📁 Using existing checkout: {}", crate_path.display());
+    Ok(())
+}
+
+/// 列出 Cargo.toml 中的版本依赖和 git 依赖，让用户用方向键选择一个
+fn select_dependency() -> Result<String> {
+    let cargo_toml = CargoToml::find_and_load().context("Failed to find and load Cargo.toml")?;
+    let mut dependencies: Vec<_> = cargo_toml
+        .get_version_dependencies()
+        .into_iter()
+        .chain(cargo_toml.get_git_dependencies())
+        .collect();
+    dependencies.sort_by(|a, b| a.name.cmp(&b.name));
+    dependencies.dedup_by(|a, b| a.name == b.name);
+    if dependencies.is_empty() {
+        return Err(anyhow!(
+            "No version or git dependencies found in Cargo.toml"
+        ));
+    }
+
+    let labels: Vec<String> = dependencies
+        .iter()
+        .map(|dep| match &dep.dep_type {
+            DependencyType::Git { git, .. } => format!("{} (git: {})", dep.name, git),
+            DependencyType::Version { version, .. } => format!("{} ({})", dep.name, version),
+            DependencyType::Path { path } => format!("{} (path: {})", dep.name, path),
+        })
+        .collect();
+    let selection = dialoguer::Select::new()
+        .with_prompt("Select a dependency to patch")
+        .items(&labels)
+        .default(0)
+        .interact()
+        .context("Failed to read selection")?;
+    Ok(dependencies.swap_remove(selection).name)
+}
+
+/// `--all` 时要包含的依赖类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AllFilter {
+    Any,
+    GitOnly,
+    VersionsOnly,
+}
+
+impl AllFilter {
+    fn from_flags(git_only: bool, versions_only: bool) -> Self {
+        match (git_only, versions_only) {
+            (true, _) => AllFilter::GitOnly,
+            (_, true) => AllFilter::VersionsOnly,
+            _ => AllFilter::Any,
+        }
+    }
+}
+
+/// 为 Cargo.toml 中的每个依赖创建补丁，最多 `jobs` 个同时进行，单个依赖失败不会中断整个流程
+async fn patch_all(
+    options: &LpatchOptions,
+    filter: AllFilter,
+    skip: &[String],
+    jobs: usize,
+    trace_timings: bool,
+    clean_on_failure: bool,
+    output: OutputFormat,
+) -> Result<()> {
+    let cargo_toml = CargoToml::find_and_load().context("Failed to find and load Cargo.toml")?;
+
+    // 同一个依赖可能同时出现在 dependencies 和 dev-dependencies 中
+    let mut dependencies = cargo_toml.get_all_dependencies();
+    dependencies.sort_by(|a, b| a.name.cmp(&b.name));
+    dependencies.dedup_by(|a, b| a.name == b.name);
+
+    let mut selected = Vec::new();
+    for dep in dependencies {
+        if skip.contains(&dep.name) {
+            info!("⏭️  Skipping '{}' (--exclude)", dep.name);
+            continue;
+        }
+        match &dep.dep_type {
+            DependencyType::Path { path } => {
+                info!(
+                    "⏭️  Skipping path dependency '{}' ({path}), it is already local",
+                    dep.name
+                );
+                continue;
+            }
+            DependencyType::Version { .. } if filter == AllFilter::GitOnly => {
+                debug!("Skipping version dependency '{}' (--git-only)", dep.name);
+                continue;
+            }
+            DependencyType::Git { .. } if filter == AllFilter::VersionsOnly => {
+                debug!("Skipping git dependency '{}' (--versions-only)", dep.name);
+                continue;
+            }
+            _ => {}
+        }
+        selected.push(dep.name);
+    }
+
+    // git2 的克隆是阻塞操作，每个依赖在独立的阻塞线程中运行，由信号量限制并发数
+    let progress = MultiProgress::new();
+    let semaphore = Arc::new(Semaphore::new(jobs));
+    let mut tasks = JoinSet::new();
+    for name in selected {
+        let permit = semaphore.clone().acquire_owned().await?;
+        let options = LpatchOptions {
+            progress: (jobs > 1).then(|| progress.clone()),
+            ..options.clone()
+        };
+        tasks.spawn_blocking(move || {
+            let _permit = permit;
+            info!("📦 Patching '{name}'...");
+            let mut timings = PhaseTimings::new(trace_timings);
+            let mut cleanup = FailureCleanup::new(clean_on_failure);
+            let result = tokio::runtime::Handle::current().block_on(run_lpatch(
+                &name,
+                &options,
+                &mut timings,
+                &mut cleanup,
+            ));
+            if result.is_err() {
+                cleanup.rollback();
+            }
+            (name, result, timings.summary())
+        });
+    }
+
+    let mut results = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        results.push(joined?);
+    }
+    results.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut succeeded = 0;
+    let mut failed = Vec::new();
+    for (name, mut result, timings) in results {
+        report_run(&name, &mut result, timings, output)?;
+        match result {
+            Ok(_) => succeeded += 1,
+            Err(e) => {
+                error!("❌ Failed to patch '{name}': {e:#}");
+                failed.push(name);
+            }
+        }
+    }
+
+    info!("📊 Patched {succeeded} crate(s), {} failed", failed.len());
+    if !failed.is_empty() {
+        return Err(anyhow!("Failed to patch: {}", failed.join(", ")));
+    }
+    Ok(())
+}
+
+/// 将所有版本依赖克隆到本地并设置补丁，完成后列出全部补丁供检查
+async fn convert_all(
+    options: &LpatchOptions,
+    exclude: &[String],
+    jobs: usize,
+    output: OutputFormat,
+) -> Result<()> {
+    let result = patch_all(
+        options,
+        AllFilter::VersionsOnly,
+        exclude,
+        jobs,
+        false,
+        false,
+        output,
+    )
+    .await;
+    if options.dry_run {
+        return result;
+    }
+
+    list_patches(output)?;
+    info!(
+        "📝 Review the patches above and the clones in '{}' before committing .cargo/config.toml",
+        options.dir
+    );
+    result
+}
+
+async fn analyze_dependencies(strict: bool, output: OutputFormat) -> Result<()> {
+    info!("🔍 Analyzing Cargo.toml dependencies...");
+
+    let cargo_toml = CargoToml::find_and_load().context("Failed to find and load Cargo.toml")?;
+
+    // 必需依赖按类型分组，可选依赖单独列出并注明启用它的 feature
+    // 每组内按依赖表排序，便于按 [dependencies]/[build-dependencies]/[dev-dependencies] 分段显示
+    let required = |deps: Vec<DependencyInfo>| {
+        let mut deps: Vec<_> = deps.into_iter().filter(|dep| !dep.optional).collect();
+        deps.sort_by_key(|dep| dep.section);
+        deps
+    };
+    let mut optional: Vec<_> = cargo_toml
+        .get_all_dependencies()
+        .into_iter()
+        .filter(|dep| dep.optional)
+        .map(|dep| OptionalDependency {
+            features: cargo_toml.features_enabling(&dep.name),
+            dependency: dep,
+        })
+        .collect();
+    optional.sort_by_key(|optional| optional.dependency.section);
+    let analysis = DependencyAnalysis {
+        version: required(cargo_toml.get_version_dependencies()),
+        git: required(cargo_toml.get_git_dependencies()),
+        path: required(cargo_toml.get_path_dependencies()),
+        optional,
+        replace: cargo_toml
+            .get_replacements()
+            .into_iter()
+            .map(|r| ReplacementEntry {
+                spec: r.spec,
+                name: r.target.name,
+            })
+            .collect(),
+    };
+
+    if analysis.total() == 0 && strict {
+        return Err(anyhow!("No dependencies found in Cargo.toml (--strict)"));
+    }
+
+    output.reporter().analysis(&analysis)
+}
+
+fn auto_pr_description(name: &str, dir: &str, base: Option<&str>, write: bool) -> Result<()> {
+    let clone_path = PathBuf::from(dir).join(name);
+    if !clone_path.exists() {
+        return Err(anyhow!(
+            "No local patch found for '{}' at '{}'",
+            name,
+            clone_path.display()
+        ));
+    }
+
+    let git_ops = GitOperations::new();
+    let base_oid = git_ops.resolve_base(&clone_path, base)?;
+    let commits = git_ops.get_log(&clone_path, Some(base_oid))?;
+    let stats = git_ops.diff_stats(&clone_path, base_oid)?;
+
+    let mut body = format!("## Changes to `{name}`\n\n");
+    if commits.is_empty() {
+        body.push_str("_No commits since the base revision._\n");
+    } else {
+        for commit in &commits {
+            body.push_str(&format!(
+                "- {} {} ({})\n",
+                &commit.id[..7],
+                commit.summary,
+                commit.author
+            ));
+        }
+    }
+    body.push_str(&format!(
+        "\n{} files changed, {} insertions(+), {} deletions(-)\n",
+        stats.files_changed, stats.insertions, stats.deletions
+    ));
+
+    println!("{body}");
+
+    if write {
+        fs::write("PR_BODY.md", &body).context("Failed to write PR_BODY.md")?;
+        info!("💾 Saved PR description to PR_BODY.md");
+    }
+
+    Ok(())
+}
+
+/// 构建补丁前后的 cdylib/staticlib 产物并比较导出符号
+///
+/// 原始版本从补丁克隆的基准提交导出到 `target/lpatch-compat/<crate>/original`，
+/// 有符号被移除时返回错误
+fn check_compat(name: &str, base: Option<&str>, output: OutputFormat) -> Result<()> {
+    let cargo_config = CargoConfig::load()?;
+    let (_, _, path) = cargo_config
+        .list_patches()
+        .into_iter()
+        .find(|(_, patch_name, _)| patch_name == name)
+        .ok_or_else(|| anyhow!("Crate '{}' is not patched", name))?;
+    let patch_path = CargoConfig::resolve_patch_path(&path);
+
+    let crate_types = compat::binary_crate_types(&patch_path)?;
+    if crate_types.is_empty() {
+        return Err(anyhow!(
+            "'{}' is not a cdylib or staticlib crate, nothing to compare",
+            name
+        ));
+    }
+
+    let git_ops = GitOperations::new();
+    let repo_root = git_ops.repo_root(&patch_path)?;
+    let base_oid = git_ops.resolve_base(&repo_root, base)?;
+    let subpath = fs::canonicalize(&patch_path)?
+        .strip_prefix(fs::canonicalize(&repo_root)?)?
+        .to_path_buf();
+
+    let work_dir = CargoConfig::project_dir()
+        .join("target")
+        .join("lpatch-compat")
+        .join(name);
+    let original_root = work_dir.join("original");
+    if original_root.exists() {
+        fs::remove_dir_all(&original_root)?;
+    }
+    fs::create_dir_all(&original_root)?;
+    git_ops.export_tree(&repo_root, base_oid, &original_root)?;
+
+    let original_path = if subpath.as_os_str().is_empty() {
+        original_root
+    } else {
+        original_root.join(&subpath)
+    };
+    let original = compat::build_artifacts(&original_path, &work_dir.join("target-original"))?;
+    let patched = compat::build_artifacts(&patch_path, &work_dir.join("target-patched"))?;
+
+    let mut report = compat::CompatReport {
+        crate_name: name.to_string(),
+        base: base_oid.to_string(),
+        artifacts: Vec::new(),
+    };
+    for kind in &crate_types {
+        let (Some(original_artifact), Some(patched_artifact)) =
+            (original.get(kind), patched.get(kind))
+        else {
+            warn!(
+                "⚠️  No {} artifact produced for both versions, skipping",
+                kind
+            );
+            continue;
+        };
+        let original_symbols = compat::exported_symbols(original_artifact, kind)?;
+        let patched_symbols = compat::exported_symbols(patched_artifact, kind)?;
+        report.artifacts.push(compat::diff_symbols(
+            kind,
+            &original_symbols,
+            &patched_symbols,
+        ));
+    }
+
+    output.reporter().compat(&report)?;
+
+    if !report.is_compatible() {
+        return Err(anyhow!(
+            "Patched '{}' no longer exports some symbols of the original",
+            name
+        ));
+    }
+    info!("✅ No exported symbols were removed");
+    Ok(())
+}
+
+/// 报告补丁版本相对已发布版本新增的传递依赖
+fn dependency_audit(name: &str, strict: bool, output: OutputFormat) -> Result<()> {
+    let cargo_config = CargoConfig::load()?;
+    let (source, _, path) = cargo_config
+        .list_patches()
+        .into_iter()
+        .find(|(_, patch_name, _)| patch_name == name)
+        .ok_or_else(|| anyhow!("Crate '{}' is not patched", name))?;
+
+    let report = audit::audit(name, &source, &CargoConfig::resolve_patch_path(&path))?;
+    output.reporter().dependency_audit(&report)?;
+
+    if strict && !report.added.is_empty() {
+        return Err(anyhow!(
+            "Patched '{}' introduces {} new dependencies",
+            name,
+            report.added.len()
+        ));
+    }
+    Ok(())
+}
+
+/// 将所有补丁打包进本地 registry，并在配置中声明 `[source.local-registry]`
+///
+/// 单个 crate 打包失败不影响其他 crate，有失败时返回错误
+fn build_local_registry(registry_dir: &Path, output: OutputFormat) -> Result<()> {
+    let mut cargo_config = CargoConfig::load_or_create()?;
+    let patches = cargo_config.list_patches();
+    if patches.is_empty() {
+        info!("📭 There are no active patches");
+        return Ok(());
+    }
+
+    let target_dir = CargoConfig::project_dir()
+        .join("target")
+        .join("lpatch-registry");
+    let mut crates = Vec::new();
+    for (_, name, path) in &patches {
+        info!("📦 Packaging '{name}'...");
+        let patch_path = CargoConfig::resolve_patch_path(path);
+        let entry = match local_registry::add_crate(registry_dir, &patch_path, &target_dir) {
+            Ok((version, checksum)) => local_registry::RegistryCrate {
+                name: name.clone(),
+                version: Some(version),
+                checksum: Some(checksum),
+                error: None,
+            },
+            Err(e) => {
+                warn!("⚠️  Failed to package '{name}': {e}");
+                local_registry::RegistryCrate {
+                    name: name.clone(),
+                    version: None,
+                    checksum: None,
+                    error: Some(e.to_string()),
+                }
+            }
+        };
+        crates.push(entry);
+    }
+
+    let failed = crates.iter().filter(|c| c.error.is_some()).count();
+    if failed < crates.len() {
+        cargo_config.set_local_registry(local_registry::SOURCE_NAME, registry_dir)?;
+        cargo_config.save()?;
+    }
+
+    output
+        .reporter()
+        .local_registry(&local_registry::LocalRegistryReport {
+            path: registry_dir.display().to_string(),
+            crates,
+        })?;
+    if failed > 0 {
+        return Err(anyhow!(
+            "{} of {} patches could not be packaged",
+            failed,
+            patches.len()
+        ));
+    }
+    Ok(())
+}
+
+/// 用 cargo-llvm-cov 测量补丁的测试覆盖率，并与 base 版本比较
+///
+/// base 无法确定时只报告补丁本身的覆盖率；base 版本的测试运行失败时不报告基线
+fn patch_coverage(name: &str, base: Option<&str>, output: OutputFormat) -> Result<()> {
+    let cargo_config = CargoConfig::load()?;
+    let (_, _, path) = cargo_config
+        .list_patches()
+        .into_iter()
+        .find(|(_, patch_name, _)| patch_name == name)
+        .ok_or_else(|| anyhow!("Crate '{}' is not patched", name))?;
+    let patch_path = CargoConfig::resolve_patch_path(&path);
+
+    let work_dir = CargoConfig::project_dir()
+        .join("target")
+        .join("lpatch-coverage")
+        .join(name);
+
+    info!("🧪 Running tests of '{name}' with coverage...");
+    let patched = coverage::run_llvm_cov(&patch_path, &work_dir.join("target-patched"))?;
+
+    let git_ops = GitOperations::new();
+    let repo_root = git_ops.repo_root(&patch_path)?;
+    let base_oid = match git_ops.resolve_base(&repo_root, base) {
+        Ok(oid) => Some(oid),
+        // 显式指定的 base 解析失败应直接报错
+        Err(e) if base.is_some() => return Err(e),
+        Err(e) => {
+            warn!("⚠️  {e}; reporting coverage without a baseline");
+            None
+        }
+    };
+
+    let mut report = coverage::CoverageReport {
+        crate_name: name.to_string(),
+        base: base_oid.map(|oid| oid.to_string()),
+        patched: patched.summary.clone(),
+        baseline: None,
+        uncovered: Vec::new(),
+    };
+
+    if let Some(base_oid) = base_oid {
+        let added = git_ops.added_lines(&repo_root, base_oid)?;
+        report.uncovered = coverage::uncovered_added_lines(&patched, &repo_root, &added);
+
+        let subpath = fs::canonicalize(&patch_path)?
+            .strip_prefix(fs::canonicalize(&repo_root)?)?
+            .to_path_buf();
+        let original_root = work_dir.join("original");
+        if original_root.exists() {
+            fs::remove_dir_all(&original_root)?;
+        }
+        fs::create_dir_all(&original_root)?;
+        git_ops.export_tree(&repo_root, base_oid, &original_root)?;
+
+        info!("🧪 Running tests of the base revision with coverage...");
+        match coverage::run_llvm_cov(
+            &original_root.join(subpath),
+            &work_dir.join("target-original"),
+        ) {
+            Ok(original) => report.baseline = Some(original.summary),
+            Err(e) => warn!("⚠️  Could not measure baseline coverage: {e}"),
+        }
+    }
+
+    output.reporter().coverage(&report)
+}
+
+/// 递增补丁本地副本中的版本号，便于发布修改后的版本
+fn version_bump(name: &str, bump: bump::Bump, output: OutputFormat) -> Result<()> {
+    let cargo_config = CargoConfig::load()?;
+    let (_, _, path) = cargo_config
+        .list_patches()
+        .into_iter()
+        .find(|(_, patch_name, _)| patch_name == name)
+        .ok_or_else(|| anyhow!("Crate '{}' is not patched", name))?;
+
+    let manifest = CargoConfig::resolve_patch_path(&path).join("Cargo.toml");
+    let report = bump::bump_manifest(&manifest, name, bump)?;
+    info!("🔖 Bumped version in {}", manifest.display());
+    output.reporter().version_bump(&report)
+}
+
+/// 分别在启用和临时移除补丁时构建项目，比较构建过程的峰值内存
+fn profile_memory(name: &str, platform: profile::Platform, output: OutputFormat) -> Result<()> {
+    let mut cargo_config = CargoConfig::load()?;
+    if !cargo_config
+        .list_patches()
+        .iter()
+        .any(|(_, patch_name, _)| patch_name == name)
+    {
+        return Err(anyhow!("Crate '{}' is not patched", name));
+    }
+
+    let project_dir = CargoConfig::project_dir();
+    // 每次都从干净的目标目录完整构建，避免增量编译影响比较
+    let work_dir = project_dir.join("target").join("lpatch-profile");
+    if work_dir.exists() {
+        fs::remove_dir_all(&work_dir)?;
+    }
+    info!("📏 Building with the patch for '{name}'...");
+    let patched = profile::peak_build_rss(&project_dir, &work_dir.join("patched"), platform)?;
+
+    // 临时移除补丁，构建结束后原样恢复配置和 Cargo.lock
+    let config_path = CargoConfig::get_config_path();
+    let lock_path = project_dir.join("Cargo.lock");
+    let config_content = fs::read_to_string(&config_path)?;
+    let lock_content = fs::read_to_string(&lock_path).ok();
+    cargo_config.remove_patch(name);
+    cargo_config.save()?;
+
+    info!("📏 Building without the patch for '{name}'...");
+    let original = profile::peak_build_rss(&project_dir, &work_dir.join("original"), platform);
+
+    fs::write(&config_path, config_content)
+        .with_context(|| format!("Failed to restore {}", config_path.display()))?;
+    match lock_content {
+        Some(content) => fs::write(&lock_path, content)
+            .with_context(|| format!("Failed to restore {}", lock_path.display()))?,
+        None if lock_path.exists() => fs::remove_file(&lock_path)?,
+        None => {}
+    }
+    info!("↩️  Restored the patch for '{name}'");
+
+    let report = profile::MemoryReport::new(name, patched, original?);
+    output.reporter().memory_profile(&report)
+}
+
+/// 根据依赖的 rustdoc JSON 生成桩 crate，并将其设为补丁
+fn create_mock(name: &str, dir: &str, toolchain: &str, output: OutputFormat) -> Result<()> {
+    let mock_dir = PathBuf::from(dir).join(name);
+    if mock_dir.exists() {
+        return Err(anyhow!(
+            "'{}' already exists, remove it first to regenerate the mock",
+            mock_dir.display()
+        ));
+    }
+
+    let project_dir = CargoConfig::project_dir();
+    let target_dir = project_dir.join("target").join("lpatch-mock");
+    let json_path = mock::generate_rustdoc_json(&project_dir, name, toolchain, &target_dir)?;
+    let mock_crate = mock::render_mock(&json_path)?;
+    mock::write_mock_crate(&mock_dir, name, &mock_crate)?;
+    info!(
+        "🧪 Created mock crate {} v{} at {}",
+        name,
+        mock_crate.version,
+        mock_dir.display()
+    );
+    for item in &mock_crate.skipped {
+        warn!("⚠️  Not mocked: {item}");
+    }
+
+    // 与克隆时相同的规则选择 patch 源，不在 Cargo.toml 中的依赖按 crates.io 处理
+    let patch_source = CargoToml::find_and_load()
+        .ok()
+        .and_then(|cargo_toml| lookup_dependency(&cargo_toml, name, None))
+        .and_then(|dep| dep.dep_type.patch_source())
+        .unwrap_or_else(|| cargo_toml::patch_source(None, None));
+
+    let mut cargo_config = CargoConfig::load_or_create()?;
+    cargo_config.add_patch_with_source(name, &mock_dir, &patch_source)?;
+    cargo_config.save()?;
+
+    output.reporter().mock_crate(&MockReport {
+        name: name.to_string(),
+        version: mock_crate.version,
+        path: mock_dir.display().to_string(),
+        source: patch_source,
+        skipped: mock_crate.skipped,
+    })
+}
+
+/// 列出 `.cargo/config.toml` 中的所有补丁
+fn list_patches(output: OutputFormat) -> Result<()> {
+    let cargo_config = CargoConfig::load()?;
+    let patches: Vec<_> = cargo_config
+        .list_patches()
+        .into_iter()
+        .map(|(source, name, path)| PatchEntry {
+            path_exists: CargoConfig::resolve_patch_path(&path).exists(),
+            source,
+            name,
+            path,
+        })
+        .collect();
+
+    output.reporter().patches(&patches)
+}
+
+/// 补丁所在克隆的根目录
+///
+/// 从补丁路径向上查找含有 `.git` 的目录，但不越过项目目录，
+/// 以免把宿主项目的仓库当作补丁的克隆
+fn clone_root(patch_path: &Path) -> Option<PathBuf> {
+    let project_dir = fs::canonicalize(CargoConfig::project_dir()).ok()?;
+    let patch_path = fs::canonicalize(patch_path).ok()?;
+    patch_path
+        .ancestors()
+        .take_while(|dir| !project_dir.starts_with(dir))
+        .find(|dir| dir.join(".git").exists())
+        .map(Path::to_path_buf)
+}
+
+/// 按 crate 显示每个补丁仓库中被修改的文件，类似 `git status --short`
+fn show_status(output: OutputFormat) -> Result<()> {
+    let cargo_config = CargoConfig::load()?;
+    let patches = cargo_config.list_patches();
+    if patches.is_empty() {
+        info!(
+            "📭 No active patches in {}",
+            CargoConfig::get_config_path().display()
+        );
+        return Ok(());
+    }
+
+    let git_ops = GitOperations::new();
+    let mut entries = Vec::new();
+    for (_, name, path) in patches {
+        let patch_path = CargoConfig::resolve_patch_path(&path);
+        let path_exists = patch_path.exists();
+        let problem = patch_problem(&name, &patch_path);
+        // create-mock 生成的补丁等不在 git 仓库中，此时没有文件状态可显示
+        let files = match clone_root(&patch_path) {
+            Some(root) => git_ops.file_statuses(&root).unwrap_or_default(),
+            None => Vec::new(),
+        };
+        entries.push(StatusEntry {
+            name,
+            path,
+            path_exists,
+            problem,
+            files,
+        });
+    }
+
+    output.reporter().status(&entries)?;
+
+    let broken = entries.iter().filter(|e| e.problem.is_some()).count();
+    if broken > 0 {
+        return Err(anyhow!(
+            "{} of {} patches are broken",
+            broken,
+            entries.len()
+        ));
+    }
+    Ok(())
+}
+
+/// 检查补丁路径是否指向名称匹配的 crate，返回失效原因
+fn patch_problem(name: &str, patch_path: &Path) -> Option<String> {
+    if !patch_path.exists() {
+        return Some("path does not exist".to_string());
+    }
+    if !patch_path.join("Cargo.toml").exists() {
+        return Some("no Cargo.toml in patch path".to_string());
+    }
+    match WorkspaceDetector::get_crate_name(patch_path) {
+        Ok(package) if package == name => None,
+        Ok(package) => Some(format!("Cargo.toml declares package '{package}'")),
+        Err(e) => Some(format!("{e:#}")),
+    }
+}
+
+/// 诊断补丁配置，发现错误时以非零状态退出
+fn run_doctor(output: OutputFormat) -> Result<()> {
+    let issues = match CargoConfig::load() {
+        Ok(cargo_config) => doctor::diagnose(&cargo_config),
+        Err(e) => vec![doctor::DiagnosticIssue {
+            severity: doctor::Severity::Error,
+            message: format!("{e:#}"),
+            suggestion: format!(
+                "Fix the TOML syntax in {}",
+                CargoConfig::get_config_path().display()
+            ),
+        }],
+    };
+
+    output.reporter().diagnostics(&issues)?;
+
+    let errors = issues
+        .iter()
+        .filter(|issue| issue.severity == doctor::Severity::Error)
+        .count();
+    if errors > 0 {
+        return Err(anyhow!("Found {} error(s) in the patch setup", errors));
+    }
+    Ok(())
+}
+
+/// 以 unified diff 格式显示补丁仓库中未提交的修改
+///
+/// 标准输出是终端且设置了 `PAGER` 时通过分页器显示
+fn show_diff(name: Option<&str>, output: OutputFormat) -> Result<()> {
+    let cargo_config = CargoConfig::load()?;
+    let patches: Vec<_> = cargo_config
+        .list_patches()
+        .into_iter()
+        .filter(|(_, patch_name, _)| name.is_none_or(|name| name == patch_name))
+        .collect();
+    if let (Some(name), true) = (name, patches.is_empty()) {
+        return Err(anyhow!("Crate '{}' is not patched", name));
+    }
+
+    let git_ops = GitOperations::new();
+    let mut shown_repos = Vec::new();
+    let mut diffs = Vec::new();
+    for (_, patch_name, path) in &patches {
+        let patch_path = CargoConfig::resolve_patch_path(path);
+        if !patch_path.exists() {
+            warn!("⚠️  Skipping '{patch_name}': {path} does not exist");
+            continue;
+        }
+
+        // 同一仓库中的多个 crate 只显示一次
+        let repo_root = git_ops.repo_root(&patch_path)?;
+        if shown_repos.contains(&repo_root) {
+            continue;
+        }
+        shown_repos.push(repo_root.clone());
+
+        let diff = git_ops.workdir_diff(&repo_root)?;
+        if !diff.is_empty() {
+            diffs.push(PatchDiff {
+                name: patch_name.clone(),
+                repository: repo_root.display().to_string(),
+                diff,
+            });
+        }
+    }
+
+    output.reporter().diffs(&diffs)
+}
+
+/// 拉取补丁克隆的上游更新，并在可能时快进本地分支
+///
+/// 每次快进都会追加到补丁的更新记录中（见 [`provenance::record_update`]）
+fn update_patches(name: Option<&str>, changelog: bool, output: OutputFormat) -> Result<()> {
+    let cargo_config = CargoConfig::load()?;
+    let patches: Vec<_> = cargo_config
+        .list_patches()
+        .into_iter()
+        .filter(|(_, patch_name, _)| name.is_none_or(|name| name == patch_name))
+        .collect();
+
+    if patches.is_empty() {
+        if let Some(name) = name {
+            return Err(anyhow!("Crate '{}' is not patched", name));
+        }
+        info!(
+            "📭 No active patches in {}",
+            CargoConfig::get_config_path().display()
+        );
+    }
+
+    let report = UpdateReport {
+        changelog,
+        ..update_clones(&patches)?
+    };
+    output.reporter().updates(&report)?;
+    if report.failed > 0 {
+        return Err(anyhow!("{} patch(es) failed to update", report.failed));
+    }
+    Ok(())
+}
+
+/// 将 cargo metadata 中的包来源转换为对应的 patch 源
+///
+/// crates.io 的 index 对应 `crates-io`，git 来源去掉查询参数和提交哈希，其他 registry 为 index 地址
+fn patch_source_of(package_source: &str) -> String {
+    match package_source.split_once('+') {
+        Some(("registry" | "sparse", index))
+            if index.contains("github.com/rust-lang/crates.io-index")
+                || index.contains("index.crates.io") =>
+        {
+            "crates-io".to_string()
+        }
+        Some(("git", url)) => url.split(['?', '#']).next().unwrap_or(url).to_string(),
+        Some(("sparse", _)) => package_source.to_string(),
+        Some((_, index)) => index.to_string(),
+        None => package_source.to_string(),
+    }
+}
+
+/// 说明补丁所在的 `[patch.*]` 源与依赖实际来源是否一致，并用 cargo metadata 确认补丁是否生效
+fn show_patch_source(name: &str, output: OutputFormat) -> Result<()> {
+    let patches: Vec<(String, String)> = CargoConfig::load()?
+        .list_patches()
+        .into_iter()
+        .filter(|(_, patch_name, _)| patch_name == name)
+        .map(|(source, _, path)| (source, path))
+        .collect();
+    if patches.is_empty() {
+        return Err(anyhow!("Crate '{}' is not patched", name));
+    }
+
+    let metadata = match metadata::CargoMetadata::load(&CargoConfig::project_dir()) {
+        Ok(metadata) => Some(metadata),
+        Err(e) => {
+            warn!("⚠️  Could not run cargo metadata: {e}");
+            None
+        }
+    };
+    let packages: Vec<&metadata::MetadataPackage> = metadata
+        .iter()
+        .flat_map(|metadata| &metadata.packages)
+        .filter(|package| package.name == name)
+        .collect();
+
+    // 与打补丁时相同的规则：git URL > registry 名称 > crates-io
+    let dependency = CargoToml::find_and_load()
+        .ok()
+        .and_then(|cargo_toml| lookup_dependency(&cargo_toml, name, None));
+    let (expected_source, reason) = match dependency.map(|dep| dep.dep_type) {
+        Some(DependencyType::Git { git, .. }) => {
+            let reason = format!(
+                "'{name}' is a git dependency on {git} in Cargo.toml, so Cargo only applies a patch keyed by that URL; [patch.crates-io] is ignored"
+            );
+            (Some(git), reason)
+        }
+        Some(DependencyType::Version {
+            registry: Some(registry),
+            ..
+        }) => {
+            let reason = format!(
+                "'{name}' comes from registry '{registry}' in Cargo.toml, so the patch must be keyed by that registry"
+            );
+            (Some(registry), reason)
+        }
+        Some(DependencyType::Version { registry: None, .. }) => (
+            Some("crates-io".to_string()),
+            format!("'{name}' is a crates.io version dependency in Cargo.toml, so the patch must be under [patch.crates-io]"),
+        ),
+        Some(DependencyType::Path { path }) => (
+            None,
+            format!("'{name}' is a path dependency on '{path}' in Cargo.toml and needs no patch"),
+        ),
+        None => match packages.iter().find_map(|package| package.source.as_deref()) {
+            Some(source) => (
+                Some(patch_source_of(source)),
+                format!("'{name}' is not a direct dependency; cargo metadata shows it comes from {source}"),
+            ),
+            None => (
+                None,
+                format!("'{name}' is neither in Cargo.toml nor in the resolved dependency graph"),
+            ),
+        },
+    };
+
+    let same_source = |patch_source: &str| {
+        expected_source.as_deref().is_some_and(|expected| {
+            dedup::normalize_url(patch_source) == dedup::normalize_url(expected)
+                || (expected == "crates-io" && patch_source_of(patch_source) == "crates-io")
+        })
+    };
+    let entries: Vec<PatchSourceEntry> = patches
+        .iter()
+        .map(|(source, path)| PatchSourceEntry {
+            source: source.clone(),
+            path: path.clone(),
+            matches: same_source(source),
+        })
+        .collect();
+
+    // 补丁生效时该 crate 在 metadata 中没有 source，清单位于补丁目录下
+    let patch_dirs: Vec<PathBuf> = patches
+        .iter()
+        .filter_map(|(_, path)| fs::canonicalize(CargoConfig::resolve_patch_path(path)).ok())
+        .collect();
+    let applied = metadata.as_ref().map(|_| {
+        packages.iter().any(|package| {
+            package.source.is_none()
+                && package.manifest_path.as_ref().is_some_and(|manifest| {
+                    let manifest = fs::canonicalize(manifest).unwrap_or(manifest.clone());
+                    patch_dirs.iter().any(|dir| manifest.starts_with(dir))
+                })
+        })
+    });
+    let resolved = packages
+        .iter()
+        .map(|package| match (&package.source, &package.manifest_path) {
+            (Some(source), _) => format!("{} v{} from {}", package.name, package.version, source),
+            (None, Some(manifest)) => format!(
+                "{} v{} at {}",
+                package.name,
+                package.version,
+                manifest.parent().unwrap_or(manifest).display()
+            ),
+            (None, None) => format!("{} v{}", package.name, package.version),
+        })
+        .collect();
+
+    output.reporter().patch_source(&PatchSourceReport {
+        name: name.to_string(),
+        patches: entries,
+        expected_source,
+        reason,
+        applied,
+        resolved,
+    })
+}
+
+/// 只获取远程更新并统计每个补丁落后上游的提交数，不修改工作区
+fn check_updates(output: OutputFormat) -> Result<()> {
+    sync_patches(false, output)
+}
+
+/// 获取所有补丁仓库的上游更新并报告领先/落后的提交数
+///
+/// `merge` 时快进只落后于上游、没有本地提交的仓库（工作区有修改时不快进）
+fn sync_patches(merge: bool, output: OutputFormat) -> Result<()> {
+    let cargo_config = CargoConfig::load()?;
+    let patches = cargo_config.list_patches();
+    if patches.is_empty() {
+        info!(
+            "📭 No active patches in {}",
+            CargoConfig::get_config_path().display()
+        );
+        return Ok(());
+    }
+
+    let git_ops = GitOperations::new();
+    // 同一仓库中的多个 crate 只需要获取一次
+    let mut fetched: HashMap<PathBuf, Result<(), String>> = HashMap::new();
+    let mut checks = Vec::new();
+    for (_, name, path) in patches {
+        let patch_path = CargoConfig::resolve_patch_path(&path);
+        let mut check = UpdateCheck {
+            name,
+            path,
+            ahead: None,
+            behind: None,
+            error: None,
+        };
+
+        let root = git_ops.repo_root(&patch_path);
+        let result = root
+            .as_ref()
+            .map_err(|e| anyhow!("{e:#}"))
+            .and_then(|root| {
+                let fetch = fetched.entry(root.clone()).or_insert_with(|| {
+                    info!("🔄 Fetching {}...", root.display());
+                    git_ops.fetch(root).map_err(|e| format!("{e:#}"))
+                });
+                fetch.clone().map_err(|e| anyhow!(e))?;
+                git_ops.ahead_behind_upstream(root)
+            });
+        match result {
+            Ok(Some((ahead, behind))) => {
+                check.ahead = Some(ahead);
+                check.behind = Some(behind);
+            }
+            Ok(None) => {}
+            Err(e) => check.error = Some(format!("{e:#}")),
+        }
+        checks.push((root.ok(), check));
+    }
+
+    if merge {
+        // 仓库 -> 快进结果，同一仓库中的多个 crate 只快进一次
+        let mut merged: HashMap<PathBuf, bool> = HashMap::new();
+        for (root, check) in &mut checks {
+            let Some(root) = root else {
+                continue;
+            };
+            if check.ahead != Some(0) || matches!(check.behind, None | Some(0)) {
+                continue;
+            }
+            let fast_forwarded = *merged.entry(root.clone()).or_insert_with(|| {
+                match git_ops.fast_forward_upstream(root) {
+                    Ok(FastForwardOutcome::FastForwarded { .. }) => {
+                        info!("⏩ Fast-forwarded {}", root.display());
+                        true
+                    }
+                    Ok(FastForwardOutcome::LocalChanges) => {
+                        warn!(
+                            "⚠️  {} has uncommitted changes, not fast-forwarding",
+                            root.display()
+                        );
+                        false
+                    }
+                    Ok(_) => false,
+                    Err(e) => {
+                        warn!("⚠️  Failed to fast-forward {}: {e:#}", root.display());
+                        false
+                    }
+                }
+            });
+            if fast_forwarded {
+                check.behind = Some(0);
+            }
+        }
+    }
+
+    let checks: Vec<UpdateCheck> = checks.into_iter().map(|(_, check)| check).collect();
+    output.reporter().update_checks(&checks)
+}
+
+/// 更新所有没有本地修改的补丁克隆，适合由 cron 等定时任务调用
+fn auto_update(output: OutputFormat) -> Result<()> {
+    let cargo_config = CargoConfig::load()?;
+    let patches = cargo_config.list_patches();
+    if patches.is_empty() {
+        info!(
+            "📭 No active patches in {}",
+            CargoConfig::get_config_path().display()
+        );
+    }
+
+    let report = update_clones(&patches)?;
+    output.reporter().updates(&report)?;
+    if report.failed > 0 {
+        return Err(anyhow!("{} patch(es) failed to update", report.failed));
+    }
+    Ok(())
+}
+
+/// 逐个拉取补丁所在仓库，跳过有未提交修改的工作区
+fn update_clones(patches: &[(String, String, String)]) -> Result<UpdateReport> {
+    let git_ops = GitOperations::new();
+    let mut updated_repos = Vec::new();
+    let mut summary = UpdateReport::default();
+    for (_, patch_name, path) in patches {
+        let patch_path = CargoConfig::resolve_patch_path(path);
+        if !patch_path.exists() {
+            warn!("⚠️  Skipping '{patch_name}': {path} does not exist");
+            summary.skipped += 1;
+            continue;
+        }
+
+        // 同一仓库中的多个 crate 只需要更新一次
+        let repo_root = match git_ops.repo_root(&patch_path) {
+            Ok(root) => root,
+            Err(e) => {
+                warn!("⚠️  Skipping '{patch_name}': {e}");
+                summary.skipped += 1;
+                continue;
+            }
+        };
+        if updated_repos.contains(&repo_root) {
+            continue;
+        }
+        updated_repos.push(repo_root.clone());
+
+        if git_ops.is_dirty(&repo_root)? {
+            warn!(
+                "⚠️  Skipping '{patch_name}': {} has uncommitted changes",
+                repo_root.display()
+            );
+            summary.dirty += 1;
+            continue;
+        }
+
+        info!("🔄 Updating '{patch_name}'...");
+        match git_ops.pull(&repo_root) {
+            Ok(FastForwardOutcome::UpToDate) => info!("✅ '{patch_name}' is up to date"),
+            Ok(FastForwardOutcome::FastForwarded { from, to }) => {
+                let commits = git_ops.commits_between(&repo_root, from, to)?;
+                info!(
+                    "✅ '{patch_name}' fast-forwarded by {} commit(s):",
+                    commits.len()
+                );
+                for commit in &commits {
+                    info!("  {} {}", &commit.id[..7], commit.summary);
+                }
+                let record = provenance::record_update(patch_name, from, to, &commits)?;
+                debug!("Recorded update in {}", record.display());
+                summary.updates.push(PatchUpdate {
+                    name: patch_name.clone(),
+                    from: from.to_string(),
+                    to: to.to_string(),
+                    commits,
+                });
+                summary.updated += 1;
+            }
+            // pull 已经说明了原因
+            Ok(FastForwardOutcome::LocalChanges) => summary.dirty += 1,
+            Ok(FastForwardOutcome::Diverged) => summary.skipped += 1,
+            Ok(FastForwardOutcome::NoUpstream) => {}
+            Err(e) => {
+                error!("❌ Failed to update '{patch_name}': {e}");
+                summary.failed += 1;
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+/// 在补丁克隆中安装 git 钩子，`name` 为 None 时处理所有补丁
+fn init_hooks(name: Option<&str>, hook: &str, output: OutputFormat) -> Result<()> {
+    let kind = hooks::HookKind::parse(hook)?;
+    let cargo_config = CargoConfig::load()?;
+    let patches: Vec<_> = cargo_config
+        .list_patches()
+        .into_iter()
+        .filter(|(_, patch_name, _)| name.is_none_or(|name| name == patch_name))
+        .collect();
+
+    if patches.is_empty() {
+        if let Some(name) = name {
+            return Err(anyhow!("Crate '{}' is not patched", name));
+        }
+        info!(
+            "📭 No active patches in {}",
+            CargoConfig::get_config_path().display()
+        );
+    }
+
+    let mut report = HooksReport {
+        hook: hook.to_string(),
+        installed: Vec::new(),
+        failed: Vec::new(),
+    };
+    let mut installed = Vec::new();
+    for (_, patch_name, path) in &patches {
+        let result = hooks::install_hook(&CargoConfig::resolve_patch_path(path), kind);
+        match result {
+            // 同一仓库中的多个 crate 共用钩子，只报告一次
+            Ok(hook_path) if installed.contains(&hook_path) => {}
+            Ok(hook_path) => {
+                report.installed.push(HookInstall {
+                    name: patch_name.clone(),
+                    path: hook_path.display().to_string(),
+                });
+                installed.push(hook_path);
+            }
+            Err(e) => report.failed.push(HookFailure {
+                name: patch_name.clone(),
+                error: e.to_string(),
+            }),
+        }
+    }
+
+    output.reporter().hooks(&report)?;
+    if !report.failed.is_empty() {
+        return Err(anyhow!("{} hook(s) failed to install", report.failed.len()));
+    }
+    Ok(())
+}
+
+/// 将指向同一仓库的多个克隆合并为一个，并改写相关的 patch 路径
+fn dedup_clones(dry_run: bool, output: OutputFormat) -> Result<()> {
+    let mut cargo_config = CargoConfig::load()?;
+    let groups = dedup::plan(&cargo_config)?;
+
+    let mut removable = Vec::new();
+    for group in &groups {
+        info!("📦 {} (keeping {})", group.url, group.keeper.display());
+        for duplicate in &group.duplicates {
+            if let Some(reason) = &duplicate.blocked {
+                warn!("  ⏭️  {}: skipped, {reason}", duplicate.clone.display());
+                continue;
+            }
+
+            info!("  🔀 {} will be merged", duplicate.clone.display());
+            for (source, name, new_path) in &duplicate.patches {
+                info!("    {name} ({source}) -> {}", new_path.display());
+                if !dry_run {
+                    cargo_config.add_patch_with_source(name, new_path, source)?;
+                }
+            }
+            removable.push(duplicate.clone.clone());
+        }
+    }
+
+    let mut removed = Vec::new();
+    if !dry_run && !removable.is_empty() {
+        // 先保存配置，确保删除克隆后没有 patch 指向不存在的路径
+        cargo_config.save()?;
+        for clone in removable {
+            fs::remove_dir_all(&clone)
+                .with_context(|| format!("Failed to remove '{}'", clone.display()))?;
+            removed.push(clone.display().to_string());
+        }
+    }
+
+    output.reporter().dedup(&DedupReport {
+        dry_run,
+        groups,
+        removed,
+    })
+}
+
+fn show_patch_tree(depth: usize, output: OutputFormat) -> Result<()> {
+    let cargo_config = CargoConfig::load()?;
+    if cargo_config.list_patches().is_empty() {
+        info!(
+            "📭 No active patches in {}",
+            CargoConfig::get_config_path().display()
+        );
+    }
+
+    output
+        .reporter()
+        .patch_tree(&tree::build_patch_tree(&cargo_config, depth))
+}
+
+/// 删除指定 crate 的本地补丁
+fn unpatch(name: &str, dir: &str, remove_clone: bool, output: OutputFormat) -> Result<()> {
+    let mut cargo_config = CargoConfig::load()?;
+
+    let sources = cargo_config.remove_patch(name);
+    if sources.is_empty() {
+        let patches = cargo_config.list_patches();
+        if patches.is_empty() {
+            info!("📭 There are no active patches");
+        } else {
+            info!("📋 Current patches:");
+            for (source, name, path) in &patches {
+                info!("  📦 {name} -> {path} ({source})");
+            }
+        }
+        return Err(anyhow!(
+            "Crate '{}' is not patched in {}",
+            name,
+            CargoConfig::get_config_path().display()
+        ));
+    }
+
+    cargo_config.save()?;
+
+    let mut deleted_clone = None;
+    if remove_clone {
+        let clone_path = PathBuf::from(dir).join(name);
+        if !clone_path.exists() {
+            warn!(
+                "⚠️  Clone directory '{}' does not exist",
+                clone_path.display()
+            );
+        } else if GitOperations::new().is_dirty(&clone_path).unwrap_or(false) {
+            warn!(
+                "⚠️  '{}' has uncommitted changes, not deleting it",
+                clone_path.display()
+            );
+        } else {
+            fs::remove_dir_all(&clone_path)
+                .with_context(|| format!("Failed to delete '{}'", clone_path.display()))?;
+            deleted_clone = Some(clone_path.display().to_string());
+        }
+    }
+
+    output.reporter().unpatched(&UnpatchReport {
+        name: name.to_string(),
+        sources,
+        deleted_clone,
+    })
+}
+
+/// 输出补全脚本，`--name` 的候选值取自当前 Cargo.toml 中的依赖
+fn generate_completions(shell: clap_complete::Shell) -> Result<()> {
+    let mut names: Vec<String> = CargoToml::find_and_load()
+        .map(|cargo_toml| {
+            cargo_toml
+                .get_all_dependencies()
+                .into_iter()
+                .map(|dep| dep.name)
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    names.dedup();
+
+    let mut cli = build_cli();
+    if !names.is_empty() {
+        cli = cli.mut_subcommand("lpatch", |lpatch| with_name_hints(lpatch, &names));
+    }
+    clap_complete::generate(shell, &mut cli, "cargo-lpatch", &mut std::io::stdout());
+    Ok(())
+}
+
+/// 为命令及其子命令的 `--name` 参数加上候选值，只用于生成补全脚本
+fn with_name_hints(mut command: Command, names: &[String]) -> Command {
+    if command.get_arguments().any(|arg| arg.get_id() == "name") {
+        let names = names.to_vec();
+        command = command.mut_arg("name", |arg| {
+            arg.value_parser(clap::builder::PossibleValuesParser::new(names))
+        });
+    }
+    let subcommands: Vec<String> = command
+        .get_subcommands()
+        .map(|sub| sub.get_name().to_string())
+        .collect();
+    for sub in subcommands {
+        command = command.mut_subcommand(sub, |sub| with_name_hints(sub, names));
+    }
+    command
+}
+
+/// 创建克隆目录、`.cargo/config.toml` 模板以及可选的 `.gitignore` 条目
+fn init_project(dir: &str, gitignore: bool, output: OutputFormat) -> Result<()> {
+    let project_dir = CargoConfig::project_dir();
+    let clone_dir = project_dir.join(dir);
+    let config_path = CargoConfig::get_config_path();
+    let gitignore_path = project_dir.join(".gitignore");
+    let ignore_entry = format!("/{}/", dir.trim_start_matches("./").trim_matches('/'));
+    let ignored = fs::read_to_string(&gitignore_path).is_ok_and(|content| {
+        content
+            .lines()
+            .any(|line| line.trim().trim_matches('/') == ignore_entry.trim_matches('/'))
+    });
+
+    let mut report = InitReport {
+        project_dir: project_dir.display().to_string(),
+        dir: dir.to_string(),
+        created: Vec::new(),
+        gitignore_entry: None,
+    };
+
+    if !clone_dir.is_dir() {
+        fs::create_dir_all(&clone_dir)
+            .with_context(|| format!("Failed to create directory {}", clone_dir.display()))?;
+        report.created.push(clone_dir.display().to_string());
+    }
+
+    if !config_path.exists() {
+        CargoConfig::create_new()?;
+        fs::write(&config_path, CargoConfig::template(dir))
+            .with_context(|| format!("Failed to write config file: {}", config_path.display()))?;
+        report.created.push(config_path.display().to_string());
+    }
+
+    if gitignore && !ignored {
+        let mut content = fs::read_to_string(&gitignore_path).unwrap_or_default();
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push('\n');
+        }
+        content.push_str(&ignore_entry);
+        content.push('\n');
+        fs::write(&gitignore_path, content)
+            .with_context(|| format!("Failed to write {}", gitignore_path.display()))?;
+        report.gitignore_entry = Some(ignore_entry);
+    }
+
+    output.reporter().init(&report)
+}
+
+/// 移除所有补丁，克隆目录默认保留（其中可能有未提交的修改）
+fn reset_patches(dir: &str, and_delete: bool, output: OutputFormat) -> Result<()> {
+    let mut cargo_config = CargoConfig::load()?;
+    let patches = cargo_config.list_patches();
+
+    let mut report = ResetReport {
+        removed: cargo_config.clear_all_patches(),
+        ..Default::default()
+    };
+    if report.removed == 0 || !and_delete {
+        if report.removed > 0 {
+            cargo_config.save()?;
+        }
+        return output.reporter().reset(&report);
+    }
+    cargo_config.save()?;
+
+    // 只删除位于 --dir 下的克隆，克隆目录是 --dir 下的第一级子目录
+    let clone_dir = PathBuf::from(dir);
+    let clone_dir = fs::canonicalize(&clone_dir).unwrap_or(clone_dir);
+    let mut clones: Vec<PathBuf> = patches
+        .iter()
+        .filter_map(|(_, _, path)| {
+            let patch_path = fs::canonicalize(CargoConfig::resolve_patch_path(path)).ok()?;
+            let first = patch_path
+                .strip_prefix(&clone_dir)
+                .ok()?
+                .components()
+                .next()?;
+            Some(clone_dir.join(first))
+        })
+        .collect();
+    clones.sort();
+    clones.dedup();
+
+    let git_ops = GitOperations::new();
+    for clone_path in &clones {
+        if git_ops.is_dirty(clone_path).unwrap_or(false) {
+            report.kept.push(clone_path.display().to_string());
+            continue;
+        }
+        fs::remove_dir_all(clone_path)
+            .with_context(|| format!("Failed to delete '{}'", clone_path.display()))?;
+        report.deleted.push(clone_path.display().to_string());
+    }
+
+    output.reporter().reset(&report)
+}
+
+/// 删除 --dir 下不再被任何补丁引用的克隆（例如手动编辑配置删掉了补丁条目）
+fn clean_stale(dir: &str, all: bool, output: OutputFormat) -> Result<()> {
+    let clone_dir = PathBuf::from(dir);
+    let mut report = CleanStaleReport {
+        clone_dir: clone_dir.display().to_string(),
+        clone_dir_exists: clone_dir.is_dir(),
+        stale: Vec::new(),
+        deleted: Vec::new(),
+    };
+    if !report.clone_dir_exists {
+        return output.reporter().clean_stale(&report);
+    }
 
-    if let Some(lpatch_matches) = matches.subcommand_matches("lpatch") {
-        let name = lpatch_matches.get_one::<String>("name");
-        let dir = lpatch_matches.get_one::<String>("dir").unwrap();
-        let analyze = lpatch_matches.get_flag("analyze");
+    // 比较规范化后的路径，通过 link 创建的符号链接指向的外部仓库也算被引用
+    let patch_paths: Vec<PathBuf> = CargoConfig::load()?
+        .list_patches()
+        .iter()
+        .filter_map(|(_, _, path)| fs::canonicalize(CargoConfig::resolve_patch_path(path)).ok())
+        .collect();
 
-        if analyze {
-            analyze_dependencies().await?;
-        } else if let Some(name) = name {
-            run_lpatch(name, dir).await?;
-        } else {
-            // 如果没有提供 name 且没有 analyze，显示帮助
-            error!("Either --name or --analyze must be specified.");
-            error!("Use --help for more information.");
-            std::process::exit(1);
+    let mut stale: Vec<PathBuf> = fs::read_dir(&clone_dir)
+        .with_context(|| format!("Failed to read '{}'", clone_dir.display()))?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let Ok(target) = fs::canonicalize(path) else {
+                return false;
+            };
+            target.is_dir() && !patch_paths.iter().any(|patch| patch.starts_with(&target))
+        })
+        .collect();
+    stale.sort();
+    report.stale = stale.iter().map(|p| p.display().to_string()).collect();
+
+    let git_ops = GitOperations::new();
+    for clone_path in &stale {
+        // 只检查克隆自身的仓库，不能向上找到项目所在的仓库
+        if clone_path.join(".git").exists() && git_ops.is_dirty(clone_path).unwrap_or(false) {
+            warn!(
+                "⚠️  '{}' has uncommitted changes, not deleting it",
+                clone_path.display()
+            );
+            continue;
+        }
+        if !all {
+            let confirmed = dialoguer::Confirm::new()
+                .with_prompt(format!(
+                    "'{}' is not patched anymore, delete it?",
+                    clone_path.display()
+                ))
+                .default(false)
+                .interact()
+                .context("Failed to read confirmation")?;
+            if !confirmed {
+                continue;
+            }
         }
+        // 通过 link 创建的符号链接只删除链接本身
+        let result = if clone_path.is_symlink() {
+            fs::remove_file(clone_path)
+        } else {
+            fs::remove_dir_all(clone_path)
+        };
+        result.with_context(|| format!("Failed to delete '{}'", clone_path.display()))?;
+        info!("🗑️  Deleted {}", clone_path.display());
+        report.deleted.push(clone_path.display().to_string());
     }
 
+    output.reporter().clean_stale(&report)
+}
+
+/// 在用户配置中启用或关闭全局克隆缓存
+fn configure_global_cache(enable: bool, output: OutputFormat) -> Result<()> {
+    let config_path = global_cache::user_config_path()?;
+    let report = if enable {
+        let cache_dir = global_cache::enable()?;
+        GlobalCacheReport {
+            enabled: true,
+            changed: true,
+            config_path: config_path.display().to_string(),
+            cache_dir: Some(cache_dir.display().to_string()),
+        }
+    } else {
+        GlobalCacheReport {
+            enabled: false,
+            changed: global_cache::disable()?,
+            config_path: config_path.display().to_string(),
+            cache_dir: None,
+        }
+    };
+    output.reporter().global_cache(&report)?;
+
+    if std::env::var_os(global_cache::CACHE_DIR_KEY).is_some() {
+        warn!(
+            "⚠️  {} is set in the environment and overrides the user config",
+            global_cache::CACHE_DIR_KEY
+        );
+    }
     Ok(())
 }
 
-async fn analyze_dependencies() -> Result<()> {
-    info!("🔍 Analyzing Cargo.toml dependencies...");
+/// 在克隆目录中创建指向外部仓库的符号链接，并为其设置补丁
+fn link_external_clone(name: &str, target: &Path, dir: &str, output: OutputFormat) -> Result<()> {
+    let target = fs::canonicalize(target)
+        .with_context(|| format!("External path '{}' does not exist", target.display()))?;
+    if !target.is_dir() {
+        return Err(anyhow!(
+            "External path '{}' is not a directory",
+            target.display()
+        ));
+    }
 
-    let cargo_toml = CargoToml::find_and_load().context("Failed to find and load Cargo.toml")?;
+    let link_dir = PathBuf::from(dir);
+    fs::create_dir_all(&link_dir).with_context(|| format!("Failed to create directory '{dir}'"))?;
 
-    let all_deps = cargo_toml.get_all_dependencies();
+    let link_path = link_dir.join(name);
+    if link_path.symlink_metadata().is_ok() {
+        return Err(anyhow!(
+            "'{}' already exists, remove it first to create a link",
+            link_path.display()
+        ));
+    }
 
-    if all_deps.is_empty() {
-        info!("📦 No dependencies found in Cargo.toml");
-        return Ok(());
+    create_dir_symlink(&target, &link_path)?;
+    info!("🔗 Linked {} -> {}", link_path.display(), target.display());
+
+    let actual_crate_path = match WorkspaceDetector::find_crate_path(&link_path, name) {
+        Ok(path) => path,
+        Err(e) => {
+            // 链接目标中没有该 crate，撤销刚创建的链接
+            let _ = remove_dir_symlink(&link_path);
+            return Err(e);
+        }
+    };
+
+    // 与克隆时相同的规则选择 patch 源，不在 Cargo.toml 中的依赖按 crates.io 处理
+    let patch_source = CargoToml::find_and_load()
+        .ok()
+        .and_then(|cargo_toml| lookup_dependency(&cargo_toml, name, None))
+        .and_then(|dep| dep.dep_type.patch_source())
+        .unwrap_or_else(|| cargo_toml::patch_source(None, None));
+
+    let mut cargo_config = CargoConfig::load_or_create()?;
+    cargo_config.add_patch_with_source(name, &actual_crate_path, &patch_source)?;
+    cargo_config.save()?;
+
+    output.reporter().link(&LinkReport {
+        name: name.to_string(),
+        link: link_path.display().to_string(),
+        target: target.display().to_string(),
+        crate_path: actual_crate_path.display().to_string(),
+        source: patch_source,
+    })
+}
+
+#[cfg(unix)]
+fn create_dir_symlink(target: &Path, link: &Path) -> Result<()> {
+    std::os::unix::fs::symlink(target, link).with_context(|| {
+        format!(
+            "Failed to create symlink {} -> {}",
+            link.display(),
+            target.display()
+        )
+    })
+}
+
+#[cfg(unix)]
+fn remove_dir_symlink(link: &Path) -> std::io::Result<()> {
+    fs::remove_file(link)
+}
+
+#[cfg(windows)]
+fn remove_dir_symlink(link: &Path) -> std::io::Result<()> {
+    fs::remove_dir(link)
+}
+
+#[cfg(windows)]
+fn create_dir_symlink(target: &Path, link: &Path) -> Result<()> {
+    std::os::windows::fs::symlink_dir(target, link).with_context(|| {
+        format!(
+            "Failed to create symlink {} -> {}\n\
+            Creating symlinks on Windows requires Developer Mode or administrator privileges",
+            link.display(),
+            target.display()
+        )
+    })
+}
+
+/// 列出项目源码中所有引用指定 crate 的位置
+fn scan_crate_references(name: &str, dir: &str, output: OutputFormat) -> Result<()> {
+    let manifest_path = CargoToml::find_cargo_toml()?;
+    let project_root = manifest_path.parent().unwrap_or(Path::new("."));
+
+    info!(
+        "🔎 Scanning {} for references to '{name}'...",
+        project_root.display()
+    );
+    let references = scan::scan_references(project_root, name, &[dir])?;
+    output.reporter().references(&ReferenceReport {
+        name: name.to_string(),
+        references,
+    })
+}
+
+/// 输出所有补丁的汇总统计
+fn show_metrics(output: OutputFormat) -> Result<()> {
+    let cargo_config = CargoConfig::load()?;
+    let git_ops = GitOperations::new();
+    let metrics = metrics::collect_metrics(&cargo_config, &git_ops)?;
+
+    output.reporter().metrics(&metrics)
+}
+
+/// 输出可被 shell `source` 的环境变量设置，让补丁会话使用独立的构建目录
+fn export_env(target_dir: Option<&str>, output: OutputFormat) -> Result<()> {
+    let cargo_config = CargoConfig::load()?;
+    let patches = cargo_config.list_patches();
+    let project_dir = CargoConfig::project_dir();
+
+    let target_dir = match target_dir {
+        Some(dir) => PathBuf::from(dir),
+        None => project_dir.join("target").join("lpatch"),
+    };
+
+    if patches.is_empty() {
+        warn!("⚠️  No active patches found in .cargo/config.toml");
     }
 
-    info!("📦 Found {} dependencies:", all_deps.len());
+    output.reporter().env_export(&EnvExport {
+        project_dir: project_dir.display().to_string(),
+        patches: patches
+            .into_iter()
+            .map(|(source, name, path)| PatchEntry {
+                path_exists: CargoConfig::resolve_patch_path(&path).exists(),
+                source,
+                name,
+                path,
+            })
+            .collect(),
+        env: [(
+            "CARGO_TARGET_DIR".to_string(),
+            target_dir.display().to_string(),
+        )]
+        .into(),
+    })
+}
 
-    // 按类型分组显示
-    let version_deps = cargo_toml.get_version_dependencies();
-    let git_deps = cargo_toml.get_git_dependencies();
-    let path_deps = cargo_toml.get_path_dependencies();
+fn generate_ci_config(format: &str, output: Option<&str>) -> Result<()> {
+    let format = ci::CiFormat::parse(format)?;
+    let cargo_config = CargoConfig::load()?;
+    let mut patches: Vec<_> = cargo_config
+        .list_patches()
+        .into_iter()
+        .map(|(_, name, path)| ci::CiPatch::from_patch_path(&name, &path))
+        .collect();
+    // 同一个 crate 可能出现在多个源中，只需要重建一次
+    patches.sort_by(|a, b| a.name.cmp(&b.name));
+    patches.dedup_by(|a, b| a.name == b.name);
 
-    if !version_deps.is_empty() {
-        info!(
-            "🌐 Version dependencies (from crates.io): {}",
-            version_deps.len()
-        );
-        for dep in &version_deps {
-            if let DependencyType::Version { version } = &dep.dep_type {
-                info!("  📋 {} = \"{}\"", dep.name, version);
-            }
-        }
+    if patches.is_empty() {
+        warn!("⚠️  No active patches found in .cargo/config.toml");
     }
 
-    if !git_deps.is_empty() {
-        info!("🔗 Git dependencies: {}", git_deps.len());
-        for dep in &git_deps {
-            if let DependencyType::Git {
-                git,
-                branch,
-                tag,
-                rev,
-            } = &dep.dep_type
-            {
-                let mut git_spec = format!("  🌿 {} = {{ git = \"{}\"", dep.name, git);
-                if let Some(branch) = branch {
-                    git_spec.push_str(&format!(", branch = \"{branch}\""));
-                }
-                if let Some(tag) = tag {
-                    git_spec.push_str(&format!(", tag = \"{tag}\""));
-                }
-                if let Some(rev) = rev {
-                    git_spec.push_str(&format!(", rev = \"{rev}\""));
-                }
-                git_spec.push_str(" }");
-                info!("{git_spec}");
+    let content = ci::generate(format, &patches);
+    match output {
+        Some(path) => {
+            let path = Path::new(path);
+            if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                fs::create_dir_all(parent).with_context(|| {
+                    format!("Failed to create directory '{}'", parent.display())
+                })?;
             }
+            fs::write(path, content)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            info!("📝 Wrote CI configuration to {}", path.display());
         }
+        None => print!("{content}"),
     }
 
-    if !path_deps.is_empty() {
-        info!("📁 Path dependencies: {}", path_deps.len());
-        for dep in &path_deps {
-            if let DependencyType::Path { path } = &dep.dep_type {
-                info!("  📂 {} = {{ path = \"{}\" }}", dep.name, path);
-            }
+    Ok(())
+}
+
+/// 根据补丁仓库的远程地址输出 CI 中需要配置的凭据
+fn add_ci_secret(provider: &str) -> Result<()> {
+    let format = ci::CiFormat::parse(provider)?;
+    let cargo_config = CargoConfig::load()?;
+    let git_ops = GitOperations::new();
+
+    let mut remotes: Vec<ci::CiRemote> = Vec::new();
+    for (source, name, path) in cargo_config.list_patches() {
+        if remotes.iter().any(|r| r.name == name) {
+            continue;
         }
+        // 优先使用克隆的 origin，未克隆时 git 依赖的 patch 源就是仓库地址
+        let patch_path = CargoConfig::resolve_patch_path(&path);
+        let url = match git_ops
+            .repo_root(&patch_path)
+            .and_then(|root| git_ops.origin_url(&root))
+        {
+            Ok(url) => url,
+            Err(_) if is_git_url(&source) => source,
+            Err(e) => {
+                warn!("⚠️  Skipping '{name}': could not determine its repository ({e})");
+                continue;
+            }
+        };
+        let access = ci::RemoteAccess::of(&url);
+        let host = match access {
+            ci::RemoteAccess::Ssh => ssh_to_https(&url)
+                .ok()
+                .and_then(|https| Url::parse(&https).ok())
+                .and_then(|parsed| parsed.host_str().map(str::to_string)),
+            _ => None,
+        };
+        remotes.push(ci::CiRemote {
+            name,
+            url,
+            access,
+            host,
+        });
     }
 
-    info!("💡 Use 'cargo lpatch --name <CRATE_NAME>' to patch a specific dependency");
-
+    if remotes.is_empty() {
+        warn!("⚠️  No active patches found in .cargo/config.toml");
+    }
+    print!("{}", ci::secret_instructions(format, &remotes));
     Ok(())
 }
 
-async fn run_lpatch(name: &str, dir: &str) -> Result<()> {
+async fn run_lpatch(
+    name: &str,
+    options: &LpatchOptions,
+    timings: &mut PhaseTimings,
+    cleanup: &mut FailureCleanup,
+) -> Result<DryRunPlan> {
+    let dir = options.dir.as_str();
+    let mut plan = DryRunPlan::new(options.dry_run);
     info!("Creating local patch for: {name}");
     info!("Clone directory: {dir}");
 
     // 尝试从 Cargo.toml 分析依赖信息
-    let dependency_info = if let Ok(cargo_toml) = CargoToml::find_and_load() {
-        cargo_toml.find_dependency(name)
+    let start = Instant::now();
+    let cargo_toml = CargoToml::find_and_load().ok();
+    let dependency_info = cargo_toml
+        .as_ref()
+        .and_then(|cargo_toml| lookup_dependency(cargo_toml, name, options.section));
+    timings.record("manifest load", start);
+
+    // --locked/--frozen：要求 Cargo.lock 存在并且锁定了目标 crate
+    let locked_package = if options.locked {
+        let manifest_path = CargoToml::find_cargo_toml()?;
+        let lock = CargoLock::load_for_manifest(&manifest_path)?;
+        let lock_name = match &dependency_info {
+            Some(dep_info) => dep_info.name.clone(),
+            None if is_git_url(name) => extract_crate_name_from_git_url(name)?,
+            None => name.to_string(),
+        };
+        let package = lock.find_package(&lock_name)?.clone();
+        check_lock_freshness(&package, dependency_info.as_ref().map(|d| &d.dep_type))?;
+        info!(
+            "🔒 Using locked version {} {}",
+            package.name, package.version
+        );
+        Some(package)
     } else {
         None
     };
 
+    // 检查目标 crate 是否已经被已废弃的 [replace] 表替换
+    let replacement = cargo_toml.as_ref().and_then(|cargo_toml| {
+        cargo_toml
+            .get_replacements()
+            .into_iter()
+            .find(|r| r.target.name == name)
+    });
+    if let Some(replacement) = &replacement {
+        warn!(
+            "⚠️  '{}' is already overridden by [replace] entry '{}', which can conflict with [patch]",
+            name, replacement.spec
+        );
+        if !options.migrate_replace {
+            info!("💡 Use --migrate-replace to move it to a [patch] entry pointing at the local clone");
+        }
+    }
+
     // 根据依赖信息或用户输入确定 crate 信息
-    let crate_info = if let Some(dep_info) = dependency_info {
-        info!("📦 Found dependency '{}' in Cargo.toml", dep_info.name);
+    let mut crate_info = if let Some(dep_info) = dependency_info {
+        info!(
+            "📦 Found dependency '{}' in [{}] of Cargo.toml",
+            dep_info.name,
+            dep_info.section.table_name()
+        );
+        if let Some(target_cfg) = &dep_info.target_cfg {
+            info!(
+                "🖥️  Dependency is only active for target '{target_cfg}', but the patch applies to all targets"
+            );
+        }
+        if dep_info.optional {
+            info!("🔌 Dependency is optional, the patch only takes effect when its feature is enabled");
+        }
 
         match &dep_info.dep_type {
             DependencyType::Git {
@@ -192,30 +3431,81 @@ async fn run_lpatch(name: &str, dir: &str) -> Result<()> {
                     info!("  🔄 Revision: {rev}");
                 }
 
+                // Cargo 只允许指定其中之一，按 rev > tag > branch 的优先级选择
+                let git_ref = rev
+                    .clone()
+                    .map(GitReference::Rev)
+                    .or_else(|| tag.clone().map(GitReference::Tag))
+                    .or_else(|| branch.clone().map(GitReference::Branch));
+
+                // Cargo.lock 中锁定的提交比 Cargo.toml 中的分支/tag 更精确
+                let git_ref = match locked_package.as_ref().and_then(|p| p.git_commit()) {
+                    Some(commit) => Some(GitReference::Rev(commit.to_string())),
+                    None => git_ref,
+                };
+
                 CrateInfo {
                     name: dep_info.name.clone(),
                     repository_url: git.clone(),
                     is_git_ref: true,
                     original_git_url: Some(git.clone()),
+                    git_ref,
+                    registry: None,
                 }
             }
-            DependencyType::Version { version } => {
+            DependencyType::Version { version, registry } => {
                 info!("🌐 Version dependency detected: {version}");
-                info!("🔍 Querying crates.io for repository URL...");
 
-                let client = CratesIoClient::new();
-                let repo_url = client
-                    .get_repository_url(&dep_info.name)
-                    .await
-                    .with_context(|| {
-                        format!("Failed to get repository URL for crate '{}'", dep_info.name)
-                    })?;
+                let (crate_name, repository_url) = if options.no_crates_io && registry.is_none() {
+                    return Err(crates_io_disabled(&dep_info.name));
+                } else if options.frozen {
+                    frozen_repository_url(dir, &dep_info.name)?
+                } else {
+                    let start = Instant::now();
+                    let mut client = match registry {
+                        Some(registry) => {
+                            info!("🔍 Querying registry '{registry}' for repository URL...");
+                            let index = CargoConfig::registry_index(registry)?.ok_or_else(|| {
+                                anyhow!(
+                                    "Registry '{}' used by '{}' is not declared under [registries] in the cargo config",
+                                    registry,
+                                    dep_info.name
+                                )
+                            })?;
+                            CratesIoClient::for_registry(registry, &index).await?
+                        }
+                        None => {
+                            info!("🔍 Querying crates.io for repository URL...");
+                            CratesIoClient::new().with_env_auth()
+                        }
+                    };
+                    if let Some(retries) = options.retries {
+                        client = client.with_retries(retries);
+                    }
+                    if !options.no_cache {
+                        client = client.with_cache(options.lookup_cache_ttl());
+                    }
+                    let resolved =
+                        client
+                            .resolve_crate(&dep_info.name)
+                            .await
+                            .with_context(|| {
+                                format!(
+                                    "Failed to get repository URL for crate '{}'",
+                                    dep_info.name
+                                )
+                            })?;
+                    timings.record("crates.io resolution", start);
+                    (resolved.name, resolved.repository_url)
+                };
 
                 CrateInfo {
-                    name: dep_info.name.clone(),
-                    repository_url: repo_url,
+                    name: crate_name,
+                    repository_url,
                     is_git_ref: false,
                     original_git_url: None,
+                    git_ref: None,
+                    registry: registry.clone(),
                 }
             }
             DependencyType::Path { path } => {
@@ -230,57 +3520,205 @@ async fn run_lpatch(name: &str, dir: &str) -> Result<()> {
         // 回退到原有逻辑：检查是否是 git URL
         if is_git_url(name) {
             info!("🔗 Direct git URL detected");
+            let git_url = normalize_local_git_url(name)?;
             CrateInfo {
-                name: extract_crate_name_from_git_url(name)?,
-                repository_url: name.to_string(),
+                name: extract_crate_name_from_git_url(&git_url)?,
+                repository_url: git_url.clone(),
                 is_git_ref: true,
-                original_git_url: Some(name.to_string()),
+                original_git_url: Some(git_url),
+                git_ref: None,
+                registry: None,
             }
         } else {
-            // 从 crates.io 查询
-            info!("🌐 Querying crates.io for crate: {name}");
-            let client = CratesIoClient::new();
-            let repo_url = client
-                .get_repository_url(name)
-                .await
-                .with_context(|| format!("Failed to get repository URL for crate '{name}'"))?;
+            let (crate_name, repository_url) = if options.no_crates_io {
+                return Err(crates_io_disabled(name));
+            } else if options.frozen {
+                frozen_repository_url(dir, name)?
+            } else {
+                // 从 crates.io 查询
+                info!("🌐 Querying crates.io for crate: {name}");
+                let start = Instant::now();
+                let mut client = CratesIoClient::new().with_env_auth();
+                if let Some(retries) = options.retries {
+                    client = client.with_retries(retries);
+                }
+                if !options.no_cache {
+                    client = client.with_cache(options.lookup_cache_ttl());
+                }
+                let resolved = client
+                    .resolve_crate(name)
+                    .await
+                    .with_context(|| format!("Failed to get repository URL for crate '{name}'"))?;
+                timings.record("crates.io resolution", start);
+                (resolved.name, resolved.repository_url)
+            };
 
             CrateInfo {
-                name: name.to_string(),
-                repository_url: repo_url,
+                name: crate_name,
+                repository_url,
                 is_git_ref: false,
                 original_git_url: None,
+                git_ref: None,
+                registry: None,
             }
         }
     };
 
+    // 命令行指定的引用覆盖 Cargo.toml 和 Cargo.lock 推断出的引用
+    if let Some(git_ref) = &options.git_ref {
+        info!("📌 Using {git_ref} from the command line");
+        crate_info.git_ref = Some(git_ref.clone());
+    }
+
     info!("Repository URL: {}", crate_info.repository_url);
 
+    // 在任何网络访问之前检查主机规则
+    options.host_policy.check(&crate_info.repository_url)?;
+
     // 创建目标目录
     let target_dir = PathBuf::from(dir);
-    if !target_dir.exists() {
+    if !target_dir.exists()
+        && plan.record(PlannedAction::CreateDir {
+            path: target_dir.clone(),
+        })
+    {
+        cleanup.track_new_dir(&target_dir);
         fs::create_dir_all(&target_dir)
             .with_context(|| format!("Failed to create directory '{dir}'"))?;
     }
 
     // 克隆仓库
-    let git_ops = GitOperations::new();
+    let start = Instant::now();
+    // libgit2 的本地传输不支持浅克隆
+    let depth = match options.depth {
+        Some(depth) if depth > 0 && crate_info.repository_url.starts_with("file://") => {
+            warn!("⚠️  Shallow clones are not supported for local repositories, ignoring --depth");
+            None
+        }
+        depth => depth,
+    };
+    let mut git_ops = GitOperations::new().with_depth(depth);
+    if let Some(retries) = options.retries {
+        git_ops = git_ops.with_retries(retries);
+    }
+    if options.sparse {
+        git_ops = git_ops.with_no_checkout();
+    }
+    if let Some(progress) = &options.progress {
+        git_ops = git_ops.with_progress(progress.clone());
+    }
     let clone_path = target_dir.join(&crate_info.name);
+    plan.clone_path = Some(clone_path.clone());
 
-    if clone_path.exists() {
+    let fetch_result = if clone_path.exists() && options.frozen {
+        info!(
+            "Directory '{}' already exists, not pulling because of --frozen",
+            clone_path.display()
+        );
+        Ok(())
+    } else if clone_path.exists() && options.dry_run {
+        plan.record(PlannedAction::Pull {
+            path: clone_path.clone(),
+        });
+        if let Some(git_ref) = &options.git_ref {
+            plan.record(PlannedAction::Checkout {
+                path: clone_path.clone(),
+                reference: git_ref.to_string(),
+            });
+        }
+        Ok(())
+    } else if clone_path.exists() {
         info!(
             "Directory '{}' already exists, pulling latest changes...",
             clone_path.display()
         );
-        git_ops.pull(&clone_path)?;
+        git_ops
+            .pull(&clone_path)
+            .and_then(|_| match &options.git_ref {
+                // 已有克隆只在显式指定引用时切换，且不能覆盖未提交的修改
+                Some(git_ref) => {
+                    if git_ops.is_dirty(&clone_path)? {
+                        return Err(anyhow!(
+                            "'{}' has uncommitted changes, refusing to check out {}",
+                            clone_path.display(),
+                            git_ref
+                        ));
+                    }
+                    git_ops
+                        .checkout_ref(&clone_path, git_ref)
+                        .map_err(|e| with_shallow_hint(e, &git_ops, &clone_path))
+                }
+                None => Ok(()),
+            })
+    } else if options.frozen && !crate_info.repository_url.starts_with("file://") {
+        Err(anyhow!(
+            "'{}' has not been cloned yet and --frozen forbids network access",
+            clone_path.display()
+        ))
+    } else if options.dry_run {
+        let reference = match (&crate_info.git_ref, &locked_package) {
+            (Some(git_ref), _) => Some(git_ref.to_string()),
+            (None, Some(package)) => Some(format!("the tag of locked version {}", package.version)),
+            (None, None) => None,
+        };
+        plan.record(PlannedAction::Clone {
+            url: crate_info.repository_url.clone(),
+            path: clone_path.clone(),
+            reference,
+        });
+        Ok(())
     } else {
         info!("Cloning repository to '{}'...", clone_path.display());
-        git_ops.clone(&crate_info.repository_url, &clone_path)?;
-    }
+        cleanup.track_new_dir(&clone_path);
+        let branch = match &crate_info.git_ref {
+            Some(GitReference::Branch(branch)) => Some(branch.as_str()),
+            _ => None,
+        };
+        // 稀疏检出需要独立的克隆，不使用全局缓存
+        let cache_dir = match options.sparse {
+            true => None,
+            false => global_cache::cache_dir()?,
+        };
+        match &cache_dir {
+            Some(cache_dir) => global_cache::add_worktree(
+                &git_ops,
+                cache_dir,
+                &crate_info.repository_url,
+                &clone_path,
+                &crate_info.name,
+                branch,
+            ),
+            None => git_ops.clone(&crate_info.repository_url, &clone_path, branch),
+        }
+        .and_then(|()| match options.sparse {
+            true => sparse_checkout_crate(&git_ops, &clone_path, &crate_info.name),
+            false => Ok(()),
+        })
+        .and_then(|()| match &crate_info.git_ref {
+            // 分支已经在克隆时检出
+            Some(GitReference::Branch(_)) => Ok(()),
+            Some(git_ref) => git_ops
+                .checkout_ref(&clone_path, git_ref)
+                .map_err(|e| with_shallow_hint(e, &git_ops, &clone_path)),
+            None => match &locked_package {
+                Some(package) => checkout_locked_version(&git_ops, &clone_path, package),
+                None => Ok(()),
+            },
+        })
+    };
+    timings.record("clone/pull", start);
+    fetch_result?;
 
     // 检测 workspace 并找到正确的 crate 路径
-    let actual_crate_path = match WorkspaceDetector::find_crate_path(&clone_path, &crate_info.name)
-    {
+    let start = Instant::now();
+    let requires_clone = options.dry_run && !clone_path.exists();
+    let workspace_result = if requires_clone {
+        info!("🔍 Not cloned yet, assuming the crate is at the repository root");
+        Ok(clone_path.clone())
+    } else {
+        WorkspaceDetector::find_crate_path(&clone_path, &crate_info.name)
+    };
+    let actual_crate_path = match workspace_result {
         Ok(path) => {
             if path != clone_path {
                 info!(
@@ -325,25 +3763,75 @@ async fn run_lpatch(name: &str, dir: &str) -> Result<()> {
             }
         }
     };
+    timings.record("workspace detection", start);
+    plan.crate_path = Some(if requires_clone {
+        PlannedCratePath::RequiresClone {
+            assumed: actual_crate_path.clone(),
+        }
+    } else {
+        PlannedCratePath::Resolved {
+            path: actual_crate_path.clone(),
+        }
+    });
 
     // 更新或创建 .cargo/config.toml
-    let mut cargo_config = CargoConfig::load_or_create()?;
-
-    // 根据依赖类型选择正确的 patch 源
-    if let Some(original_git_url) = &crate_info.original_git_url {
-        // Git 依赖使用原始的 git URL 作为 patch 源
-        cargo_config.add_patch_with_source(
-            &crate_info.name,
-            &actual_crate_path,
-            original_git_url,
-        )?;
-    } else {
-        // 版本依赖使用 crates-io 作为 patch 源
-        cargo_config.add_patch(&crate_info.name, &actual_crate_path)?;
+    let start = Instant::now();
+    let patch_source = cargo_toml::patch_source(
+        crate_info.original_git_url.as_deref(),
+        crate_info.registry.as_deref(),
+    );
+    let patch_file = options.patch_target.path(cargo_toml.as_ref())?;
+    let write_patch = plan.record(PlannedAction::WritePatch {
+        config: patch_file.clone(),
+        crate_name: crate_info.name.clone(),
+        source: patch_source.clone(),
+        path: actual_crate_path.clone(),
+    });
+    if let (Some(replacement), true) = (&replacement, options.migrate_replace) {
+        plan.record(PlannedAction::RemoveReplacement {
+            manifest: CargoToml::find_cargo_toml()?,
+            spec: replacement.spec.clone(),
+        });
     }
+    if !write_patch {
+        timings.record("config write", start);
+        info!("🔍 Dry run, no changes made");
+        return Ok(plan);
+    }
+
+    // --all --jobs 下多个任务会并发写入同一个配置文件
+    let _config_guard = CONFIG_WRITE_LOCK
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    let mut cargo_config = match options.patch_target {
+        PatchTarget::CargoConfig => {
+            cleanup.track_new_dir(&CargoConfig::get_config_dir());
+            cleanup.backup_config(&patch_file)?;
+            CargoConfig::load_or_create()?
+        }
+        PatchTarget::CargoToml => {
+            cleanup.backup_config(&patch_file)?;
+            CargoConfig::load_manifest(&patch_file)?
+        }
+    };
+
+    cargo_config.add_patch_with_source(&crate_info.name, &actual_crate_path, &patch_source)?;
 
     cargo_config.save()?;
 
+    // patch 写入成功后再移除 [replace]，避免两者都不存在
+    if let (Some(replacement), true) = (&replacement, options.migrate_replace) {
+        let manifest_path = CargoToml::find_cargo_toml()?;
+        if CargoToml::remove_replacement(&manifest_path, &replacement.spec)? {
+            info!(
+                "🔀 Migrated [replace] entry '{}' to [patch] in {}",
+                replacement.spec,
+                manifest_path.display()
+            );
+        }
+    }
+    timings.record("config write", start);
+
     info!(
         "✅ Successfully set up local patch for '{}'",
         crate_info.name
@@ -352,40 +3840,270 @@ async fn run_lpatch(name: &str, dir: &str) -> Result<()> {
     if actual_crate_path != clone_path {
         info!("🎯 Crate located at: {}", actual_crate_path.display());
     }
-    info!("⚙️  Updated .cargo/config.toml with local patch configuration");
+    info!(
+        "⚙️  Updated {} with local patch configuration",
+        patch_file.display()
+    );
+
+    if options.show_dependents {
+        show_dependents(&crate_info.name);
+    }
+
+    Ok(plan)
+}
+
+/// 浅克隆中找不到引用时，提示用户获取完整历史
+fn with_shallow_hint(
+    e: anyhow::Error,
+    git_ops: &GitOperations,
+    clone_path: &Path,
+) -> anyhow::Error {
+    if git_ops.is_shallow(clone_path) {
+        e.context(format!(
+            "'{}' is a shallow clone and the ref may be outside the fetched history, re-run with --depth 0 to fetch the full history",
+            clone_path.display()
+        ))
+    } else {
+        e
+    }
+}
 
+/// 检查 Cargo.lock 中的来源是否与 Cargo.toml 中的依赖类型一致
+fn check_lock_freshness(package: &LockedPackage, dep_type: Option<&DependencyType>) -> Result<()> {
+    let stale = match dep_type {
+        Some(DependencyType::Git { .. }) => !package.is_git(),
+        Some(DependencyType::Version { .. }) => package.is_git(),
+        _ => false,
+    };
+    if stale {
+        return Err(anyhow!(
+            "Cargo.lock entry for '{}' ({}) does not match Cargo.toml, the lockfile is stale (run 'cargo update -p {}')",
+            package.name,
+            package.source.as_deref().unwrap_or("no source"),
+            package.name
+        ));
+    }
     Ok(())
 }
 
+fn crates_io_disabled(crate_name: &str) -> anyhow::Error {
+    anyhow!(
+        "Resolving '{}' requires crates.io, which is disabled by --no-crates-io; declare it as a git dependency in Cargo.toml or pass a git URL",
+        crate_name
+    )
+}
+
+/// --frozen 模式下不查询 crates.io，只能复用已有克隆的 origin URL
+fn frozen_repository_url(dir: &str, crate_name: &str) -> Result<(String, String)> {
+    let clone_path = PathBuf::from(dir).join(crate_name);
+    if !clone_path.exists() {
+        return Err(anyhow!(
+            "Cannot resolve '{}' without querying crates.io, which --frozen forbids (no existing clone at '{}')",
+            crate_name,
+            clone_path.display()
+        ));
+    }
+
+    let url = GitOperations::new().origin_url(&clone_path)?;
+    info!("🧊 Reusing existing clone at '{}'", clone_path.display());
+    Ok((crate_name.to_string(), url))
+}
+
+/// 稀疏检出 crate 所在目录，在仓库中找不到该 crate 时退回完整检出
+fn sparse_checkout_crate(
+    git_ops: &GitOperations,
+    clone_path: &Path,
+    crate_name: &str,
+) -> Result<()> {
+    match git_ops.find_crate_dir(clone_path, crate_name)? {
+        Some(subdir) if !subdir.is_empty() => git_ops.sparse_checkout(clone_path, &subdir),
+        found => {
+            match found {
+                Some(_) => {
+                    info!("📦 '{crate_name}' is at the repository root, checking out everything")
+                }
+                None => warn!(
+                    "⚠️  Could not find '{crate_name}' in the repository, checking out everything"
+                ),
+            }
+            git_ops.checkout_head(clone_path)
+        }
+    }
+}
+
+/// 将新克隆的仓库切换到 Cargo.lock 锁定版本对应的 tag
+fn checkout_locked_version(
+    git_ops: &GitOperations,
+    clone_path: &Path,
+    package: &LockedPackage,
+) -> Result<()> {
+    match git_ops.find_version_tag(clone_path, &package.name, &package.version)? {
+        Some(tag) => {
+            info!(
+                "🏷️  Checking out tag '{tag}' for locked version {}",
+                package.version
+            );
+            git_ops.checkout_ref(clone_path, &GitReference::Tag(tag))
+        }
+        None => {
+            warn!(
+                "⚠️  No tag found for locked version {} of '{}', staying on the default branch",
+                package.version, package.name
+            );
+            Ok(())
+        }
+    }
+}
+
+/// 打印项目中引入指定 crate 的依赖路径，失败时只给出警告
+fn show_dependents(crate_name: &str) {
+    let metadata = match metadata::CargoMetadata::load(&CargoConfig::project_dir()) {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            warn!("⚠️  Could not determine dependents: {e}");
+            return;
+        }
+    };
+
+    let paths = metadata.dependent_paths(crate_name);
+    if paths.is_empty() {
+        info!("🌳 No dependents of '{crate_name}' found in the dependency graph");
+        return;
+    }
+
+    info!("🌳 '{crate_name}' is pulled in by:");
+    for path in paths {
+        info!("  {}", path.join(" -> "));
+    }
+}
+
+/// 从 `.env` 文件加载环境变量（每行一个 `KEY=VALUE`，忽略 `#` 注释和空行），返回加载的变量数
+///
+/// 在初始化日志和启动 tokio 运行时之前调用，因此这里不输出日志
+fn load_env_file(path: &Path) -> Result<usize> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read env file: {}", path.display()))?;
+
+    let mut count = 0;
+    for (line_no, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, value) = line.split_once('=').ok_or_else(|| {
+            anyhow!(
+                "Invalid line {} in {}: expected KEY=VALUE",
+                line_no + 1,
+                path.display()
+            )
+        })?;
+
+        let value = value.trim();
+        let value = value
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+            .unwrap_or(value);
+
+        // 空名称以及名称或值中的 NUL 会让 set_var panic
+        let key = key.trim();
+        if key.is_empty() || key.contains('\0') {
+            return Err(anyhow!(
+                "Invalid line {} in {}: the variable name must be non-empty and must not contain NUL",
+                line_no + 1,
+                path.display()
+            ));
+        }
+        if value.contains('\0') {
+            return Err(anyhow!(
+                "Invalid line {} in {}: the value of '{}' must not contain NUL",
+                line_no + 1,
+                path.display(),
+                key
+            ));
+        }
+
+        std::env::set_var(key, value);
+        count += 1;
+    }
+
+    Ok(count)
+}
+
 fn is_git_url(s: &str) -> bool {
     s.starts_with("http://")
         || s.starts_with("https://")
         || s.starts_with("git://")
         || s.starts_with("ssh://")
+        || s.starts_with("file://")
         || s.contains("git@")
+        || is_local_git_path(s)
 }
 
-fn extract_crate_name_from_git_url(git_url: &str) -> Result<String> {
-    let url = if git_url.starts_with("git@") {
-        // 转换 SSH URL 格式
-        let parts: Vec<&str> = git_url.split(':').collect();
-        if parts.len() != 2 {
-            return Err(anyhow!("Invalid git SSH URL format"));
-        }
-        format!("https://{}/{}", parts[0].replace("git@", ""), parts[1])
-    } else {
-        git_url.to_string()
+/// 本地文件系统中的 git 仓库路径（例如 `../mirror/foo.git` 或 `/path/to/repo/.git`）
+fn is_local_git_path(s: &str) -> bool {
+    s.trim_end_matches('/').ends_with(".git") && Path::new(s).exists()
+}
+
+/// 将本地仓库路径转换为 `file://` URL，其他 URL 保持不变
+fn normalize_local_git_url(s: &str) -> Result<String> {
+    if !is_local_git_path(s) || s.starts_with("file://") {
+        return Ok(s.to_string());
+    }
+
+    let path = fs::canonicalize(s).with_context(|| format!("Failed to resolve path '{s}'"))?;
+    let url = Url::from_directory_path(&path)
+        .map_err(|_| anyhow!("Failed to convert '{}' to a file URL", path.display()))?;
+    Ok(url.as_str().trim_end_matches('/').to_string())
+}
+
+/// 将 SSH 形式的 git URL 规范化为 HTTPS URL，其他 URL 保持不变
+///
+/// 支持 `ssh://[user@]host[:port]/path` 和 scp 风格的 `[user@]host:path`，
+/// 路径可以包含多级分组（如 GitLab 子分组），端口会被丢弃
+fn ssh_to_https(git_url: &str) -> Result<String> {
+    if git_url.starts_with("ssh://") {
+        let parsed =
+            Url::parse(git_url).with_context(|| format!("Failed to parse URL: {git_url}"))?;
+        let host = parsed
+            .host_str()
+            .ok_or_else(|| anyhow!("Invalid git SSH URL format: {git_url}"))?;
+        return Ok(format!("https://{}{}", host, parsed.path()));
+    }
+    if git_url.contains("://") {
+        return Ok(git_url.to_string());
+    }
+
+    // scp 风格：[user@]host:path
+    let (host, path) = git_url
+        .split_once(':')
+        .ok_or_else(|| anyhow!("Invalid git SSH URL format: {git_url}"))?;
+    let host = host.rsplit_once('@').map_or(host, |(_, host)| host);
+    if host.is_empty() || path.is_empty() {
+        return Err(anyhow!("Invalid git SSH URL format: {git_url}"));
+    }
+    // 兼容 `git@host:2222/org/repo.git` 这种把端口写在冒号后的写法
+    let path = match path.split_once('/') {
+        Some((port, rest)) if !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit()) => rest,
+        _ => path,
     };
+    Ok(format!("https://{}/{}", host, path.trim_start_matches('/')))
+}
 
+fn extract_crate_name_from_git_url(git_url: &str) -> Result<String> {
+    let url = ssh_to_https(git_url)?;
     let parsed_url = Url::parse(&url).with_context(|| format!("Failed to parse URL: {url}"))?;
 
-    let path = parsed_url.path();
-    let name = path
-        .trim_start_matches('/')
+    let name = parsed_url
+        .path()
+        .trim_end_matches('/')
+        .trim_end_matches("/.git")
         .trim_end_matches(".git")
-        .split('/')
-        .next_back()
-        .ok_or_else(|| anyhow!("Could not extract crate name from URL"))?;
+        .rsplit('/')
+        .find(|segment| !segment.is_empty())
+        .ok_or_else(|| anyhow!("Could not extract crate name from URL: {git_url}"))?;
 
     Ok(name.to_string())
 }
@@ -422,3 +4140,206 @@ fn find_similar_crate(
 
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ssh_to_https_handles_subgroups_ports_and_schemes() {
+        assert_eq!(
+            ssh_to_https("git@gitlab.com:group/subgroup/myproj.git").unwrap(),
+            "https://gitlab.com/group/subgroup/myproj.git"
+        );
+        assert_eq!(
+            ssh_to_https("ssh://git@host.example:2222/org/repo.git").unwrap(),
+            "https://host.example/org/repo.git"
+        );
+        assert_eq!(
+            ssh_to_https("git@host.example:2222/org/repo.git").unwrap(),
+            "https://host.example/org/repo.git"
+        );
+        assert_eq!(
+            ssh_to_https("https://github.com/org/repo").unwrap(),
+            "https://github.com/org/repo"
+        );
+        assert!(ssh_to_https("git@host.example:").is_err());
+    }
+
+    #[test]
+    fn crate_name_from_git_urls() {
+        let cases = [
+            ("git@gitlab.com:group/subgroup/myproj.git", "myproj"),
+            ("git@gitlab.com:a/b/c/deep.git", "deep"),
+            ("ssh://git@host.example:2222/org/repo.git", "repo"),
+            ("git@host.example:2222/org/repo.git", "repo"),
+            ("https://github.com/org/repo/", "repo"),
+            ("https://github.com/org/repo.git/", "repo"),
+            ("https://gitlab.com/group/subgroup/myproj", "myproj"),
+        ];
+        for (url, name) in cases {
+            assert_eq!(extract_crate_name_from_git_url(url).unwrap(), name, "{url}");
+        }
+    }
+
+    /// 在 `project` 中执行一次 lpatch 主流程
+    fn lpatch_in(project: &Path, name: &str, options: &LpatchOptions) -> Result<DryRunPlan> {
+        test_support::in_dir(project, || {
+            tokio::runtime::Runtime::new().unwrap().block_on(run_lpatch(
+                name,
+                options,
+                &mut PhaseTimings::new(false),
+                &mut FailureCleanup::new(false),
+            ))
+        })
+    }
+
+    #[test]
+    fn patches_target_gated_git_dependency() {
+        let tmp = tempfile::tempdir().unwrap();
+        test_support::crate_repo(&tmp.path().join("upstream/samp"), "samp", 1);
+        let url = Url::from_directory_path(tmp.path().join("upstream/samp"))
+            .unwrap()
+            .as_str()
+            .trim_end_matches('/')
+            .to_string();
+        let app = tmp.path().join("app");
+        test_support::project(
+            &app,
+            &format!(
+                "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n\
+                 [target.'cfg(windows)'.dependencies]\nsamp = {{ git = \"{url}\" }}\n"
+            ),
+        );
+
+        let options = LpatchOptions {
+            dir: "crates".to_string(),
+            ..Default::default()
+        };
+        lpatch_in(&app, "samp", &options).unwrap();
+
+        assert!(app.join("crates/samp/Cargo.toml").exists());
+        let patches = test_support::in_dir(&app, || CargoConfig::load().unwrap().list_patches());
+        assert_eq!(
+            patches,
+            [(url, "samp".to_string(), "crates/samp".to_string())]
+        );
+    }
+
+    /// 目录下所有文件及其内容
+    fn snapshot(dir: &Path) -> Vec<(PathBuf, Vec<u8>)> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                files.push((path.clone(), Vec::new()));
+                files.extend(snapshot(&path));
+            } else {
+                files.push((path.clone(), fs::read(&path).unwrap()));
+            }
+        }
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn dry_run_plans_without_touching_the_filesystem() {
+        let tmp = tempfile::tempdir().unwrap();
+        let app = tmp.path().join("app");
+        // 仓库不存在，真正克隆会失败
+        let url = "file:///nonexistent/samp";
+        test_support::project(
+            &app,
+            &format!(
+                "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n\
+                 [dependencies]\nsamp = {{ git = \"{url}\", branch = \"dev\" }}\n"
+            ),
+        );
+        let before = snapshot(&app);
+
+        let options = LpatchOptions {
+            dir: "crates".to_string(),
+            dry_run: true,
+            ..Default::default()
+        };
+        let plan = lpatch_in(&app, "samp", &options).unwrap();
+
+        assert_eq!(snapshot(&app), before);
+        assert!(plan.clone_path.unwrap().ends_with("crates/samp"));
+        assert!(matches!(
+            plan.crate_path,
+            Some(PlannedCratePath::RequiresClone { .. })
+        ));
+        let [PlannedAction::CreateDir { path: dir }, PlannedAction::Clone {
+            url: clone_url,
+            reference,
+            ..
+        }, PlannedAction::WritePatch {
+            crate_name, source, ..
+        }] = plan.actions.as_slice()
+        else {
+            panic!("unexpected plan: {:?}", plan.actions);
+        };
+        assert!(dir.ends_with("crates"));
+        assert_eq!(clone_url, url);
+        assert_eq!(reference.as_deref(), Some("branch 'dev'"));
+        assert_eq!(crate_name, "samp");
+        assert_eq!(source, url);
+    }
+
+    #[test]
+    fn clones_local_repository_through_file_url() {
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("upstream").join("samp");
+        let repo = test_support::crate_repo(&source, "samp", 2);
+
+        let git_dir = source.join(".git");
+        let git_dir = git_dir.to_str().unwrap();
+        assert!(is_git_url(git_dir));
+        let url = normalize_local_git_url(git_dir).unwrap();
+        assert!(url.starts_with("file://"), "{url}");
+        assert!(is_git_url(&url));
+        assert_eq!(extract_crate_name_from_git_url(&url).unwrap(), "samp");
+
+        let target = tmp.path().join("crates").join("samp");
+        GitOperations::new().clone(&url, &target, None).unwrap();
+        assert!(target.join("Cargo.toml").exists());
+        assert_eq!(
+            git2::Repository::open(&target)
+                .unwrap()
+                .head()
+                .unwrap()
+                .target(),
+            repo.head().unwrap().target()
+        );
+    }
+
+    #[test]
+    fn link_keys_registry_dependencies_by_registry() {
+        let tmp = tempfile::tempdir().unwrap();
+        let external = tmp.path().join("external/samp");
+        test_support::project(
+            &external,
+            "[package]\nname = \"samp\"\nversion = \"0.1.0\"\n",
+        );
+        let app = tmp.path().join("app");
+        test_support::project(
+            &app,
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n\
+             [dependencies]\nsamp = { version = \"0.1\", registry = \"internal\" }\n",
+        );
+
+        let patches = test_support::in_dir(&app, || {
+            link_external_clone("samp", &external, "crates", OutputFormat::Human).unwrap();
+            CargoConfig::load().unwrap().list_patches()
+        });
+        assert_eq!(
+            patches,
+            [(
+                "internal".to_string(),
+                "samp".to_string(),
+                "crates/samp".to_string()
+            )]
+        );
+    }
+}
@@ -0,0 +1,273 @@
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// `cargo metadata --format-version 1` 输出中用到的部分
+#[derive(Debug, Deserialize)]
+pub struct CargoMetadata {
+    pub packages: Vec<MetadataPackage>,
+    pub workspace_members: Vec<String>,
+    pub resolve: Option<MetadataResolve>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MetadataPackage {
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub dependencies: Vec<MetadataDependency>,
+    #[serde(default)]
+    pub features: BTreeMap<String, Vec<String>>,
+    pub links: Option<String>,
+    pub manifest_path: Option<PathBuf>,
+    /// 包的来源，如 `registry+https://...` 或 `git+https://...#<commit>`，本地路径包为空
+    pub source: Option<String>,
+}
+
+/// 包清单中声明的一个依赖
+#[derive(Debug, Deserialize)]
+pub struct MetadataDependency {
+    pub name: String,
+    pub req: String,
+    /// `null` 为普通依赖，另有 `"dev"` 和 `"build"`
+    pub kind: Option<String>,
+    pub rename: Option<String>,
+    pub optional: bool,
+    pub uses_default_features: bool,
+    pub features: Vec<String>,
+    pub target: Option<String>,
+    /// 非 crates.io 依赖所在 registry 的 index 地址
+    pub registry: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MetadataResolve {
+    pub nodes: Vec<MetadataNode>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MetadataNode {
+    pub id: String,
+    pub dependencies: Vec<String>,
+    #[serde(default)]
+    pub deps: Vec<NodeDep>,
+}
+
+/// 带依赖类型的依赖边
+#[derive(Debug, Deserialize)]
+pub struct NodeDep {
+    pub pkg: String,
+    #[serde(default)]
+    pub dep_kinds: Vec<DepKindInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DepKindInfo {
+    /// `null` 为普通依赖，另有 `"dev"` 和 `"build"`
+    pub kind: Option<String>,
+}
+
+impl CargoMetadata {
+    /// 在指定目录中运行 `cargo metadata`
+    pub fn load(project_dir: &Path) -> Result<Self> {
+        let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+        let mut command = Command::new(cargo);
+        command
+            .args(["metadata", "--format-version", "1"])
+            .current_dir(project_dir);
+        Self::run(command)
+    }
+
+    /// 对指定的 `Cargo.toml` 运行 `cargo metadata`
+    pub fn load_manifest(manifest_path: &Path) -> Result<Self> {
+        let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+        let mut command = Command::new(cargo);
+        command
+            .args(["metadata", "--format-version", "1", "--manifest-path"])
+            .arg(manifest_path);
+        if let Some(dir) = manifest_path.parent() {
+            command.current_dir(dir);
+        }
+        Self::run(command)
+    }
+
+    /// 只读取指定清单自身的包信息（`--no-deps`，不解析依赖图）
+    pub fn load_package(manifest_path: &Path) -> Result<MetadataPackage> {
+        let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+        let mut command = Command::new(cargo);
+        command
+            .args([
+                "metadata",
+                "--format-version",
+                "1",
+                "--no-deps",
+                "--manifest-path",
+            ])
+            .arg(manifest_path);
+        let metadata = Self::run(command)?;
+
+        // workspace 根清单的 --no-deps 输出包含所有成员，按清单路径找出自身
+        let manifest_path =
+            std::fs::canonicalize(manifest_path).unwrap_or_else(|_| manifest_path.to_path_buf());
+        let mut packages = metadata.packages;
+        let index = packages
+            .iter()
+            .position(|p| p.manifest_path.as_deref() == Some(manifest_path.as_path()))
+            .or_else(|| (packages.len() == 1).then_some(0))
+            .ok_or_else(|| anyhow!("{} does not define a package", manifest_path.display()))?;
+        Ok(packages.swap_remove(index))
+    }
+
+    fn run(mut command: Command) -> Result<Self> {
+        let output = command.output().context("Failed to run 'cargo metadata'")?;
+
+        if !output.status.success() {
+            return Err(anyhow!(
+                "'cargo metadata' failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        serde_json::from_slice(&output.stdout).context("Failed to parse 'cargo metadata' output")
+    }
+
+    /// 指定 crate 的传递依赖（名称 -> 版本），不含 dev-dependencies 和它自身
+    ///
+    /// 优先从同名的 workspace 成员出发，找不到时返回 `None`
+    pub fn transitive_dependencies(&self, crate_name: &str) -> Option<BTreeMap<String, String>> {
+        let resolve = self.resolve.as_ref()?;
+        let root = self
+            .packages
+            .iter()
+            .filter(|p| p.name == crate_name)
+            .max_by_key(|p| self.workspace_members.contains(&p.id))?;
+
+        let packages: HashMap<&str, &MetadataPackage> =
+            self.packages.iter().map(|p| (p.id.as_str(), p)).collect();
+        let nodes: HashMap<&str, &MetadataNode> =
+            resolve.nodes.iter().map(|n| (n.id.as_str(), n)).collect();
+
+        let mut dependencies = BTreeMap::new();
+        let mut visited = HashSet::from([root.id.as_str()]);
+        let mut queue = VecDeque::from([root.id.as_str()]);
+        while let Some(current) = queue.pop_front() {
+            let Some(node) = nodes.get(current) else {
+                continue;
+            };
+            // 旧版本 cargo 没有 deps 字段，只能退回到不区分类型的 dependencies
+            let next: Vec<&str> = if node.deps.is_empty() {
+                node.dependencies.iter().map(String::as_str).collect()
+            } else {
+                node.deps
+                    .iter()
+                    .filter(|dep| {
+                        dep.dep_kinds.is_empty()
+                            || dep
+                                .dep_kinds
+                                .iter()
+                                .any(|k| k.kind.as_deref() != Some("dev"))
+                    })
+                    .map(|dep| dep.pkg.as_str())
+                    .collect()
+            };
+            for id in next {
+                if !visited.insert(id) {
+                    continue;
+                }
+                if let Some(package) = packages.get(id) {
+                    dependencies.insert(package.name.clone(), package.version.clone());
+                }
+                queue.push_back(id);
+            }
+        }
+        Some(dependencies)
+    }
+
+    /// 查找从 workspace 成员到指定 crate 的依赖路径（每个直接依赖者对应一条最短路径）
+    ///
+    /// 返回的每条路径都以 workspace 成员开头、以目标 crate 结尾，元素为 `name v版本`
+    pub fn dependent_paths(&self, crate_name: &str) -> Vec<Vec<String>> {
+        let Some(resolve) = &self.resolve else {
+            return Vec::new();
+        };
+
+        let labels: HashMap<&str, String> = self
+            .packages
+            .iter()
+            .map(|p| (p.id.as_str(), format!("{} v{}", p.name, p.version)))
+            .collect();
+        let targets: HashSet<&str> = self
+            .packages
+            .iter()
+            .filter(|p| p.name == crate_name)
+            .map(|p| p.id.as_str())
+            .collect();
+        let members: HashSet<&str> = self.workspace_members.iter().map(String::as_str).collect();
+
+        // 反向依赖图：被依赖者 -> 依赖者
+        let mut dependents: HashMap<&str, Vec<&str>> = HashMap::new();
+        for node in &resolve.nodes {
+            for dep in &node.dependencies {
+                dependents
+                    .entry(dep.as_str())
+                    .or_default()
+                    .push(node.id.as_str());
+            }
+        }
+
+        let mut paths = Vec::new();
+        for target in &targets {
+            let mut direct: Vec<&str> = dependents.get(target).cloned().unwrap_or_default();
+            direct.sort();
+            for dependent in direct {
+                if let Some(mut path) =
+                    Self::shortest_path_to_member(&dependents, dependent, &members)
+                {
+                    path.push(target);
+                    paths.push(
+                        path.iter()
+                            .map(|id| labels.get(id).cloned().unwrap_or_else(|| id.to_string()))
+                            .collect(),
+                    );
+                }
+            }
+        }
+        paths
+    }
+
+    /// 沿反向依赖图做 BFS，找到最近的 workspace 成员，返回成员到 `start` 的路径
+    fn shortest_path_to_member<'a>(
+        dependents: &HashMap<&'a str, Vec<&'a str>>,
+        start: &'a str,
+        members: &HashSet<&str>,
+    ) -> Option<Vec<&'a str>> {
+        let mut previous: HashMap<&str, &str> = HashMap::new();
+        let mut queue = VecDeque::from([start]);
+        let mut visited = HashSet::from([start]);
+
+        while let Some(current) = queue.pop_front() {
+            if members.contains(current) {
+                // 从成员沿 previous 走回 start 即为正向路径
+                let mut path = vec![current];
+                let mut node = current;
+                while let Some(next) = previous.get(node) {
+                    path.push(next);
+                    node = next;
+                }
+                return Some(path);
+            }
+
+            for &parent in dependents.get(current).into_iter().flatten() {
+                if visited.insert(parent) {
+                    previous.insert(parent, current);
+                    queue.push_back(parent);
+                }
+            }
+        }
+
+        None
+    }
+}
@@ -0,0 +1,89 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::CargoConfig;
+use crate::git::GitOperations;
+
+/// 所有补丁的汇总统计
+#[derive(Debug, Default, Serialize)]
+pub struct PatchMetrics {
+    pub total_patches: usize,
+    pub crates_io_patches: usize,
+    pub git_patches: usize,
+    pub missing_paths: usize,
+    pub clones: usize,
+    pub disk_usage_bytes: u64,
+    pub dirty_clones: usize,
+    pub clones_behind_upstream: usize,
+}
+
+/// 根据 `.cargo/config.toml` 中的补丁收集统计信息
+pub fn collect_metrics(config: &CargoConfig, git_ops: &GitOperations) -> Result<PatchMetrics> {
+    let mut metrics = PatchMetrics::default();
+    let mut clone_roots = BTreeSet::new();
+
+    for (source, _name, path) in config.list_patches() {
+        metrics.total_patches += 1;
+        if source == "crates-io" {
+            metrics.crates_io_patches += 1;
+        } else {
+            metrics.git_patches += 1;
+        }
+
+        let path = CargoConfig::resolve_patch_path(&path);
+        if !path.exists() {
+            metrics.missing_paths += 1;
+            continue;
+        }
+
+        // 同一个仓库中的多个 crate 只统计一次
+        let root = git2::Repository::discover(&path)
+            .ok()
+            .and_then(|repo| repo.workdir().map(Path::to_path_buf))
+            .unwrap_or(path);
+        clone_roots.insert(root);
+    }
+
+    for root in &clone_roots {
+        metrics.clones += 1;
+        metrics.disk_usage_bytes += dir_size(root);
+
+        if git_ops.is_dirty(root).unwrap_or(false) {
+            metrics.dirty_clones += 1;
+        }
+        if let Ok(Some((_, behind))) = git_ops.ahead_behind_upstream(root) {
+            if behind > 0 {
+                metrics.clones_behind_upstream += 1;
+            }
+        }
+    }
+
+    Ok(metrics)
+}
+
+/// 递归计算目录占用的字节数（不跟随符号链接）
+fn dir_size(path: &Path) -> u64 {
+    let mut total = 0;
+    let mut stack: Vec<PathBuf> = vec![path.to_path_buf()];
+
+    while let Some(dir) = stack.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(metadata) = entry.path().symlink_metadata() else {
+                continue;
+            };
+            if metadata.is_dir() {
+                stack.push(entry.path());
+            } else {
+                total += metadata.len();
+            }
+        }
+    }
+
+    total
+}
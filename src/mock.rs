@@ -0,0 +1,748 @@
+use anyhow::{anyhow, Context, Result};
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// 根据 rustdoc JSON 生成的桩 crate
+#[derive(Debug)]
+pub struct MockCrate {
+    pub version: String,
+    pub lib_rs: String,
+    /// 无法生成的公开条目（路径 + 类型）
+    pub skipped: Vec<String>,
+}
+
+/// 用 `cargo rustdoc --output-format json` 导出依赖的公开 API，返回 JSON 文件路径
+///
+/// rustdoc JSON 仍是不稳定功能，需要 nightly 工具链
+pub fn generate_rustdoc_json(
+    project_dir: &Path,
+    crate_name: &str,
+    toolchain: &str,
+    target_dir: &Path,
+) -> Result<PathBuf> {
+    info!("📚 Generating rustdoc JSON for {crate_name} with the {toolchain} toolchain...");
+    let output = Command::new("cargo")
+        .arg(format!("+{toolchain}"))
+        .args(["rustdoc", "--lib", "-p", crate_name])
+        .arg("--target-dir")
+        .arg(target_dir)
+        .args(["--", "-Z", "unstable-options", "--output-format", "json"])
+        .current_dir(project_dir)
+        .output()
+        .context("Failed to run cargo rustdoc")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "cargo rustdoc failed for '{}':\n{}",
+            crate_name,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let json_path = target_dir
+        .join("doc")
+        .join(format!("{}.json", crate_name.replace('-', "_")));
+    if !json_path.exists() {
+        return Err(anyhow!("rustdoc did not produce {}", json_path.display()));
+    }
+    Ok(json_path)
+}
+
+/// 解析 rustdoc JSON，为每个公开函数生成 `todo!()` 函数体
+pub fn render_mock(json_path: &Path) -> Result<MockCrate> {
+    let content = fs::read_to_string(json_path)
+        .with_context(|| format!("Failed to read {}", json_path.display()))?;
+    let doc: Value = serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse {}", json_path.display()))?;
+
+    let index = doc
+        .get("index")
+        .and_then(Value::as_object)
+        .ok_or_else(|| anyhow!("rustdoc JSON has no 'index'"))?;
+    let root = doc
+        .get("root")
+        .ok_or_else(|| anyhow!("rustdoc JSON has no 'root'"))?;
+    let version = doc
+        .get("crate_version")
+        .and_then(Value::as_str)
+        .unwrap_or("0.0.0")
+        .to_string();
+
+    let mut renderer = Renderer {
+        index,
+        out: String::new(),
+        skipped: Vec::new(),
+        depth: 0,
+    };
+    renderer.out.push_str(
+        "//! 由 `cargo lpatch create-mock` 根据 rustdoc JSON 生成的桩 crate\n\
+         //!\n\
+         //! 函数体均为 `todo!()`，trait 实现不会被生成\n\
+         #![allow(unused_variables, dead_code)]\n",
+    );
+    let root_item = renderer.item(root)?;
+    renderer.module_items(root_item, "")?;
+
+    Ok(MockCrate {
+        version,
+        lib_rs: renderer.out,
+        skipped: renderer.skipped,
+    })
+}
+
+/// 写出桩 crate 的 `Cargo.toml` 与 `src/lib.rs`
+pub fn write_mock_crate(dir: &Path, crate_name: &str, mock: &MockCrate) -> Result<()> {
+    fs::create_dir_all(dir.join("src"))
+        .with_context(|| format!("Failed to create {}", dir.display()))?;
+
+    let manifest = format!(
+        "[package]\nname = \"{}\"\nversion = \"{}\"\nedition = \"2021\"\npublish = false\n\n[dependencies]\n",
+        crate_name, mock.version
+    );
+    fs::write(dir.join("Cargo.toml"), manifest)
+        .with_context(|| format!("Failed to write {}/Cargo.toml", dir.display()))?;
+    fs::write(dir.join("src").join("lib.rs"), &mock.lib_rs)
+        .with_context(|| format!("Failed to write {}/src/lib.rs", dir.display()))?;
+    Ok(())
+}
+
+struct Renderer<'a> {
+    index: &'a Map<String, Value>,
+    out: String,
+    skipped: Vec<String>,
+    depth: usize,
+}
+
+impl<'a> Renderer<'a> {
+    fn item(&self, id: &Value) -> Result<&'a Value> {
+        let key = match id {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        };
+        self.index
+            .get(&key)
+            .ok_or_else(|| anyhow!("Item {} is missing from the rustdoc index", key))
+    }
+
+    fn line(&mut self, text: &str) {
+        for _ in 0..self.depth {
+            self.out.push_str("    ");
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn skip(&mut self, path: &str, kind: &str) {
+        self.line(&format!("// create-mock: skipped {kind} `{path}`"));
+        self.skipped.push(format!("{path} ({kind})"));
+    }
+
+    /// 输出模块中的所有公开条目
+    fn module_items(&mut self, module: &Value, prefix: &str) -> Result<()> {
+        let items = module["inner"]["module"]["items"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+        for id in &items {
+            let item = self.item(id)?;
+            if item["visibility"] != "public" {
+                continue;
+            }
+            let name = item["name"].as_str().unwrap_or_default();
+            let path = if prefix.is_empty() {
+                name.to_string()
+            } else {
+                format!("{prefix}::{name}")
+            };
+            let Some((kind, inner)) = item["inner"].as_object().and_then(|o| o.iter().next())
+            else {
+                continue;
+            };
+
+            self.out.push('\n');
+            match kind.as_str() {
+                "module" => {
+                    if inner["is_stripped"].as_bool().unwrap_or(false) {
+                        continue;
+                    }
+                    self.line(&format!("pub mod {name} {{"));
+                    self.depth += 1;
+                    self.module_items(item, &path)?;
+                    self.depth -= 1;
+                    self.line("}");
+                }
+                "function" => {
+                    let sig = self.function(name, inner, "pub ");
+                    self.line(&format!("{sig} {{"));
+                    self.line("    todo!()");
+                    self.line("}");
+                }
+                "struct" => self.struct_item(name, inner)?,
+                "enum" => self.enum_item(name, inner)?,
+                "trait" => self.trait_item(name, inner, &path)?,
+                "type_alias" => {
+                    let (params, where_clause) = self.generics(&inner["generics"]);
+                    let ty = self.ty(&inner["type"]);
+                    self.line(&format!("pub type {name}{params}{where_clause} = {ty};"));
+                }
+                "use" => {
+                    // 只重新导出本 crate 内的条目，外部 crate 不在桩的依赖中
+                    let local = inner["id"]
+                        .as_u64()
+                        .and_then(|id| self.index.get(&id.to_string()))
+                        .is_some_and(|target| target["crate_id"] == 0);
+                    let source = inner["source"].as_str().unwrap_or_default();
+                    let alias = inner["name"].as_str().unwrap_or_default();
+                    if !local {
+                        self.skip(source, "re-export");
+                    } else if inner["is_glob"].as_bool().unwrap_or(false) {
+                        self.line(&format!("pub use {source}::*;"));
+                    } else if source.rsplit("::").next() == Some(alias) {
+                        self.line(&format!("pub use {source};"));
+                    } else {
+                        self.line(&format!("pub use {source} as {alias};"));
+                    }
+                }
+                other => self.skip(&path, other),
+            }
+        }
+        Ok(())
+    }
+
+    fn struct_item(&mut self, name: &str, inner: &Value) -> Result<()> {
+        let generics = &inner["generics"];
+        let (params, where_clause) = self.generics(generics);
+        let marker = self.private_marker(generics);
+
+        let kind = &inner["kind"];
+        if kind == "unit" {
+            self.line(&format!("pub struct {name}{params}{where_clause};"));
+        } else if let Some(fields) = kind["tuple"].as_array() {
+            let mut rendered = Vec::new();
+            for field in fields {
+                if field.is_null() {
+                    rendered.push(marker.clone());
+                } else {
+                    let field = self.item(field)?;
+                    rendered.push(format!("pub {}", self.ty(&field["inner"]["struct_field"])));
+                }
+            }
+            self.line(&format!(
+                "pub struct {name}{params}({}){where_clause};",
+                rendered.join(", ")
+            ));
+        } else {
+            let plain = &kind["plain"];
+            self.line(&format!("pub struct {name}{params}{where_clause} {{"));
+            for field in plain["fields"].as_array().into_iter().flatten() {
+                let field = self.item(field)?;
+                let ty = self.ty(&field["inner"]["struct_field"]);
+                let field_name = field["name"].as_str().unwrap_or_default();
+                self.line(&format!("    pub {field_name}: {ty},"));
+            }
+            if plain["has_stripped_fields"].as_bool().unwrap_or(false) {
+                self.line(&format!("    _private: {marker},"));
+            }
+            self.line("}");
+        }
+
+        self.inherent_impls(inner)
+    }
+
+    fn enum_item(&mut self, name: &str, inner: &Value) -> Result<()> {
+        let (params, where_clause) = self.generics(&inner["generics"]);
+        self.line(&format!("pub enum {name}{params}{where_clause} {{"));
+        for variant in inner["variants"].as_array().into_iter().flatten() {
+            let variant = self.item(variant)?;
+            let variant_name = variant["name"].as_str().unwrap_or_default();
+            let body = &variant["inner"]["variant"];
+            let discriminant = body["discriminant"]["expr"]
+                .as_str()
+                .map(|expr| format!(" = {expr}"))
+                .unwrap_or_default();
+
+            let kind = &body["kind"];
+            if let Some(fields) = kind["tuple"].as_array() {
+                let mut rendered = Vec::new();
+                for field in fields.iter().filter(|f| !f.is_null()) {
+                    rendered.push(self.ty(&self.item(field)?["inner"]["struct_field"]));
+                }
+                self.line(&format!("    {variant_name}({}),", rendered.join(", ")));
+            } else if let Some(fields) = kind["struct"]["fields"].as_array() {
+                let mut rendered = Vec::new();
+                for field in fields {
+                    let field = self.item(field)?;
+                    rendered.push(format!(
+                        "{}: {}",
+                        field["name"].as_str().unwrap_or_default(),
+                        self.ty(&field["inner"]["struct_field"])
+                    ));
+                }
+                self.line(&format!(
+                    "    {variant_name} {{ {} }},",
+                    rendered.join(", ")
+                ));
+            } else {
+                self.line(&format!("    {variant_name}{discriminant},"));
+            }
+        }
+        self.line("}");
+
+        self.inherent_impls(inner)
+    }
+
+    fn trait_item(&mut self, name: &str, inner: &Value, path: &str) -> Result<()> {
+        let (params, where_clause) = self.generics(&inner["generics"]);
+        let bounds = self.bounds(&inner["bounds"]);
+        let supertraits = if bounds.is_empty() {
+            String::new()
+        } else {
+            format!(": {bounds}")
+        };
+        let unsafety = if inner["is_unsafe"].as_bool().unwrap_or(false) {
+            "unsafe "
+        } else {
+            ""
+        };
+        self.line(&format!(
+            "pub {unsafety}trait {name}{params}{supertraits}{where_clause} {{"
+        ));
+        self.depth += 1;
+        for id in inner["items"].as_array().into_iter().flatten() {
+            let item = self.item(id)?;
+            let item_name = item["name"].as_str().unwrap_or_default();
+            let Some((kind, body)) = item["inner"].as_object().and_then(|o| o.iter().next()) else {
+                continue;
+            };
+            match kind.as_str() {
+                "function" => {
+                    let sig = self.function(item_name, body, "");
+                    if body["has_body"].as_bool().unwrap_or(false) {
+                        self.line(&format!("{sig} {{"));
+                        self.line("    todo!()");
+                        self.line("}");
+                    } else {
+                        self.line(&format!("{sig};"));
+                    }
+                }
+                "assoc_type" => {
+                    let bounds = self.bounds(&body["bounds"]);
+                    let bounds = if bounds.is_empty() {
+                        String::new()
+                    } else {
+                        format!(": {bounds}")
+                    };
+                    self.line(&format!("type {item_name}{bounds};"));
+                }
+                "assoc_const" => {
+                    let ty = self.ty(&body["type"]);
+                    self.line(&format!("const {item_name}: {ty};"));
+                }
+                other => self.skip(&format!("{path}::{item_name}"), other),
+            }
+        }
+        self.depth -= 1;
+        self.line("}");
+        Ok(())
+    }
+
+    /// 输出类型的固有 impl 块，trait 实现不会被生成
+    fn inherent_impls(&mut self, inner: &Value) -> Result<()> {
+        for id in inner["impls"].as_array().into_iter().flatten() {
+            let item = self.item(id)?;
+            let imp = &item["inner"]["impl"];
+            if !imp["trait"].is_null() || imp["is_synthetic"].as_bool().unwrap_or(false) {
+                continue;
+            }
+
+            let methods: Vec<&Value> = imp["items"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|id| self.item(id).ok())
+                .filter(|m| m["visibility"] == "public" && !m["inner"]["function"].is_null())
+                .collect();
+            if methods.is_empty() {
+                continue;
+            }
+
+            let (params, where_clause) = self.generics(&imp["generics"]);
+            let for_type = self.ty(&imp["for"]);
+            self.out.push('\n');
+            self.line(&format!("impl{params} {for_type}{where_clause} {{"));
+            self.depth += 1;
+            for method in methods {
+                let name = method["name"].as_str().unwrap_or_default();
+                let sig = self.function(name, &method["inner"]["function"], "pub ");
+                self.line(&format!("{sig} {{"));
+                self.line("    todo!()");
+                self.line("}");
+            }
+            self.depth -= 1;
+            self.line("}");
+        }
+        Ok(())
+    }
+
+    /// 被隐藏的私有字段用 PhantomData 占位，以免泛型参数未被使用
+    fn private_marker(&self, generics: &Value) -> String {
+        let used: Vec<String> = generics["params"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|param| {
+                let name = param["name"].as_str()?;
+                let kind = &param["kind"];
+                if !kind["lifetime"].is_null() {
+                    Some(format!("&{name} ()"))
+                } else if !kind["type"].is_null() {
+                    Some(name.to_string())
+                } else {
+                    None
+                }
+            })
+            .collect();
+        match used.len() {
+            0 => "()".to_string(),
+            1 => format!("std::marker::PhantomData<{}>", used[0]),
+            _ => format!("std::marker::PhantomData<({})>", used.join(", ")),
+        }
+    }
+
+    /// 生成函数签名（不含函数体）
+    fn function(&self, name: &str, function: &Value, vis: &str) -> String {
+        let header = &function["header"];
+        let mut qualifiers = String::new();
+        if header["is_const"].as_bool().unwrap_or(false) {
+            qualifiers.push_str("const ");
+        }
+        if header["is_async"].as_bool().unwrap_or(false) {
+            qualifiers.push_str("async ");
+        }
+        if header["is_unsafe"].as_bool().unwrap_or(false) {
+            qualifiers.push_str("unsafe ");
+        }
+        qualifiers.push_str(&abi(&header["abi"]));
+
+        let (params, where_clause) = self.generics(&function["generics"]);
+        let sig = &function["sig"];
+        let inputs: Vec<String> = sig["inputs"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|input| {
+                let arg = input[0].as_str().unwrap_or("_");
+                let ty = &input[1];
+                if arg == "self" {
+                    self_param(ty).unwrap_or_else(|| format!("self: {}", self.ty(ty)))
+                } else {
+                    format!("{}: {}", arg, self.ty(ty))
+                }
+            })
+            .collect();
+        let output = if sig["output"].is_null() {
+            String::new()
+        } else {
+            format!(" -> {}", self.ty(&sig["output"]))
+        };
+
+        format!(
+            "{vis}{qualifiers}fn {name}{params}({}){output}{where_clause}",
+            inputs.join(", ")
+        )
+    }
+
+    /// 返回 (`<...>` 参数列表, ` where ...` 子句)
+    fn generics(&self, generics: &Value) -> (String, String) {
+        let params: Vec<String> = generics["params"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|param| {
+                let name = param["name"].as_str()?;
+                let kind = &param["kind"];
+                if let Some(outlives) = kind["lifetime"]["outlives"].as_array() {
+                    let outlives: Vec<&str> = outlives.iter().filter_map(Value::as_str).collect();
+                    if outlives.is_empty() {
+                        Some(name.to_string())
+                    } else {
+                        Some(format!("{name}: {}", outlives.join(" + ")))
+                    }
+                } else if !kind["type"].is_null() {
+                    // `impl Trait` 参数由编译器合成，不需要写出
+                    if kind["type"]["is_synthetic"].as_bool().unwrap_or(false) {
+                        return None;
+                    }
+                    let bounds = self.bounds(&kind["type"]["bounds"]);
+                    let mut param = name.to_string();
+                    if !bounds.is_empty() {
+                        param.push_str(&format!(": {bounds}"));
+                    }
+                    if !kind["type"]["default"].is_null() {
+                        param.push_str(&format!(" = {}", self.ty(&kind["type"]["default"])));
+                    }
+                    Some(param)
+                } else {
+                    let ty = self.ty(&kind["const"]["type"]);
+                    Some(format!("const {name}: {ty}"))
+                }
+            })
+            .collect();
+
+        let predicates: Vec<String> = generics["where_predicates"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|predicate| {
+                if let Some(bound) = predicate.get("bound_predicate") {
+                    let bounds = self.bounds(&bound["bounds"]);
+                    Some(format!("{}: {}", self.ty(&bound["type"]), bounds))
+                } else if let Some(lifetime) = predicate.get("lifetime_predicate") {
+                    let outlives: Vec<&str> = lifetime["outlives"]
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .filter_map(Value::as_str)
+                        .collect();
+                    Some(format!(
+                        "{}: {}",
+                        lifetime["lifetime"].as_str().unwrap_or("'_"),
+                        outlives.join(" + ")
+                    ))
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        let params = if params.is_empty() {
+            String::new()
+        } else {
+            format!("<{}>", params.join(", "))
+        };
+        let where_clause = if predicates.is_empty() {
+            String::new()
+        } else {
+            format!(" where {}", predicates.join(", "))
+        };
+        (params, where_clause)
+    }
+
+    fn bounds(&self, bounds: &Value) -> String {
+        bounds
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|bound| {
+                if let Some(trait_bound) = bound.get("trait_bound") {
+                    let maybe = if trait_bound["modifier"] == "maybe" {
+                        "?"
+                    } else {
+                        ""
+                    };
+                    Some(format!("{maybe}{}", self.path(&trait_bound["trait"])))
+                } else {
+                    bound
+                        .get("outlives")
+                        .and_then(Value::as_str)
+                        .map(str::to_string)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" + ")
+    }
+
+    fn path(&self, path: &Value) -> String {
+        let name = path["path"].as_str().unwrap_or("_");
+        let args = &path["args"];
+        if let Some(angle) = args.get("angle_bracketed") {
+            let mut rendered: Vec<String> = angle["args"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|arg| {
+                    if let Some(lifetime) = arg.get("lifetime").and_then(Value::as_str) {
+                        lifetime.to_string()
+                    } else if let Some(ty) = arg.get("type") {
+                        self.ty(ty)
+                    } else if let Some(expr) = arg["const"]["expr"].as_str() {
+                        expr.to_string()
+                    } else {
+                        "_".to_string()
+                    }
+                })
+                .collect();
+            for constraint in angle["constraints"].as_array().into_iter().flatten() {
+                let assoc = constraint["name"].as_str().unwrap_or_default();
+                let binding = &constraint["binding"];
+                if let Some(ty) = binding["equality"].get("type") {
+                    rendered.push(format!("{assoc} = {}", self.ty(ty)));
+                } else if let Some(bounds) = binding.get("constraint") {
+                    rendered.push(format!("{assoc}: {}", self.bounds(bounds)));
+                }
+            }
+            if rendered.is_empty() {
+                name.to_string()
+            } else {
+                format!("{name}<{}>", rendered.join(", "))
+            }
+        } else if let Some(parenthesized) = args.get("parenthesized") {
+            let inputs: Vec<String> = parenthesized["inputs"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|ty| self.ty(ty))
+                .collect();
+            let output = if parenthesized["output"].is_null() {
+                String::new()
+            } else {
+                format!(" -> {}", self.ty(&parenthesized["output"]))
+            };
+            format!("{name}({}){output}", inputs.join(", "))
+        } else {
+            name.to_string()
+        }
+    }
+
+    /// 将 rustdoc JSON 中的类型还原为源码形式
+    fn ty(&self, ty: &Value) -> String {
+        let Some((kind, inner)) = ty.as_object().and_then(|o| o.iter().next()) else {
+            return match ty.as_str() {
+                Some("infer") => "_".to_string(),
+                _ => "()".to_string(),
+            };
+        };
+        match kind.as_str() {
+            "resolved_path" => self.path(inner),
+            "primitive" | "generic" => inner.as_str().unwrap_or("_").to_string(),
+            "tuple" => {
+                let elems: Vec<String> = inner
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|t| self.ty(t))
+                    .collect();
+                if elems.len() == 1 {
+                    format!("({},)", elems[0])
+                } else {
+                    format!("({})", elems.join(", "))
+                }
+            }
+            "slice" => format!("[{}]", self.ty(inner)),
+            "array" => format!(
+                "[{}; {}]",
+                self.ty(&inner["type"]),
+                inner["len"].as_str().unwrap_or("0")
+            ),
+            "borrowed_ref" => {
+                let lifetime = inner["lifetime"]
+                    .as_str()
+                    .map(|l| format!("{l} "))
+                    .unwrap_or_default();
+                let mutability = if inner["is_mutable"].as_bool().unwrap_or(false) {
+                    "mut "
+                } else {
+                    ""
+                };
+                format!("&{lifetime}{mutability}{}", self.ty(&inner["type"]))
+            }
+            "raw_pointer" => {
+                let mutability = if inner["is_mutable"].as_bool().unwrap_or(false) {
+                    "mut"
+                } else {
+                    "const"
+                };
+                format!("*{mutability} {}", self.ty(&inner["type"]))
+            }
+            "impl_trait" => format!("impl {}", self.bounds(inner)),
+            "dyn_trait" => {
+                let mut traits: Vec<String> = inner["traits"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|t| self.path(&t["trait"]))
+                    .collect();
+                if let Some(lifetime) = inner["lifetime"].as_str() {
+                    traits.push(lifetime.to_string());
+                }
+                format!("dyn {}", traits.join(" + "))
+            }
+            "function_pointer" => {
+                let sig = &inner["sig"];
+                let inputs: Vec<String> = sig["inputs"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|input| self.ty(&input[1]))
+                    .collect();
+                let output = if sig["output"].is_null() {
+                    String::new()
+                } else {
+                    format!(" -> {}", self.ty(&sig["output"]))
+                };
+                let unsafety = if inner["header"]["is_unsafe"].as_bool().unwrap_or(false) {
+                    "unsafe "
+                } else {
+                    ""
+                };
+                format!(
+                    "{unsafety}{}fn({}){output}",
+                    abi(&inner["header"]["abi"]),
+                    inputs.join(", ")
+                )
+            }
+            "qualified_path" => {
+                let self_type = self.ty(&inner["self_type"]);
+                let name = inner["name"].as_str().unwrap_or("_");
+                if inner["trait"].is_null() {
+                    format!("{self_type}::{name}")
+                } else {
+                    format!("<{self_type} as {}>::{name}", self.path(&inner["trait"]))
+                }
+            }
+            _ => "()".to_string(),
+        }
+    }
+}
+
+/// `self`、`&self`、`&mut self` 的简写形式
+fn self_param(ty: &Value) -> Option<String> {
+    if ty["generic"] == "Self" {
+        return Some("self".to_string());
+    }
+    let reference = ty.get("borrowed_ref")?;
+    if reference["type"]["generic"] != "Self" {
+        return None;
+    }
+    let lifetime = reference["lifetime"]
+        .as_str()
+        .map(|l| format!("{l} "))
+        .unwrap_or_default();
+    let mutability = if reference["is_mutable"].as_bool().unwrap_or(false) {
+        "mut "
+    } else {
+        ""
+    };
+    Some(format!("&{lifetime}{mutability}self"))
+}
+
+fn abi(abi: &Value) -> String {
+    match abi {
+        Value::String(s) if s == "Rust" => String::new(),
+        Value::String(s) => format!("extern \"{s}\" "),
+        Value::Object(o) => o
+            .iter()
+            .next()
+            .map(|(name, options)| {
+                let unwind = if options["unwind"] == true {
+                    "-unwind"
+                } else {
+                    ""
+                };
+                format!("extern \"{name}{unwind}\" ")
+            })
+            .unwrap_or_default(),
+        _ => String::new(),
+    }
+}
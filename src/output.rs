@@ -0,0 +1,1159 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+
+use crate::audit::DependencyAudit;
+use crate::bump::VersionBump;
+use crate::cargo_toml::{DependencyInfo, DependencySection, DependencyType};
+use crate::compat::CompatReport;
+use crate::config::CargoConfig;
+use crate::coverage::{self, CoverageReport};
+use crate::dedup::DedupGroup;
+use crate::doctor::DiagnosticIssue;
+use crate::dry_run::DryRunPlan;
+use crate::git::{CommitSummary, FileStatus};
+use crate::github::RepositoryHit;
+use crate::local_registry::LocalRegistryReport;
+use crate::metrics::PatchMetrics;
+use crate::profile::MemoryReport;
+use crate::scan::CrateReference;
+use crate::timings::{Timings, TimingsReport};
+use crate::tree::{self, PatchTreeNode};
+
+/// 命令输出格式，由全局 `--output` 参数决定
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Human,
+    Json,
+}
+
+impl OutputFormat {
+    pub fn parse(s: &str) -> Self {
+        match s {
+            "json" => OutputFormat::Json,
+            _ => OutputFormat::Human,
+        }
+    }
+
+    pub fn reporter(self) -> Box<dyn Reporter> {
+        match self {
+            OutputFormat::Human => Box::new(HumanReporter),
+            OutputFormat::Json => Box::new(JsonReporter),
+        }
+    }
+}
+
+/// `list` 输出中的一项
+#[derive(Debug, Clone, Serialize)]
+pub struct PatchEntry {
+    pub source: String,
+    pub name: String,
+    pub path: String,
+    pub path_exists: bool,
+}
+
+/// `status` 输出中的一项，`files` 为补丁所在仓库中有变化的文件
+#[derive(Debug, Clone, Serialize)]
+pub struct StatusEntry {
+    pub name: String,
+    pub path: String,
+    pub path_exists: bool,
+    /// 补丁失效的原因，`None` 表示补丁有效
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub problem: Option<String>,
+    pub files: Vec<FileStatus>,
+}
+
+/// `check-updates` 输出中的一项
+#[derive(Debug, Clone, Serialize)]
+pub struct UpdateCheck {
+    pub name: String,
+    pub path: String,
+    /// 本地领先/落后上游的提交数，没有上游分支或检查失败时为空
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ahead: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub behind: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// `show-patch-source` 中的一个 patch 条目
+#[derive(Debug, Clone, Serialize)]
+pub struct PatchSourceEntry {
+    pub source: String,
+    pub path: String,
+    /// 源与依赖的实际来源一致，Cargo 会使用这个 patch
+    pub matches: bool,
+}
+
+/// `show-patch-source` 的结果
+#[derive(Debug, Clone, Serialize)]
+pub struct PatchSourceReport {
+    pub name: String,
+    pub patches: Vec<PatchSourceEntry>,
+    /// 依赖实际来源对应的 patch 源，路径依赖或无法确定时为空
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected_source: Option<String>,
+    pub reason: String,
+    /// cargo metadata 是否将该 crate 解析到了补丁目录，metadata 不可用时为空
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub applied: Option<bool>,
+    /// cargo metadata 中该 crate 的来源（补丁生效时为补丁路径）
+    pub resolved: Vec<String>,
+}
+
+/// `update`/`auto-update` 的结果
+#[derive(Debug, Default, Serialize)]
+pub struct UpdateReport {
+    pub updated: usize,
+    /// 因工作区有未提交修改而跳过
+    pub dirty: usize,
+    /// 因路径不存在、历史分叉等其他原因跳过
+    pub skipped: usize,
+    pub failed: usize,
+    /// 快进了的补丁及其引入的提交
+    pub updates: Vec<PatchUpdate>,
+    /// 是否在标准输出打印每次快进引入的提交（`--changelog`）
+    #[serde(skip)]
+    pub changelog: bool,
+}
+
+/// 一次快进更新
+#[derive(Debug, Serialize)]
+pub struct PatchUpdate {
+    pub name: String,
+    pub from: String,
+    pub to: String,
+    pub commits: Vec<CommitSummary>,
+}
+
+/// `diff` 输出中一个仓库的未提交修改
+#[derive(Debug, Serialize)]
+pub struct PatchDiff {
+    pub name: String,
+    pub repository: String,
+    pub diff: String,
+}
+
+/// `scan` 的结果
+#[derive(Debug, Serialize)]
+pub struct ReferenceReport {
+    pub name: String,
+    pub references: Vec<CrateReference>,
+}
+
+/// `env` 的结果：补丁会话使用的环境变量
+#[derive(Debug, Serialize)]
+pub struct EnvExport {
+    pub project_dir: String,
+    pub patches: Vec<PatchEntry>,
+    pub env: BTreeMap<String, String>,
+}
+
+/// `create-mock` 的结果
+#[derive(Debug, Serialize)]
+pub struct MockReport {
+    pub name: String,
+    pub version: String,
+    pub path: String,
+    pub source: String,
+    /// 无法生成 mock 的条目
+    pub skipped: Vec<String>,
+}
+
+/// `unpatch` 的结果
+#[derive(Debug, Serialize)]
+pub struct UnpatchReport {
+    pub name: String,
+    /// 删除了补丁的 patch 源
+    pub sources: Vec<String>,
+    /// `--remove-clone` 删除的克隆目录
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_clone: Option<String>,
+}
+
+/// `init` 的结果
+#[derive(Debug, Serialize)]
+pub struct InitReport {
+    pub project_dir: String,
+    pub dir: String,
+    /// 本次新建的目录和文件
+    pub created: Vec<String>,
+    /// 本次加入 `.gitignore` 的条目
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gitignore_entry: Option<String>,
+}
+
+/// `reset` 的结果
+#[derive(Debug, Default, Serialize)]
+pub struct ResetReport {
+    pub removed: usize,
+    /// `--and-delete` 删除的克隆
+    pub deleted: Vec<String>,
+    /// 因有未提交修改而保留的克隆
+    pub kept: Vec<String>,
+}
+
+/// `clean-stale` 的结果
+#[derive(Debug, Serialize)]
+pub struct CleanStaleReport {
+    pub clone_dir: String,
+    pub clone_dir_exists: bool,
+    /// 不再被任何补丁引用的克隆
+    pub stale: Vec<String>,
+    pub deleted: Vec<String>,
+}
+
+/// `global-cache enable/disable` 的结果
+#[derive(Debug, Serialize)]
+pub struct GlobalCacheReport {
+    pub enabled: bool,
+    /// 用户配置是否被修改（关闭一个未启用的缓存时为 false）
+    pub changed: bool,
+    pub config_path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_dir: Option<String>,
+}
+
+/// `link` 的结果
+#[derive(Debug, Serialize)]
+pub struct LinkReport {
+    pub name: String,
+    pub link: String,
+    pub target: String,
+    pub crate_path: String,
+    pub source: String,
+}
+
+/// `init-hooks` 为一个补丁安装的钩子
+#[derive(Debug, Serialize)]
+pub struct HookInstall {
+    pub name: String,
+    pub path: String,
+}
+
+/// `init-hooks` 中安装失败的一个补丁
+#[derive(Debug, Serialize)]
+pub struct HookFailure {
+    pub name: String,
+    pub error: String,
+}
+
+/// `init-hooks` 的结果
+#[derive(Debug, Serialize)]
+pub struct HooksReport {
+    pub hook: String,
+    /// 同一仓库中的多个 crate 共用钩子，只记录第一个
+    pub installed: Vec<HookInstall>,
+    pub failed: Vec<HookFailure>,
+}
+
+/// `dedup` 的结果
+#[derive(Debug, Serialize)]
+pub struct DedupReport {
+    pub dry_run: bool,
+    pub groups: Vec<DedupGroup>,
+    /// 已合并并删除的克隆
+    pub removed: Vec<String>,
+}
+
+/// `[replace]` 表中的一项
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplacementEntry {
+    pub spec: String,
+    pub name: String,
+}
+
+/// 可选依赖及启用它的 feature
+#[derive(Debug, Clone, Serialize)]
+pub struct OptionalDependency {
+    #[serde(flatten)]
+    pub dependency: DependencyInfo,
+    pub features: Vec<String>,
+}
+
+/// `--analyze` 的结果，必需依赖按类型分组，可选依赖单独列出
+#[derive(Debug, Default, Serialize)]
+pub struct DependencyAnalysis {
+    pub version: Vec<DependencyInfo>,
+    pub git: Vec<DependencyInfo>,
+    pub path: Vec<DependencyInfo>,
+    pub optional: Vec<OptionalDependency>,
+    pub replace: Vec<ReplacementEntry>,
+}
+
+impl DependencyAnalysis {
+    pub fn total(&self) -> usize {
+        self.version.len() + self.git.len() + self.path.len() + self.optional.len()
+    }
+}
+
+/// 将命令结果呈现给用户，业务逻辑只负责收集数据
+pub trait Reporter {
+    fn patches(&self, patches: &[PatchEntry]) -> Result<()>;
+    fn analysis(&self, analysis: &DependencyAnalysis) -> Result<()>;
+    fn metrics(&self, metrics: &PatchMetrics) -> Result<()>;
+    fn plan(&self, plan: &DryRunPlan) -> Result<()>;
+    fn compat(&self, report: &CompatReport) -> Result<()>;
+    fn status(&self, entries: &[StatusEntry]) -> Result<()>;
+    fn update_checks(&self, checks: &[UpdateCheck]) -> Result<()>;
+    fn diagnostics(&self, issues: &[DiagnosticIssue]) -> Result<()>;
+    fn dependency_audit(&self, audit: &DependencyAudit) -> Result<()>;
+    fn memory_profile(&self, report: &MemoryReport) -> Result<()>;
+    fn patch_search(&self, hits: &[RepositoryHit]) -> Result<()>;
+    fn version_bump(&self, bump: &VersionBump) -> Result<()>;
+    fn coverage(&self, report: &CoverageReport) -> Result<()>;
+    fn local_registry(&self, report: &LocalRegistryReport) -> Result<()>;
+    fn patch_source(&self, report: &PatchSourceReport) -> Result<()>;
+    fn timings(&self, report: &TimingsReport) -> Result<()>;
+    fn updates(&self, report: &UpdateReport) -> Result<()>;
+    fn diffs(&self, diffs: &[PatchDiff]) -> Result<()>;
+    fn references(&self, report: &ReferenceReport) -> Result<()>;
+    fn patch_tree(&self, nodes: &[PatchTreeNode]) -> Result<()>;
+    fn env_export(&self, export: &EnvExport) -> Result<()>;
+    fn extracted(&self, plan: &DryRunPlan) -> Result<()>;
+    fn mock_crate(&self, report: &MockReport) -> Result<()>;
+    fn unpatched(&self, report: &UnpatchReport) -> Result<()>;
+    fn init(&self, report: &InitReport) -> Result<()>;
+    fn reset(&self, report: &ResetReport) -> Result<()>;
+    fn clean_stale(&self, report: &CleanStaleReport) -> Result<()>;
+    fn global_cache(&self, report: &GlobalCacheReport) -> Result<()>;
+    fn link(&self, report: &LinkReport) -> Result<()>;
+    fn hooks(&self, report: &HooksReport) -> Result<()>;
+    fn dedup(&self, report: &DedupReport) -> Result<()>;
+}
+
+/// 面向终端的输出：表格写到标准输出，说明性内容走日志
+pub struct HumanReporter;
+
+/// 分析结果按依赖表分段，表变化时输出表名
+fn section_header(current: &mut Option<DependencySection>, dep: &DependencyInfo) {
+    if *current != Some(dep.section) {
+        info!("  [{}]", dep.section.table_name());
+        *current = Some(dep.section);
+    }
+}
+
+/// 平台相关依赖在分析结果中标注其 target 表达式
+fn target_suffix(dep: &DependencyInfo) -> String {
+    match &dep.target_cfg {
+        Some(cfg) => format!(" [target: {cfg}]"),
+        None => String::new(),
+    }
+}
+
+/// 各阶段耗时属于诊断信息，与其他说明性内容一样走日志
+fn log_timings(timings: &Timings) {
+    info!("⏱️  Phase timings:");
+    for phase in &timings.phases {
+        info!("  {:<22} {:>9.3}s", phase.phase, phase.seconds);
+    }
+    info!("  {:<22} {:>9.3}s", "total", timings.total_seconds);
+}
+
+impl Reporter for HumanReporter {
+    fn patches(&self, patches: &[PatchEntry]) -> Result<()> {
+        if patches.is_empty() {
+            info!(
+                "📭 No active patches in {}",
+                CargoConfig::get_config_path().display()
+            );
+            return Ok(());
+        }
+
+        let name_width = patches.iter().map(|p| p.name.len()).max().unwrap_or(0);
+        let mut stale = 0;
+        let mut current_source = None;
+        for patch in patches {
+            // patches 已按源排序，按源分组输出
+            if current_source != Some(&patch.source) {
+                if current_source.is_some() {
+                    println!();
+                }
+                println!("{}", CargoConfig::patch_table_header(&patch.source));
+                current_source = Some(&patch.source);
+            }
+
+            if patch.path_exists {
+                println!("  {:<name_width$}  {}", patch.name, patch.path);
+            } else {
+                stale += 1;
+                println!(
+                    "  {:<name_width$}  {}  (stale: path does not exist)",
+                    patch.name, patch.path
+                );
+            }
+        }
+
+        if stale > 0 {
+            warn!("⚠️  {stale} patch(es) point to missing paths, remove them with 'cargo lpatch unpatch <crate>'");
+        }
+        Ok(())
+    }
+
+    fn analysis(&self, analysis: &DependencyAnalysis) -> Result<()> {
+        if analysis.total() == 0 {
+            info!("📦 No dependencies found in Cargo.toml");
+            return Ok(());
+        }
+
+        info!("📦 Found {} dependencies:", analysis.total());
+
+        if !analysis.version.is_empty() {
+            info!(
+                "🌐 Version dependencies (from registries): {}",
+                analysis.version.len()
+            );
+            let mut section = None;
+            for dep in &analysis.version {
+                section_header(&mut section, dep);
+                if let DependencyType::Version { version, registry } = &dep.dep_type {
+                    match registry {
+                        Some(registry) => {
+                            info!(
+                                "  📋 {} = \"{}\" (registry: {}){}",
+                                dep.name,
+                                version,
+                                registry,
+                                target_suffix(dep)
+                            )
+                        }
+                        None => info!("  📋 {} = \"{}\"{}", dep.name, version, target_suffix(dep)),
+                    }
+                }
+            }
+        }
+
+        if !analysis.git.is_empty() {
+            info!("🔗 Git dependencies: {}", analysis.git.len());
+            let mut section = None;
+            for dep in &analysis.git {
+                section_header(&mut section, dep);
+                if let DependencyType::Git {
+                    git,
+                    branch,
+                    tag,
+                    rev,
+                } = &dep.dep_type
+                {
+                    let mut git_spec = format!("  🌿 {} = {{ git = \"{}\"", dep.name, git);
+                    if let Some(branch) = branch {
+                        git_spec.push_str(&format!(", branch = \"{branch}\""));
+                    }
+                    if let Some(tag) = tag {
+                        git_spec.push_str(&format!(", tag = \"{tag}\""));
+                    }
+                    if let Some(rev) = rev {
+                        git_spec.push_str(&format!(", rev = \"{rev}\""));
+                    }
+                    git_spec.push_str(" }");
+                    git_spec.push_str(&target_suffix(dep));
+                    info!("{git_spec}");
+                }
+            }
+        }
+
+        if !analysis.path.is_empty() {
+            info!("📁 Path dependencies: {}", analysis.path.len());
+            let mut section = None;
+            for dep in &analysis.path {
+                section_header(&mut section, dep);
+                if let DependencyType::Path { path } = &dep.dep_type {
+                    info!(
+                        "  📂 {} = {{ path = \"{}\" }}{}",
+                        dep.name,
+                        path,
+                        target_suffix(dep)
+                    );
+                }
+            }
+        }
+
+        if !analysis.optional.is_empty() {
+            info!("🧩 Optional dependencies: {}", analysis.optional.len());
+            let mut section = None;
+            for optional in &analysis.optional {
+                let dep = &optional.dependency;
+                section_header(&mut section, dep);
+                let source = match &dep.dep_type {
+                    DependencyType::Version { version, .. } => format!("\"{version}\""),
+                    DependencyType::Git { git, .. } => format!("{{ git = \"{git}\" }}"),
+                    DependencyType::Path { path } => format!("{{ path = \"{path}\" }}"),
+                };
+                let features = if optional.features.is_empty() {
+                    "not enabled by any feature".to_string()
+                } else {
+                    format!("enabled by: {}", optional.features.join(", "))
+                };
+                info!(
+                    "  🔌 {} = {} ({}){}",
+                    dep.name,
+                    source,
+                    features,
+                    target_suffix(dep)
+                );
+            }
+        }
+
+        if !analysis.replace.is_empty() {
+            info!(
+                "🔁 [replace] entries (deprecated): {}",
+                analysis.replace.len()
+            );
+            for replacement in &analysis.replace {
+                info!("  ♻️  {} ({})", replacement.name, replacement.spec);
+            }
+        }
+
+        info!("💡 Use 'cargo lpatch --name <CRATE_NAME>' to patch a specific dependency");
+        Ok(())
+    }
+
+    fn metrics(&self, metrics: &PatchMetrics) -> Result<()> {
+        println!("Active patches:          {}", metrics.total_patches);
+        println!("  crates-io source:      {}", metrics.crates_io_patches);
+        println!("  git source:            {}", metrics.git_patches);
+        println!("  missing paths:         {}", metrics.missing_paths);
+        println!("Clones:                  {}", metrics.clones);
+        println!(
+            "  disk usage:            {:.1} MB",
+            metrics.disk_usage_bytes as f64 / 1024.0 / 1024.0
+        );
+        println!("  uncommitted changes:   {}", metrics.dirty_clones);
+        println!(
+            "  behind upstream:       {}",
+            metrics.clones_behind_upstream
+        );
+        Ok(())
+    }
+
+    fn plan(&self, plan: &DryRunPlan) -> Result<()> {
+        if let Some(clone_path) = &plan.clone_path {
+            println!("Clone path: {}", clone_path.display());
+        }
+        if let Some(crate_path) = &plan.crate_path {
+            println!("Crate path: {crate_path}");
+        }
+        if plan.actions.is_empty() {
+            println!("Nothing to do");
+        }
+        for (i, action) in plan.actions.iter().enumerate() {
+            println!("{}. {action}", i + 1);
+        }
+        if let Some(timings) = &plan.timings {
+            log_timings(timings);
+        }
+        Ok(())
+    }
+
+    fn compat(&self, report: &CompatReport) -> Result<()> {
+        for artifact in &report.artifacts {
+            println!(
+                "{} ({}): {} added, {} removed",
+                report.crate_name,
+                artifact.kind,
+                artifact.added.len(),
+                artifact.removed.len()
+            );
+            for symbol in &artifact.removed {
+                println!("  - {} ({})", symbol.demangled, symbol.mangled);
+            }
+            for symbol in &artifact.added {
+                println!("  + {} ({})", symbol.demangled, symbol.mangled);
+            }
+        }
+        Ok(())
+    }
+
+    fn status(&self, entries: &[StatusEntry]) -> Result<()> {
+        for (i, entry) in entries.iter().enumerate() {
+            if i > 0 {
+                println!();
+            }
+            match &entry.problem {
+                Some(problem) => {
+                    println!("{} ({})  BROKEN: {}", entry.name, entry.path, problem);
+                    continue;
+                }
+                None => println!("{} ({})  OK", entry.name, entry.path),
+            }
+            if entry.files.is_empty() {
+                println!("  nothing to commit, working tree clean");
+            }
+            for file in &entry.files {
+                println!("  {} {}", file.code, file.path);
+            }
+        }
+        Ok(())
+    }
+
+    fn update_checks(&self, checks: &[UpdateCheck]) -> Result<()> {
+        let name_width = checks.iter().map(|c| c.name.len()).max().unwrap_or(0);
+        let mut outdated = 0;
+        for check in checks {
+            let state = match (check.behind, check.ahead, &check.error) {
+                (_, _, Some(error)) => format!("check failed: {error}"),
+                (Some(0), _, _) => "up to date".to_string(),
+                (Some(behind), Some(ahead), _) if ahead > 0 => {
+                    outdated += 1;
+                    format!("{behind} commits behind upstream, {ahead} local commits")
+                }
+                (Some(behind), _, _) => {
+                    outdated += 1;
+                    format!("{behind} commits behind upstream")
+                }
+                (None, _, _) => "no upstream branch".to_string(),
+            };
+            println!("{:<name_width$}  {}", check.name, state);
+        }
+        if outdated > 0 {
+            info!("💡 Run 'cargo lpatch update' to pull the new commits");
+        }
+        Ok(())
+    }
+
+    fn diagnostics(&self, issues: &[DiagnosticIssue]) -> Result<()> {
+        if issues.is_empty() {
+            println!("No problems found");
+            return Ok(());
+        }
+        for issue in issues {
+            println!("{}: {}", issue.severity, issue.message);
+            println!("  fix: {}", issue.suggestion);
+        }
+        Ok(())
+    }
+
+    fn dependency_audit(&self, audit: &DependencyAudit) -> Result<()> {
+        if audit.added.is_empty() {
+            println!(
+                "{} v{}: no new transitive dependencies",
+                audit.crate_name, audit.version
+            );
+            return Ok(());
+        }
+        println!(
+            "{} v{}: {} new transitive dependencies compared to {}",
+            audit.crate_name,
+            audit.version,
+            audit.added.len(),
+            audit.source
+        );
+        for dep in &audit.added {
+            println!("  + {} v{}", dep.name, dep.version);
+        }
+        Ok(())
+    }
+
+    fn memory_profile(&self, report: &MemoryReport) -> Result<()> {
+        println!(
+            "{}: peak build RSS {:.1} MB with patch, {:.1} MB without ({:+.1} MB)",
+            report.crate_name, report.patched_mb, report.original_mb, report.delta_mb
+        );
+        Ok(())
+    }
+
+    fn patch_search(&self, hits: &[RepositoryHit]) -> Result<()> {
+        if hits.is_empty() {
+            println!("No matching repositories found");
+            return Ok(());
+        }
+        for hit in hits {
+            let pushed = hit
+                .pushed_at
+                .as_deref()
+                .and_then(|date| date.split('T').next())
+                .unwrap_or("unknown");
+            println!(
+                "{}  ★ {}  pushed {}  {} open PRs",
+                hit.full_name, hit.stars, pushed, hit.open_prs
+            );
+            println!("  {}", hit.url);
+            if let Some(description) = &hit.description {
+                println!("  {description}");
+            }
+        }
+        Ok(())
+    }
+
+    fn version_bump(&self, bump: &VersionBump) -> Result<()> {
+        println!(
+            "{}: {} → {}",
+            bump.crate_name, bump.old_version, bump.new_version
+        );
+        Ok(())
+    }
+
+    fn coverage(&self, report: &CoverageReport) -> Result<()> {
+        fn delta(patched: f64, baseline: Option<f64>) -> String {
+            match baseline {
+                Some(baseline) => {
+                    format!("  (baseline {baseline:.1}%, {:+.1})", patched - baseline)
+                }
+                None => String::new(),
+            }
+        }
+        let baseline = report.baseline.as_ref();
+
+        println!("Coverage of {}", report.crate_name);
+        let patched = &report.patched;
+        println!(
+            "  lines:     {:.1}% ({}/{}){}",
+            patched.line_percent,
+            patched.lines_covered,
+            patched.lines_total,
+            delta(patched.line_percent, baseline.map(|b| b.line_percent))
+        );
+        if let Some(branch_percent) = patched.branch_percent {
+            println!(
+                "  branches:  {:.1}%{}",
+                branch_percent,
+                delta(branch_percent, baseline.and_then(|b| b.branch_percent))
+            );
+        }
+
+        if report.base.is_none() {
+            return Ok(());
+        }
+        if report.uncovered.is_empty() {
+            println!("All lines added by the patch are covered");
+        } else {
+            println!("Uncovered lines added by the patch:");
+            for file in &report.uncovered {
+                println!("  {}: {}", file.file, coverage::format_ranges(&file.lines));
+            }
+        }
+        Ok(())
+    }
+
+    fn local_registry(&self, report: &LocalRegistryReport) -> Result<()> {
+        let name_width = report
+            .crates
+            .iter()
+            .map(|c| c.name.len())
+            .max()
+            .unwrap_or(0);
+        for entry in &report.crates {
+            match (&entry.version, &entry.error) {
+                (_, Some(error)) => println!("{:<name_width$}  failed: {}", entry.name, error),
+                (Some(version), None) => println!("{:<name_width$}  v{}", entry.name, version),
+                (None, None) => println!("{:<name_width$}", entry.name),
+            }
+        }
+        info!(
+            "💡 Add 'replace-with = \"{}\"' under [source.crates-io] to build against {}",
+            crate::local_registry::SOURCE_NAME,
+            report.path
+        );
+        Ok(())
+    }
+
+    fn patch_source(&self, report: &PatchSourceReport) -> Result<()> {
+        println!("{}", report.name);
+        for patch in &report.patches {
+            let state = if patch.matches { "used" } else { "IGNORED" };
+            println!(
+                "  {} -> {}  ({})",
+                CargoConfig::patch_table_header(&patch.source),
+                patch.path,
+                state
+            );
+        }
+        match &report.expected_source {
+            Some(source) => println!("  expected: {}", CargoConfig::patch_table_header(source)),
+            None => println!("  expected: none"),
+        }
+        println!("  why:      {}", report.reason);
+        let resolved = report.resolved.join(", ");
+        match report.applied {
+            Some(true) => println!("  cargo:    patch applied ({resolved})"),
+            Some(false) if resolved.is_empty() => {
+                println!("  cargo:    not in the dependency graph")
+            }
+            Some(false) => println!("  cargo:    patch NOT applied, resolves to {resolved}"),
+            None => println!("  cargo:    cargo metadata unavailable"),
+        }
+        Ok(())
+    }
+
+    fn timings(&self, report: &TimingsReport) -> Result<()> {
+        log_timings(&report.timings);
+        Ok(())
+    }
+
+    fn updates(&self, report: &UpdateReport) -> Result<()> {
+        if report.changelog {
+            for update in &report.updates {
+                println!(
+                    "{}: {}..{}",
+                    update.name,
+                    &update.from[..7],
+                    &update.to[..7]
+                );
+                for commit in &update.commits {
+                    println!(
+                        "  {} {} ({})",
+                        &commit.id[..7],
+                        commit.summary,
+                        commit.author
+                    );
+                }
+            }
+        }
+        println!(
+            "Updated {} patches, skipped {} due to local changes",
+            report.updated, report.dirty
+        );
+        if report.skipped > 0 {
+            info!("⏭️  {} patch(es) skipped for other reasons", report.skipped);
+        }
+        Ok(())
+    }
+
+    fn diffs(&self, diffs: &[PatchDiff]) -> Result<()> {
+        if diffs.is_empty() {
+            info!("✅ No uncommitted changes in patched crates");
+            return Ok(());
+        }
+
+        let mut output = String::new();
+        for diff in diffs {
+            output.push_str(&format!("# {} ({})\n", diff.name, diff.repository));
+            output.push_str(&diff.diff);
+        }
+        let pager = std::env::var("PAGER")
+            .ok()
+            .filter(|pager| !pager.is_empty());
+        match pager {
+            Some(pager) if std::io::stdout().is_terminal() => page_output(&pager, &output),
+            _ => {
+                print!("{output}");
+                Ok(())
+            }
+        }
+    }
+
+    fn references(&self, report: &ReferenceReport) -> Result<()> {
+        if report.references.is_empty() {
+            info!("📭 No references to '{}' found", report.name);
+            return Ok(());
+        }
+
+        let mut current_file: Option<&Path> = None;
+        for reference in &report.references {
+            if current_file != Some(reference.file.as_path()) {
+                println!("{}", reference.file.display());
+                current_file = Some(reference.file.as_path());
+            }
+            println!("  {:>5}: {}", reference.line, reference.text);
+        }
+        info!(
+            "📊 Found {} references in {} files",
+            report.references.len(),
+            report
+                .references
+                .iter()
+                .map(|r| &r.file)
+                .collect::<HashSet<_>>()
+                .len()
+        );
+        Ok(())
+    }
+
+    fn patch_tree(&self, nodes: &[PatchTreeNode]) -> Result<()> {
+        print!("{}", tree::render(nodes));
+        Ok(())
+    }
+
+    fn env_export(&self, export: &EnvExport) -> Result<()> {
+        println!("# cargo-lpatch session for {}", export.project_dir);
+        for patch in &export.patches {
+            println!(
+                "# patch: {} -> {} ({})",
+                patch.name, patch.path, patch.source
+            );
+        }
+        for (name, value) in &export.env {
+            println!("export {name}=\"{}\"", value.replace('"', "\\\""));
+        }
+        Ok(())
+    }
+
+    fn extracted(&self, _plan: &DryRunPlan) -> Result<()> {
+        // 执行过程已经写入日志
+        Ok(())
+    }
+
+    fn mock_crate(&self, report: &MockReport) -> Result<()> {
+        info!("✅ Successfully set up mock patch for '{}'", report.name);
+        Ok(())
+    }
+
+    fn unpatched(&self, report: &UnpatchReport) -> Result<()> {
+        info!("✅ Removed local patch for '{}'", report.name);
+        if let Some(clone) = &report.deleted_clone {
+            info!("🗑️  Deleted {clone}");
+        }
+        Ok(())
+    }
+
+    fn init(&self, report: &InitReport) -> Result<()> {
+        if report.created.is_empty() && report.gitignore_entry.is_none() {
+            info!("✅ Already initialized in {}", report.project_dir);
+            return Ok(());
+        }
+        for path in &report.created {
+            info!("📁 Created {path}");
+        }
+        if let Some(entry) = &report.gitignore_entry {
+            info!("🙈 Added {entry} to .gitignore");
+        }
+        info!(
+            "✅ Ready, run `cargo lpatch --name <CRATE> --dir {}` to patch a crate",
+            report.dir
+        );
+        Ok(())
+    }
+
+    fn reset(&self, report: &ResetReport) -> Result<()> {
+        if report.removed == 0 {
+            info!("📭 There are no active patches");
+            return Ok(());
+        }
+        info!("✅ Removed {} patch(es)", report.removed);
+        for clone in &report.kept {
+            warn!("⚠️  '{clone}' has uncommitted changes, not deleting it");
+        }
+        for clone in &report.deleted {
+            info!("🗑️  Deleted {clone}");
+        }
+        Ok(())
+    }
+
+    fn clean_stale(&self, report: &CleanStaleReport) -> Result<()> {
+        if !report.clone_dir_exists {
+            info!("📭 Clone directory '{}' does not exist", report.clone_dir);
+        } else if report.stale.is_empty() {
+            info!("✅ No stale clones in {}", report.clone_dir);
+        } else {
+            info!(
+                "✅ Deleted {} of {} stale clone(s)",
+                report.deleted.len(),
+                report.stale.len()
+            );
+        }
+        Ok(())
+    }
+
+    fn global_cache(&self, report: &GlobalCacheReport) -> Result<()> {
+        match (&report.cache_dir, report.changed) {
+            (Some(cache_dir), _) => info!(
+                "✅ Global cache enabled in {}, repositories are cached in {}",
+                report.config_path, cache_dir
+            ),
+            (None, true) => info!(
+                "✅ Global cache disabled in {}, existing worktrees are left untouched",
+                report.config_path
+            ),
+            (None, false) => info!("📭 Global cache is not enabled in {}", report.config_path),
+        }
+        Ok(())
+    }
+
+    fn link(&self, report: &LinkReport) -> Result<()> {
+        info!(
+            "✅ Successfully set up local patch for '{}' via link",
+            report.name
+        );
+        Ok(())
+    }
+
+    fn hooks(&self, report: &HooksReport) -> Result<()> {
+        for install in &report.installed {
+            info!(
+                "🪝 Installed {} hook for '{}': {}",
+                report.hook, install.name, install.path
+            );
+        }
+        for failure in &report.failed {
+            error!(
+                "❌ Failed to install {} hook for '{}': {}",
+                report.hook, failure.name, failure.error
+            );
+        }
+        Ok(())
+    }
+
+    fn dedup(&self, report: &DedupReport) -> Result<()> {
+        if report.groups.is_empty() {
+            info!("✅ No duplicate clones found");
+        } else if report.dry_run {
+            info!("🔍 Dry run, no changes made");
+        } else {
+            for clone in &report.removed {
+                info!("🗑️  Removed {clone}");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 将内容交给分页器显示，分页器命令按 shell 规则解析
+fn page_output(pager: &str, content: &str) -> Result<()> {
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+    let mut child = std::process::Command::new(shell)
+        .arg(flag)
+        .arg(pager)
+        .stdin(std::process::Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to start pager '{pager}'"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // 用户提前退出分页器时写入会失败，这不是错误
+        let _ = stdin.write_all(content.as_bytes());
+    }
+    child.wait()?;
+    Ok(())
+}
+
+/// 机器可读的输出：标准输出上只有一个 JSON 文档
+pub struct JsonReporter;
+
+impl JsonReporter {
+    fn print<T: Serialize + ?Sized>(value: &T) -> Result<()> {
+        println!("{}", serde_json::to_string_pretty(value)?);
+        Ok(())
+    }
+}
+
+impl Reporter for JsonReporter {
+    fn patches(&self, patches: &[PatchEntry]) -> Result<()> {
+        Self::print(patches)
+    }
+
+    fn analysis(&self, analysis: &DependencyAnalysis) -> Result<()> {
+        Self::print(analysis)
+    }
+
+    fn metrics(&self, metrics: &PatchMetrics) -> Result<()> {
+        Self::print(metrics)
+    }
+
+    fn plan(&self, plan: &DryRunPlan) -> Result<()> {
+        Self::print(plan)
+    }
+
+    fn compat(&self, report: &CompatReport) -> Result<()> {
+        Self::print(report)
+    }
+
+    fn status(&self, entries: &[StatusEntry]) -> Result<()> {
+        Self::print(entries)
+    }
+
+    fn update_checks(&self, checks: &[UpdateCheck]) -> Result<()> {
+        Self::print(checks)
+    }
+
+    fn diagnostics(&self, issues: &[DiagnosticIssue]) -> Result<()> {
+        Self::print(issues)
+    }
+
+    fn dependency_audit(&self, audit: &DependencyAudit) -> Result<()> {
+        Self::print(audit)
+    }
+
+    fn memory_profile(&self, report: &MemoryReport) -> Result<()> {
+        Self::print(report)
+    }
+
+    fn patch_search(&self, hits: &[RepositoryHit]) -> Result<()> {
+        Self::print(hits)
+    }
+
+    fn version_bump(&self, bump: &VersionBump) -> Result<()> {
+        Self::print(bump)
+    }
+
+    fn coverage(&self, report: &CoverageReport) -> Result<()> {
+        Self::print(report)
+    }
+
+    fn local_registry(&self, report: &LocalRegistryReport) -> Result<()> {
+        Self::print(report)
+    }
+
+    fn patch_source(&self, report: &PatchSourceReport) -> Result<()> {
+        Self::print(report)
+    }
+
+    fn timings(&self, report: &TimingsReport) -> Result<()> {
+        Self::print(report)
+    }
+
+    fn updates(&self, report: &UpdateReport) -> Result<()> {
+        Self::print(report)
+    }
+
+    fn diffs(&self, diffs: &[PatchDiff]) -> Result<()> {
+        Self::print(diffs)
+    }
+
+    fn references(&self, report: &ReferenceReport) -> Result<()> {
+        Self::print(report)
+    }
+
+    fn patch_tree(&self, nodes: &[PatchTreeNode]) -> Result<()> {
+        Self::print(nodes)
+    }
+
+    fn env_export(&self, export: &EnvExport) -> Result<()> {
+        Self::print(export)
+    }
+
+    fn extracted(&self, plan: &DryRunPlan) -> Result<()> {
+        Self::print(plan)
+    }
+
+    fn mock_crate(&self, report: &MockReport) -> Result<()> {
+        Self::print(report)
+    }
+
+    fn unpatched(&self, report: &UnpatchReport) -> Result<()> {
+        Self::print(report)
+    }
+
+    fn init(&self, report: &InitReport) -> Result<()> {
+        Self::print(report)
+    }
+
+    fn reset(&self, report: &ResetReport) -> Result<()> {
+        Self::print(report)
+    }
+
+    fn clean_stale(&self, report: &CleanStaleReport) -> Result<()> {
+        Self::print(report)
+    }
+
+    fn global_cache(&self, report: &GlobalCacheReport) -> Result<()> {
+        Self::print(report)
+    }
+
+    fn link(&self, report: &LinkReport) -> Result<()> {
+        Self::print(report)
+    }
+
+    fn hooks(&self, report: &HooksReport) -> Result<()> {
+        Self::print(report)
+    }
+
+    fn dedup(&self, report: &DedupReport) -> Result<()> {
+        Self::print(report)
+    }
+}
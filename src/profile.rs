@@ -0,0 +1,167 @@
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+/// 采样间隔
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(20);
+
+/// 读取进程内存的方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    /// 读取 `/proc/<pid>/status` 中的 VmRSS
+    Linux,
+    /// 通过 `ps -o rss` 读取
+    Macos,
+}
+
+impl Platform {
+    pub fn parse(s: &str) -> Self {
+        match s {
+            "macos" => Platform::Macos,
+            _ => Platform::Linux,
+        }
+    }
+}
+
+/// 启用和停用补丁时构建过程的峰值内存
+#[derive(Debug, Serialize)]
+pub struct MemoryReport {
+    pub crate_name: String,
+    pub patched_mb: f64,
+    pub original_mb: f64,
+    pub delta_mb: f64,
+}
+
+impl MemoryReport {
+    pub fn new(crate_name: &str, patched_kb: u64, original_kb: u64) -> Self {
+        let patched_mb = patched_kb as f64 / 1024.0;
+        let original_mb = original_kb as f64 / 1024.0;
+        Self {
+            crate_name: crate_name.to_string(),
+            patched_mb,
+            original_mb,
+            delta_mb: patched_mb - original_mb,
+        }
+    }
+}
+
+/// 运行 `cargo build` 并返回整个进程树（cargo 及其启动的 rustc 等）的峰值 RSS，单位 KB
+pub fn peak_build_rss(project_dir: &Path, target_dir: &Path, platform: Platform) -> Result<u64> {
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let mut child = Command::new(cargo)
+        .arg("build")
+        .arg("--target-dir")
+        .arg(target_dir)
+        .current_dir(project_dir)
+        .stdout(Stdio::null())
+        .spawn()
+        .context("Failed to run cargo build")?;
+
+    let peak = monitor(&mut child, platform);
+    let status = child.wait().context("Failed to wait for cargo build")?;
+    let peak = peak?;
+    if !status.success() {
+        return Err(anyhow!("cargo build failed in {}", project_dir.display()));
+    }
+    Ok(peak)
+}
+
+/// 在子进程退出前周期性采样其进程树的 RSS 总和
+fn monitor(child: &mut Child, platform: Platform) -> Result<u64> {
+    let root = child.id();
+    let mut peak = 0;
+    while child.try_wait()?.is_none() {
+        let rss = match platform {
+            Platform::Linux => tree_rss_linux(root),
+            Platform::Macos => tree_rss_ps(root)?,
+        };
+        peak = peak.max(rss);
+        thread::sleep(SAMPLE_INTERVAL);
+    }
+    Ok(peak)
+}
+
+/// 遍历 `/proc` 得到以 `root` 为根的进程树 RSS 总和（KB）
+fn tree_rss_linux(root: u32) -> u64 {
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return 0;
+    };
+
+    // pid -> (ppid, rss)
+    let mut processes = HashMap::new();
+    for entry in entries.flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|s| s.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        // 进程可能在读取期间退出，读取失败时直接跳过
+        let Ok(stat) = fs::read_to_string(entry.path().join("stat")) else {
+            continue;
+        };
+        // 进程名可能包含空格和括号，ppid 取最后一个 ')' 之后的第二个字段
+        let Some(ppid) = stat
+            .rsplit_once(')')
+            .and_then(|(_, rest)| rest.split_whitespace().nth(1))
+            .and_then(|s| s.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        let rss = fs::read_to_string(entry.path().join("status"))
+            .ok()
+            .and_then(|status| {
+                status
+                    .lines()
+                    .find_map(|line| line.strip_prefix("VmRSS:"))
+                    .and_then(|value| value.split_whitespace().next()?.parse::<u64>().ok())
+            })
+            .unwrap_or(0);
+        processes.insert(pid, (ppid, rss));
+    }
+    sum_tree(root, &processes)
+}
+
+/// 通过 `ps` 得到以 `root` 为根的进程树 RSS 总和（KB）
+fn tree_rss_ps(root: u32) -> Result<u64> {
+    let output = Command::new("ps")
+        .args(["-A", "-o", "pid=,ppid=,rss="])
+        .output()
+        .context("Failed to run ps")?;
+    let processes = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace().map(|f| f.parse::<u64>().ok());
+            let pid = fields.next()??;
+            let ppid = fields.next()??;
+            let rss = fields.next()??;
+            Some((pid as u32, (ppid as u32, rss)))
+        })
+        .collect();
+    Ok(sum_tree(root, &processes))
+}
+
+fn sum_tree(root: u32, processes: &HashMap<u32, (u32, u64)>) -> u64 {
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    for (&pid, &(ppid, _)) in processes {
+        children.entry(ppid).or_default().push(pid);
+    }
+
+    let mut total = 0;
+    let mut stack = vec![root];
+    while let Some(pid) = stack.pop() {
+        if let Some((_, rss)) = processes.get(&pid) {
+            total += rss;
+        }
+        if let Some(kids) = children.get(&pid) {
+            stack.extend(kids);
+        }
+    }
+    total
+}
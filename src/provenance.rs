@@ -0,0 +1,89 @@
+use crate::config::CargoConfig;
+use crate::git::CommitSummary;
+use anyhow::{Context, Result};
+use git2::Oid;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 补丁更新记录文件：`.cargo/lpatch-provenance/<crate>.log`
+///
+/// 每次更新追加一段，记录旧提交到新提交的范围和引入的提交，便于审查补丁如何跟随上游
+pub fn provenance_path(crate_name: &str) -> PathBuf {
+    CargoConfig::get_config_dir()
+        .join("lpatch-provenance")
+        .join(format!("{crate_name}.log"))
+}
+
+/// 追加一条更新记录
+pub fn record_update(
+    crate_name: &str,
+    from: Oid,
+    to: Oid,
+    commits: &[CommitSummary],
+) -> Result<PathBuf> {
+    let path = provenance_path(crate_name);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory '{}'", parent.display()))?;
+    }
+
+    let mut entry = format!(
+        "[{}] {}..{} ({} commit(s))\n",
+        utc_timestamp(),
+        short_id(&from.to_string()),
+        short_id(&to.to_string()),
+        commits.len()
+    );
+    for commit in commits {
+        entry.push_str(&format!(
+            "  {} {} ({})\n",
+            short_id(&commit.id),
+            commit.summary,
+            commit.author
+        ));
+    }
+    entry.push('\n');
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    file.write_all(entry.as_bytes())
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+
+    Ok(path)
+}
+
+fn short_id(id: &str) -> &str {
+    &id[..id.len().min(7)]
+}
+
+/// 当前 UTC 时间，格式为 `YYYY-MM-DDTHH:MM:SSZ`
+fn utc_timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+
+    // 由 Unix 纪元天数换算公历日期（Howard Hinnant 的 civil_from_days 算法）
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
@@ -0,0 +1,143 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::crates_io::ResolvedCrate;
+use crate::global_cache;
+
+/// 默认的缓存有效期（`--cache-ttl`）
+pub const DEFAULT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// `--jobs` 时多个任务会并发写入同一个缓存文件
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    /// registry 上的规范名称
+    name: String,
+    repository_url: String,
+    /// 查询时间（Unix 时间戳，秒）
+    fetched_at: u64,
+}
+
+/// registry 查询结果的磁盘缓存，按小写的 crate 名称索引
+///
+/// 每个 registry 一个文件：`$XDG_CACHE_HOME/cargo-lpatch/<registry>.json`
+pub struct ResolveCache {
+    path: PathBuf,
+    ttl: Duration,
+}
+
+impl ResolveCache {
+    pub fn open(registry: &str, ttl: Duration) -> Result<Self> {
+        let file_name = match registry {
+            "crates.io" => "crates-io.json".to_string(),
+            registry => format!("{registry}.json"),
+        };
+        Ok(Self::new(global_cache::cache_home()?.join(file_name), ttl))
+    }
+
+    /// 使用指定的缓存文件
+    pub fn new(path: PathBuf, ttl: Duration) -> Self {
+        Self { path, ttl }
+    }
+
+    fn load(&self) -> BTreeMap<String, CacheEntry> {
+        fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// 查找未过期的缓存项
+    pub fn get(&self, crate_name: &str) -> Option<ResolvedCrate> {
+        let entry = self.load().remove(&crate_name.to_lowercase())?;
+        let age = now().saturating_sub(entry.fetched_at);
+        if age >= self.ttl.as_secs() {
+            debug!("🗄️  Cached repository URL of '{crate_name}' expired");
+            return None;
+        }
+        debug!(
+            "🗄️  Using cached repository URL of '{}' from {}",
+            crate_name,
+            self.path.display()
+        );
+        Some(ResolvedCrate {
+            name: entry.name,
+            repository_url: entry.repository_url,
+        })
+    }
+
+    /// 记录一次查询结果，同时清理已过期的项
+    pub fn put(&self, crate_name: &str, resolved: &ResolvedCrate) -> Result<()> {
+        let _guard = WRITE_LOCK
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let now = now();
+        let mut entries = self.load();
+        entries.retain(|_, entry| now.saturating_sub(entry.fetched_at) < self.ttl.as_secs());
+        entries.insert(
+            crate_name.to_lowercase(),
+            CacheEntry {
+                name: resolved.name.clone(),
+                repository_url: resolved.repository_url.clone(),
+                fetched_at: now,
+            },
+        );
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // 先写临时文件再重命名，中断时不会留下损坏的缓存
+        let tmp_path = self.path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_string_pretty(&entries)?)
+            .with_context(|| format!("Failed to write {}", tmp_path.display()))?;
+        fs::rename(&tmp_path, &self.path)
+            .with_context(|| format!("Failed to write {}", self.path.display()))
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn serde() -> ResolvedCrate {
+        ResolvedCrate {
+            name: "serde".to_string(),
+            repository_url: "https://github.com/serde-rs/serde".to_string(),
+        }
+    }
+
+    #[test]
+    fn keys_are_case_insensitive() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cache = ResolveCache::new(tmp.path().join("crates-io.json"), DEFAULT_TTL);
+        cache.put("Serde", &serde()).unwrap();
+
+        for name in ["serde", "Serde", "SERDE"] {
+            let resolved = cache.get(name).unwrap();
+            assert_eq!(resolved.name, "serde");
+            assert_eq!(resolved.repository_url, "https://github.com/serde-rs/serde");
+        }
+        assert!(cache.get("serde_json").is_none());
+    }
+
+    #[test]
+    fn expired_entries_are_ignored() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cache = ResolveCache::new(tmp.path().join("crates-io.json"), Duration::ZERO);
+        cache.put("serde", &serde()).unwrap();
+        assert!(cache.get("serde").is_none());
+    }
+}
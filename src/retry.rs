@@ -0,0 +1,239 @@
+use std::collections::hash_map::RandomState;
+use std::fmt::Display;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// 第一次重试前的等待时间（测试中缩短，避免拖慢测试）
+const INITIAL_DELAY: Duration = if cfg!(test) {
+    Duration::from_millis(1)
+} else {
+    Duration::from_millis(500)
+};
+/// 每次重试后等待时间的倍数
+const MULTIPLIER: u32 = 2;
+/// 第一次失败后默认的最多重试次数（`--retries`）
+pub const DEFAULT_RETRIES: u32 = 3;
+/// 服务端要求的等待时间上限，避免被异常的 Retry-After 长时间挂起
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// 一次失败之后的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Retry {
+    /// 不可重试，直接返回错误
+    No,
+    /// 按指数退避等待后重试
+    Backoff,
+    /// 按服务端要求的时间（如 429 的 `Retry-After`）等待后重试
+    After(Duration),
+}
+
+impl From<bool> for Retry {
+    fn from(retryable: bool) -> Self {
+        if retryable {
+            Retry::Backoff
+        } else {
+            Retry::No
+        }
+    }
+}
+
+impl Retry {
+    /// 本次重试前需要等待的时间，不可重试时为 `None`
+    fn wait(self, backoff: Duration) -> Option<Duration> {
+        match self {
+            Retry::No => None,
+            Retry::Backoff => Some(with_jitter(backoff)),
+            Retry::After(wait) => Some(wait.min(MAX_RETRY_AFTER)),
+        }
+    }
+}
+
+/// 执行 `op`，遇到 `classify` 认为是暂时性的错误时按指数退避重试，最多重试 `retries` 次
+///
+/// 不可重试的错误（如 404、认证失败）立即返回
+pub fn retry_with_backoff<T, E, F, R>(
+    what: &str,
+    retries: u32,
+    classify: impl Fn(&E) -> R,
+    mut op: F,
+) -> Result<T, E>
+where
+    E: Display,
+    F: FnMut() -> Result<T, E>,
+    R: Into<Retry>,
+{
+    let mut delay = INITIAL_DELAY;
+    let mut attempt = 1;
+    loop {
+        debug!("🔁 {} (attempt {}/{})", what, attempt, retries + 1);
+        let e = match op() {
+            Err(e) if attempt <= retries => e,
+            result => return result,
+        };
+        let Some(wait) = classify(&e).into().wait(delay) else {
+            return Err(e);
+        };
+        warn_retry(what, &e, wait, attempt, retries);
+        std::thread::sleep(wait);
+        delay *= MULTIPLIER;
+        attempt += 1;
+    }
+}
+
+/// [`retry_with_backoff`] 的异步版本，等待期间不阻塞运行时
+pub async fn retry_with_backoff_async<T, E, F, Fut, R>(
+    what: &str,
+    retries: u32,
+    classify: impl Fn(&E) -> R,
+    mut op: F,
+) -> Result<T, E>
+where
+    E: Display,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    R: Into<Retry>,
+{
+    let mut delay = INITIAL_DELAY;
+    let mut attempt = 1;
+    loop {
+        debug!("🔁 {} (attempt {}/{})", what, attempt, retries + 1);
+        let e = match op().await {
+            Err(e) if attempt <= retries => e,
+            result => return result,
+        };
+        let Some(wait) = classify(&e).into().wait(delay) else {
+            return Err(e);
+        };
+        warn_retry(what, &e, wait, attempt, retries);
+        tokio::time::sleep(wait).await;
+        delay *= MULTIPLIER;
+        attempt += 1;
+    }
+}
+
+/// 在等待时间上随机增加最多一半，避免多个并发任务同时重试
+fn with_jitter(delay: Duration) -> Duration {
+    // RandomState 每次创建都使用不同的随机密钥，足以作为抖动的随机来源
+    let random = RandomState::new().build_hasher().finish();
+    let fraction = (random % 1000) as f64 / 1000.0;
+    delay + delay.mul_f64(fraction / 2.0)
+}
+
+fn warn_retry(what: &str, error: &dyn Display, delay: Duration, attempt: u32, retries: u32) {
+    warn!(
+        "⚠️  {} failed ({}), retrying in {} ms (retry {}/{})",
+        what,
+        error,
+        delay.as_millis(),
+        attempt,
+        retries
+    );
+}
+
+/// 超时、连接重置、服务端错误等暂时性的 git 网络错误
+pub fn is_transient_git_error(e: &git2::Error) -> bool {
+    if matches!(
+        e.code(),
+        git2::ErrorCode::Auth | git2::ErrorCode::NotFound | git2::ErrorCode::Certificate
+    ) {
+        return false;
+    }
+
+    let message = e.message().to_lowercase();
+    let transient_message = [
+        "timed out",
+        "timeout",
+        "connection reset",
+        "temporarily unavailable",
+        "early eof",
+        "http status code: 5",
+    ]
+    .iter()
+    .any(|pattern| message.contains(pattern));
+
+    // libgit2 的传输层错误（网络、套接字）
+    transient_message
+        || (e.code() == git2::ErrorCode::GenericError
+            && matches!(e.class(), git2::ErrorClass::Net | git2::ErrorClass::Os))
+}
+
+/// 超时、无法建立连接或服务端返回 5xx 的 HTTP 请求
+pub fn is_transient_http_error(e: &reqwest::Error) -> bool {
+    e.is_timeout() || e.is_connect() || e.status().is_some_and(|status| status.is_server_error())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn transient() -> git2::Error {
+        git2::Error::from_str("failed to connect: connection timed out")
+    }
+
+    #[test]
+    fn retries_transient_errors_until_success() {
+        let attempts = Cell::new(0);
+        let result = retry_with_backoff("op", DEFAULT_RETRIES, is_transient_git_error, || {
+            attempts.set(attempts.get() + 1);
+            if attempts.get() <= 2 {
+                Err(transient())
+            } else {
+                Ok(attempts.get())
+            }
+        });
+        assert_eq!(result.unwrap(), 3);
+        assert_eq!(attempts.get(), 3);
+    }
+
+    #[test]
+    fn gives_up_after_the_configured_retries() {
+        let attempts = Cell::new(0);
+        let result: Result<(), _> = retry_with_backoff("op", 2, is_transient_git_error, || {
+            attempts.set(attempts.get() + 1);
+            Err(transient())
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts.get(), 3);
+    }
+
+    #[test]
+    fn auth_and_not_found_errors_are_not_retried() {
+        for code in [git2::ErrorCode::Auth, git2::ErrorCode::NotFound] {
+            let attempts = Cell::new(0);
+            let result: Result<(), _> =
+                retry_with_backoff("op", DEFAULT_RETRIES, is_transient_git_error, || {
+                    attempts.set(attempts.get() + 1);
+                    Err(git2::Error::new(
+                        code,
+                        git2::ErrorClass::Net,
+                        "request timed out",
+                    ))
+                });
+            assert_eq!(result.unwrap_err().code(), code);
+            assert_eq!(attempts.get(), 1, "{code:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn async_retry_fails_twice_then_succeeds() {
+        let attempts = Cell::new(0);
+        let result = retry_with_backoff_async(
+            "op",
+            DEFAULT_RETRIES,
+            |_: &git2::Error| Retry::After(Duration::ZERO),
+            || async {
+                attempts.set(attempts.get() + 1);
+                if attempts.get() <= 2 {
+                    Err(transient())
+                } else {
+                    Ok(())
+                }
+            },
+        )
+        .await;
+        assert!(result.is_ok());
+        assert_eq!(attempts.get(), 3);
+    }
+}
@@ -0,0 +1,70 @@
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// 源码中对某个 crate 的一处引用
+#[derive(Debug, Clone, Serialize)]
+pub struct CrateReference {
+    pub file: PathBuf,
+    pub line: usize,
+    pub text: String,
+}
+
+/// 在项目源码中查找对指定 crate 的引用（`use foo::...` 或 `foo::...`）
+///
+/// 通过 `grep -r` 子进程实现，会跳过 `target` 和 `exclude_dirs` 中的目录
+pub fn scan_references(
+    root: &Path,
+    crate_name: &str,
+    exclude_dirs: &[&str],
+) -> Result<Vec<CrateReference>> {
+    // 代码中 crate 名称的 `-` 会变成 `_`
+    let ident = crate_name.replace('-', "_");
+    let pattern = format!(r"\b{ident}::");
+
+    let mut command = Command::new("grep");
+    command.current_dir(root).args([
+        "-rnE",
+        "--include=*.rs",
+        "--exclude-dir=target",
+        "--exclude-dir=.git",
+    ]);
+    for dir in exclude_dirs {
+        command.arg(format!("--exclude-dir={dir}"));
+    }
+    command.arg(&pattern).arg(".");
+
+    let output = command
+        .output()
+        .context("Failed to run 'grep', make sure it is installed and in PATH")?;
+
+    // grep 在没有匹配时返回 1，出错时返回 2
+    match output.status.code() {
+        Some(0) | Some(1) => {}
+        _ => {
+            return Err(anyhow!(
+                "grep failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+        }
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let references = stdout
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, ':');
+            let file = parts.next()?;
+            let line_no = parts.next()?.parse().ok()?;
+            let text = parts.next()?.trim().to_string();
+            Some(CrateReference {
+                file: PathBuf::from(file.trim_start_matches("./")),
+                line: line_no,
+                text,
+            })
+        })
+        .collect();
+
+    Ok(references)
+}
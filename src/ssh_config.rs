@@ -0,0 +1,162 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// `~/.ssh/config` 中的一个 `Host` 块
+#[derive(Debug)]
+struct HostBlock {
+    patterns: Vec<String>,
+    identity_files: Vec<String>,
+}
+
+/// `~/.ssh/config` 的最小解析器，只关心 `Host` 和 `IdentityFile`
+///
+/// libgit2 不读取 OpenSSH 的配置，为别名主机配置的密钥需要由这里找出来
+#[derive(Debug)]
+pub struct SshConfigParser {
+    home: PathBuf,
+    blocks: Vec<HostBlock>,
+}
+
+impl SshConfigParser {
+    /// 在 `~/.ssh/config` 中查找适用于 `host` 的第一个 `IdentityFile`
+    pub fn find_identity_for_host(host: &str) -> Option<PathBuf> {
+        let home = dirs::home_dir()?;
+        let content = fs::read_to_string(home.join(".ssh").join("config")).ok()?;
+        Self::parse(&content, &home).identity_for(host)
+    }
+
+    pub fn parse(content: &str, home: &Path) -> Self {
+        let mut parser = Self {
+            home: home.to_path_buf(),
+            blocks: Vec::new(),
+        };
+        // 第一个 Host 之前的配置对所有主机生效
+        let mut current = HostBlock {
+            patterns: vec!["*".to_string()],
+            identity_files: Vec::new(),
+        };
+
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            // 关键字与参数之间可以是空白或 `=`
+            let (keyword, args) = line
+                .split_once(|c: char| c.is_whitespace() || c == '=')
+                .unwrap_or((line, ""));
+            let args = args.trim_start_matches(|c: char| c.is_whitespace() || c == '=');
+
+            match keyword.to_ascii_lowercase().as_str() {
+                "host" => {
+                    parser.blocks.push(current);
+                    current = HostBlock {
+                        patterns: args.split_whitespace().map(str::to_string).collect(),
+                        identity_files: Vec::new(),
+                    };
+                }
+                // 不支持 Match 条件，其后的配置直到下一个 Host 都不生效
+                "match" => {
+                    parser.blocks.push(current);
+                    current = HostBlock {
+                        patterns: Vec::new(),
+                        identity_files: Vec::new(),
+                    };
+                }
+                "identityfile" => {
+                    let path = args.trim().trim_matches('"');
+                    if !path.is_empty() {
+                        current.identity_files.push(path.to_string());
+                    }
+                }
+                _ => {}
+            }
+        }
+        parser.blocks.push(current);
+        parser
+    }
+
+    /// 按文件顺序返回第一个匹配 `host` 的块中声明的 `IdentityFile`
+    pub fn identity_for(&self, host: &str) -> Option<PathBuf> {
+        self.blocks
+            .iter()
+            .filter(|block| host_matches(&block.patterns, host))
+            .flat_map(|block| &block.identity_files)
+            .next()
+            .map(|path| self.expand(path, host))
+    }
+
+    /// 展开 `~`、`%d`（主目录）、`%h`（主机名）和 `%%`
+    fn expand(&self, path: &str, host: &str) -> PathBuf {
+        let home = self.home.to_string_lossy();
+        let mut expanded = String::new();
+        let mut chars = path.chars();
+        if path == "~" || path.starts_with("~/") {
+            expanded.push_str(&home);
+            chars.next();
+        }
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                expanded.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('d') => expanded.push_str(&home),
+                Some('h') => expanded.push_str(host),
+                Some('%') => expanded.push('%'),
+                Some(other) => {
+                    expanded.push('%');
+                    expanded.push(other);
+                }
+                None => expanded.push('%'),
+            }
+        }
+
+        let expanded = PathBuf::from(expanded);
+        // 相对路径相对于 ~/.ssh
+        if expanded.is_relative() {
+            self.home.join(".ssh").join(expanded)
+        } else {
+            expanded
+        }
+    }
+}
+
+/// `Host` 的模式列表是否匹配主机，任一 `!` 否定模式匹配时整个块不匹配
+fn host_matches(patterns: &[String], host: &str) -> bool {
+    let host = host.to_ascii_lowercase();
+    let mut matched = false;
+    for pattern in patterns {
+        let pattern = pattern.to_ascii_lowercase();
+        if let Some(negated) = pattern.strip_prefix('!') {
+            if glob_match(negated.as_bytes(), host.as_bytes()) {
+                return false;
+            }
+        } else if glob_match(pattern.as_bytes(), host.as_bytes()) {
+            matched = true;
+        }
+    }
+    matched
+}
+
+/// 支持 `*` 和 `?` 通配符的匹配
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((b'*', rest)) => (0..=text.len()).any(|skip| glob_match(rest, &text[skip..])),
+        Some((b'?', rest)) => !text.is_empty() && glob_match(rest, &text[1..]),
+        Some((c, rest)) => text.first() == Some(c) && glob_match(rest, &text[1..]),
+    }
+}
+
+/// 从 `ssh://user@host:port/path` 或 `user@host:path` 形式的地址中取出主机名
+pub fn ssh_host(url: &str) -> Option<String> {
+    if url.contains("://") {
+        return url::Url::parse(url).ok()?.host_str().map(str::to_string);
+    }
+    let (authority, _) = url.split_once(':')?;
+    let host = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    (!host.is_empty()).then(|| host.to_string())
+}
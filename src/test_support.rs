@@ -0,0 +1,141 @@
+//! 测试用的 git 仓库和 Cargo 项目夹具
+
+use git2::{Repository, Signature};
+use std::fs;
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 工作目录是进程级状态，依赖它的测试需要串行执行
+static CWD_LOCK: Mutex<()> = Mutex::new(());
+
+/// 以 `dir` 为工作目录执行 `f`，结束后恢复原来的工作目录
+pub fn in_dir<T>(dir: &Path, f: impl FnOnce() -> T) -> T {
+    let _guard = CWD_LOCK
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    let previous = std::env::current_dir().unwrap();
+    std::env::set_current_dir(dir).unwrap();
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f));
+    std::env::set_current_dir(previous).unwrap();
+    result.unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
+
+/// 在 `path` 创建一个只有 Cargo.toml 和空 `src/lib.rs` 的项目
+pub fn project(path: &Path, manifest: &str) {
+    fs::create_dir_all(path.join("src")).unwrap();
+    fs::write(path.join("Cargo.toml"), manifest).unwrap();
+    fs::write(path.join("src/lib.rs"), "").unwrap();
+}
+
+/// 在 `path` 创建一个包含 `members` 中各 crate 的 workspace
+pub fn workspace(path: &Path, members: &[&str]) {
+    let member_list: Vec<_> = members.iter().map(|m| format!("\"{m}\"")).collect();
+    fs::create_dir_all(path).unwrap();
+    fs::write(
+        path.join("Cargo.toml"),
+        format!("[workspace]\nmembers = [{}]\n", member_list.join(", ")),
+    )
+    .unwrap();
+    for member in members {
+        let name = Path::new(member).file_name().unwrap().to_string_lossy();
+        project(
+            &path.join(member),
+            &format!("[package]\nname = \"{name}\"\nversion = \"0.1.0\"\n"),
+        );
+    }
+}
+
+/// 在 `path` 创建一个名为 `name` 的 crate 仓库，包含 `commits` 个提交
+pub fn crate_repo(path: &Path, name: &str, commits: usize) -> Repository {
+    let repo = Repository::init(path).unwrap();
+    let signature = Signature::now("lpatch", "lpatch@example.com").unwrap();
+    fs::create_dir_all(path.join("src")).unwrap();
+    fs::write(
+        path.join("Cargo.toml"),
+        format!("[package]\nname = \"{name}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n"),
+    )
+    .unwrap();
+
+    for i in 0..commits {
+        fs::write(
+            path.join("src/lib.rs"),
+            format!("pub const N: u32 = {i};\n"),
+        )
+        .unwrap();
+        let mut index = repo.index().unwrap();
+        index
+            .add_all(["*"], git2::IndexAddOption::DEFAULT, None)
+            .unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let parent = repo.head().ok().map(|head| head.peel_to_commit().unwrap());
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            &format!("commit {i}"),
+            &tree,
+            &parent.iter().collect::<Vec<_>>(),
+        )
+        .unwrap();
+    }
+    drop(repo);
+    Repository::open(path).unwrap()
+}
+
+/// 通过 `git daemon` 以 `git://` 协议提供 `base_path` 下的仓库
+///
+/// libgit2 的本地传输不支持浅克隆，需要走网络协议
+pub struct GitDaemon {
+    child: Child,
+    port: u16,
+}
+
+impl GitDaemon {
+    pub fn start(base_path: &Path) -> Self {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        // 直接启动 git-daemon：`git daemon` 会派生子进程，结束包装进程后守护进程仍在运行
+        let exec_path = Command::new("git")
+            .arg("--exec-path")
+            .output()
+            .expect("git is required to serve test repositories");
+        let exec_path = String::from_utf8(exec_path.stdout).unwrap();
+        let child = Command::new(Path::new(exec_path.trim()).join("git-daemon"))
+            .arg("--export-all")
+            .arg("--reuseaddr")
+            .arg("--listen=127.0.0.1")
+            .arg(format!("--port={port}"))
+            .arg(format!("--base-path={}", base_path.display()))
+            .arg(base_path)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("git daemon is required to serve test repositories");
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while TcpStream::connect(("127.0.0.1", port)).is_err() {
+            assert!(Instant::now() < deadline, "git daemon did not start");
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        Self { child, port }
+    }
+
+    /// `base_path` 下名为 `name` 的仓库地址
+    pub fn url(&self, name: &str) -> String {
+        format!("git://127.0.0.1:{}/{name}", self.port)
+    }
+}
+
+impl Drop for GitDaemon {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
@@ -0,0 +1,72 @@
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+/// 记录各阶段耗时
+#[derive(Debug, Default)]
+pub struct PhaseTimings {
+    enabled: bool,
+    phases: Vec<(&'static str, Duration)>,
+}
+
+impl PhaseTimings {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            phases: Vec::new(),
+        }
+    }
+
+    /// 记录从 `start` 到现在的耗时，同名阶段会累加
+    pub fn record(&mut self, phase: &'static str, start: Instant) {
+        if !self.enabled {
+            return;
+        }
+
+        let elapsed = start.elapsed();
+        if let Some((_, total)) = self.phases.iter_mut().find(|(name, _)| *name == phase) {
+            *total += elapsed;
+        } else {
+            self.phases.push((phase, elapsed));
+        }
+    }
+
+    /// 各阶段耗时汇总，未启用或没有记录时为 `None`
+    pub fn summary(&self) -> Option<Timings> {
+        if !self.enabled || self.phases.is_empty() {
+            return None;
+        }
+
+        let total: Duration = self.phases.iter().map(|(_, d)| *d).sum();
+        Some(Timings {
+            phases: self
+                .phases
+                .iter()
+                .map(|(phase, duration)| PhaseTiming {
+                    phase,
+                    seconds: duration.as_secs_f64(),
+                })
+                .collect(),
+            total_seconds: total.as_secs_f64(),
+        })
+    }
+}
+
+/// `--trace-timings` 的汇总结果
+#[derive(Debug, Clone, Serialize)]
+pub struct Timings {
+    pub phases: Vec<PhaseTiming>,
+    pub total_seconds: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PhaseTiming {
+    pub phase: &'static str,
+    pub seconds: f64,
+}
+
+/// 非 dry-run 时单独输出的耗时报告
+#[derive(Debug, Serialize)]
+pub struct TimingsReport {
+    pub name: String,
+    pub timings: Timings,
+}
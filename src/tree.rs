@@ -0,0 +1,146 @@
+use crate::cargo_toml::{CargoToml, DependencyType};
+use crate::config::CargoConfig;
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::IsTerminal;
+use std::path::Path;
+use termtree::Tree;
+
+/// 补丁依赖树中的一个节点
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PatchTreeNode {
+    /// 已打补丁的 crate，继续展开其依赖
+    Patched {
+        name: String,
+        path: String,
+        /// 在祖先中已出现过，不再展开
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        cycle: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+        dependencies: Vec<PatchTreeNode>,
+    },
+    /// 未打补丁的依赖
+    Dependency { name: String },
+    /// 路径依赖，无法通过 [patch] 替换
+    Path { name: String, path: String },
+}
+
+/// 构建已打补丁 crate 之间的依赖树
+///
+/// 每个补丁是深度 0 的节点，向下展开 `depth` 层依赖；只有同样被打了补丁的依赖会继续展开
+pub fn build_patch_tree(cargo_config: &CargoConfig, depth: usize) -> Vec<PatchTreeNode> {
+    let patched: BTreeMap<String, String> = cargo_config
+        .list_patches()
+        .into_iter()
+        .map(|(_, name, path)| (name, path))
+        .collect();
+
+    patched
+        .keys()
+        .map(|name| patched_node(name, &patched, depth, &mut Vec::new()))
+        .collect()
+}
+
+fn patched_node(
+    name: &str,
+    patched: &BTreeMap<String, String>,
+    depth: usize,
+    ancestors: &mut Vec<String>,
+) -> PatchTreeNode {
+    let path = &patched[name];
+    let mut cycle = false;
+    let mut error = None;
+    let mut dependencies = Vec::new();
+
+    // 依赖环（例如互相作为 dev-dependency）只展开一次
+    if depth > 0 && ancestors.iter().any(|a| a == name) {
+        cycle = true;
+    } else if depth > 0 {
+        match load_dependencies(&CargoConfig::resolve_patch_path(path)) {
+            Ok(deps) => {
+                ancestors.push(name.to_string());
+                for (dep_name, dep_type) in deps {
+                    dependencies.push(if patched.contains_key(&dep_name) {
+                        patched_node(&dep_name, patched, depth - 1, ancestors)
+                    } else if let DependencyType::Path { path } = dep_type {
+                        PatchTreeNode::Path {
+                            name: dep_name,
+                            path,
+                        }
+                    } else {
+                        PatchTreeNode::Dependency { name: dep_name }
+                    });
+                }
+                ancestors.pop();
+            }
+            Err(e) => error = Some(format!("failed to read Cargo.toml: {e}")),
+        }
+    }
+
+    PatchTreeNode::Patched {
+        name: name.to_string(),
+        path: path.clone(),
+        cycle,
+        error,
+        dependencies,
+    }
+}
+
+/// 以树形文本显示，输出到终端时弱化不可展开的节点
+pub fn render(nodes: &[PatchTreeNode]) -> Tree<String> {
+    let dim = std::io::stdout().is_terminal();
+    let mut root = Tree::new("patches".to_string());
+    for node in nodes {
+        root.push(render_node(node, dim));
+    }
+    root
+}
+
+fn render_node(node: &PatchTreeNode, dim: bool) -> Tree<String> {
+    match node {
+        PatchTreeNode::Patched {
+            name,
+            path,
+            cycle,
+            error,
+            dependencies,
+        } => {
+            let mut tree = Tree::new(format!("{name} (patched: {path})"));
+            if *cycle {
+                tree.root.push_str(" (cycle)");
+            }
+            if let Some(error) = error {
+                tree.push(styled(error, dim));
+            }
+            for dependency in dependencies {
+                tree.push(render_node(dependency, dim));
+            }
+            tree
+        }
+        PatchTreeNode::Dependency { name } => Tree::new(name.clone()),
+        PatchTreeNode::Path { name, path } => {
+            styled(&format!("{name} (path: {path}, not patchable)"), dim)
+        }
+    }
+}
+
+/// 读取补丁目录中 Cargo.toml 的依赖，按名称排序去重
+fn load_dependencies(crate_path: &Path) -> Result<BTreeMap<String, DependencyType>> {
+    let cargo_toml = CargoToml::load_from_path(&crate_path.join("Cargo.toml"))?;
+    Ok(cargo_toml
+        .get_all_dependencies()
+        .into_iter()
+        .map(|dep| (dep.name, dep.dep_type))
+        .collect())
+}
+
+fn styled(text: &str, dim: bool) -> Tree<String> {
+    if dim {
+        Tree::new(format!("\x1b[2m{text}\x1b[0m"))
+    } else {
+        Tree::new(text.to_string())
+    }
+}
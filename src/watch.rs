@@ -0,0 +1,186 @@
+use anyhow::{anyhow, Context, Result};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use crate::git::GitOperations;
+use crate::provenance;
+
+/// 解析时间间隔，支持 `30s`、`15m`、`1h`、`1d` 以及纯秒数
+pub fn parse_interval(s: &str) -> Result<Duration> {
+    let s = s.trim();
+    let (value, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(idx) => s.split_at(idx),
+        None => (s, "s"),
+    };
+
+    let value: u64 = value
+        .parse()
+        .map_err(|_| anyhow!("Invalid interval '{}', expected e.g. 30s, 15m, 1h", s))?;
+    let seconds = match unit {
+        "s" => value,
+        "m" => value * 60,
+        "h" => value * 60 * 60,
+        "d" => value * 60 * 60 * 24,
+        _ => {
+            return Err(anyhow!(
+                "Invalid interval unit '{}', expected s, m, h or d",
+                unit
+            ))
+        }
+    };
+
+    if seconds == 0 {
+        return Err(anyhow!("Interval must be greater than zero"));
+    }
+    Ok(Duration::from_secs(seconds))
+}
+
+/// 监视上游仓库的新 tag
+///
+/// 以本地克隆中已有的 tag 作为基准；`daemon` 为 false 时只检查一次
+pub async fn watch_upstream(
+    crate_name: &str,
+    clone_path: &Path,
+    interval: Duration,
+    on_release: Option<&str>,
+    daemon: bool,
+) -> Result<()> {
+    let git_ops = GitOperations::new();
+    let mut known: BTreeSet<String> = git_ops.list_tags(clone_path)?.into_iter().collect();
+
+    info!(
+        "👀 Watching upstream of '{}' for new tags ({} known)",
+        crate_name,
+        known.len()
+    );
+
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+
+        match git_ops.list_remote_tags(clone_path) {
+            Ok(remote_tags) => {
+                let new_tags: Vec<String> = remote_tags
+                    .into_iter()
+                    .filter(|tag| !known.contains(tag))
+                    .collect();
+
+                if new_tags.is_empty() {
+                    debug!("No new tags for '{crate_name}'");
+                }
+                for tag in new_tags {
+                    info!("🎉 New release of '{crate_name}' available: {tag}");
+                    if let Some(script) = on_release {
+                        run_release_hook(script, crate_name, &tag);
+                    }
+                    known.insert(tag);
+                }
+            }
+            Err(e) if daemon => warn!("⚠️  Failed to list upstream tags: {e}"),
+            Err(e) => return Err(e),
+        }
+
+        if !daemon {
+            return Ok(());
+        }
+    }
+}
+
+/// 执行用户指定的发布钩子脚本，通过环境变量传递 crate 名称和 tag
+fn run_release_hook(script: &str, crate_name: &str, tag: &str) {
+    let mut command = if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", script]);
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.args(["-c", script]);
+        command
+    };
+
+    let status = command
+        .env("LPATCH_CRATE", crate_name)
+        .env("LPATCH_TAG", tag)
+        .status()
+        .with_context(|| format!("Failed to run release hook '{script}'"));
+
+    match status {
+        Ok(status) if status.success() => info!("🪝 Release hook finished"),
+        Ok(status) => warn!("⚠️  Release hook exited with {status}"),
+        Err(e) => warn!("⚠️  {e}"),
+    }
+}
+
+/// 周期性地拉取补丁仓库并报告新的提交
+///
+/// 工作区有未提交修改时跳过本轮更新；`check` 为 true 时在有更新后运行 `cargo check`
+pub async fn watch_pull(
+    crate_name: &str,
+    clone_path: &Path,
+    interval: Duration,
+    check: bool,
+    project_dir: PathBuf,
+) -> Result<()> {
+    let git_ops = GitOperations::new();
+
+    info!(
+        "👀 Watching '{}' every {}s (Ctrl+C to stop)",
+        crate_name,
+        interval.as_secs()
+    );
+
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+
+        if git_ops.is_dirty(clone_path).unwrap_or(false) {
+            warn!("⚠️  '{crate_name}' has uncommitted changes, skipping this update");
+            continue;
+        }
+
+        let before = git_ops.upstream_commit(clone_path)?;
+        if let Err(e) = git_ops.pull(clone_path) {
+            warn!("⚠️  Failed to update '{crate_name}': {e}");
+            continue;
+        }
+        let after = git_ops.upstream_commit(clone_path)?;
+
+        let (Some(before), Some(after)) = (before, after) else {
+            continue;
+        };
+        if before == after {
+            debug!("No new commits for '{crate_name}'");
+            continue;
+        }
+
+        let commits = git_ops.commits_between(clone_path, before, after)?;
+        info!("📥 {} new commits for '{}':", commits.len(), crate_name);
+        for commit in &commits {
+            info!("  {} {}", &commit.id[..7], commit.summary);
+        }
+        if let Err(e) = provenance::record_update(crate_name, before, after, &commits) {
+            warn!("⚠️  Failed to record update for '{crate_name}': {e}");
+        }
+
+        if check {
+            run_cargo_check(&project_dir);
+        }
+    }
+}
+
+/// 在项目目录中运行 `cargo check`，验证补丁更新后仍能编译
+fn run_cargo_check(project_dir: &Path) {
+    info!("🔍 Running cargo check in {}...", project_dir.display());
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    match Command::new(cargo)
+        .arg("check")
+        .current_dir(project_dir)
+        .status()
+    {
+        Ok(status) if status.success() => info!("✅ cargo check passed"),
+        Ok(status) => warn!("❌ cargo check failed ({status})"),
+        Err(e) => warn!("⚠️  Failed to run cargo check: {e}"),
+    }
+}
@@ -7,6 +7,8 @@ use std::path::{Path, PathBuf};
 #[derive(Debug, Deserialize)]
 pub struct WorkspaceConfig {
     pub members: Option<Vec<String>>,
+    #[serde(rename = "default-members")]
+    pub default_members: Option<Vec<String>>,
     pub exclude: Option<Vec<String>>,
     #[serde(flatten)]
     pub _other: std::collections::HashMap<String, toml::Value>,
@@ -81,28 +83,31 @@ impl WorkspaceDetector {
     ) -> Result<PathBuf> {
         let empty_vec = vec![];
         let members = workspace.members.as_ref().unwrap_or(&empty_vec);
+        let default_members = workspace.default_members.as_ref().unwrap_or(&empty_vec);
         let exclude = workspace.exclude.as_ref().unwrap_or(&empty_vec);
 
         info!("  📂 Workspace members: {members:?}");
+        if !default_members.is_empty() {
+            info!("  ⭐ Default members: {default_members:?}");
+        }
         if !exclude.is_empty() {
             info!("  🚫 Excluded: {exclude:?}");
         }
 
-        // 收集所有潜在的 crate 路径
+        // 收集所有潜在的 crate 路径，default-members 优先搜索
         let mut candidate_paths = Vec::new();
 
-        for member in members {
+        for member in default_members.iter().chain(members) {
             let member_paths = Self::expand_glob_pattern(repo_path, member)?;
             candidate_paths.extend(member_paths);
         }
 
         // 过滤掉被排除的路径
-        for exclude_pattern in exclude {
-            let exclude_paths = Self::expand_glob_pattern(repo_path, exclude_pattern)?;
-            candidate_paths.retain(|path| !exclude_paths.contains(path));
-        }
+        Self::apply_excludes(repo_path, &mut candidate_paths, exclude)?;
 
-        // 在候选路径中查找目标 crate
+        // 在候选路径中查找目标 crate（同一路径可能同时出现在两个列表中）
+        let mut seen = std::collections::HashSet::new();
+        candidate_paths.retain(|path| seen.insert(path.clone()));
         for candidate_path in candidate_paths {
             if Self::is_target_crate(&candidate_path, crate_name)? {
                 info!(
@@ -120,39 +125,38 @@ impl WorkspaceDetector {
         ))
     }
 
-    /// 展开 glob 模式（简单实现）
+    /// 展开 glob 模式，支持 `*`、`?`、`[...]` 以及递归的 `**`，只返回目录
     fn expand_glob_pattern(base_path: &Path, pattern: &str) -> Result<Vec<PathBuf>> {
-        let mut paths = Vec::new();
+        let escaped_base = glob::Pattern::escape(&base_path.to_string_lossy());
+        let full_pattern = format!("{}/{}", escaped_base, pattern.trim_end_matches('/'));
 
-        if pattern.contains('*') {
-            // 处理通配符模式，如 "crates/*"
-            let pattern_path = base_path.join(pattern);
-            let parent = pattern_path.parent().unwrap_or(base_path);
-
-            if parent.exists() && parent.is_dir() {
-                for entry in fs::read_dir(parent)? {
-                    let entry = entry?;
-                    let path = entry.path();
-
-                    if path.is_dir() {
-                        // 简单的通配符匹配：只处理 "dir/*" 的情况
-                        if pattern.ends_with("/*") {
-                            paths.push(path);
-                        }
-                    }
-                }
-            }
-        } else {
-            // 直接路径
-            let direct_path = base_path.join(pattern);
-            if direct_path.exists() {
-                paths.push(direct_path);
+        let mut paths = Vec::new();
+        let entries = glob::glob(&full_pattern)
+            .with_context(|| format!("Invalid workspace glob pattern '{pattern}'"))?;
+        for entry in entries {
+            match entry {
+                Ok(path) if path.is_dir() => paths.push(path),
+                Ok(_) => {}
+                Err(e) => debug!("Skipping unreadable path while expanding '{pattern}': {e}"),
             }
         }
-
         Ok(paths)
     }
 
+    /// 去掉被 exclude 模式匹配到的路径及其子目录
+    fn apply_excludes(
+        base_path: &Path,
+        candidate_paths: &mut Vec<PathBuf>,
+        exclude: &[String],
+    ) -> Result<()> {
+        let mut excluded = Vec::new();
+        for exclude_pattern in exclude {
+            excluded.extend(Self::expand_glob_pattern(base_path, exclude_pattern)?);
+        }
+        candidate_paths.retain(|path| !excluded.iter().any(|ex| path.starts_with(ex)));
+        Ok(())
+    }
+
     /// 检查指定路径是否包含目标 crate
     fn is_target_crate(path: &Path, crate_name: &str) -> Result<bool> {
         let cargo_toml_path = path.join("Cargo.toml");
@@ -203,10 +207,7 @@ impl WorkspaceDetector {
             }
 
             // 过滤排除的路径
-            for exclude_pattern in exclude {
-                let exclude_paths = Self::expand_glob_pattern(repo_path, exclude_pattern)?;
-                candidate_paths.retain(|path| !exclude_paths.contains(path));
-            }
+            Self::apply_excludes(repo_path, &mut candidate_paths, exclude)?;
 
             // 获取每个 crate 的名称
             for candidate_path in candidate_paths {
@@ -225,7 +226,7 @@ impl WorkspaceDetector {
     }
 
     /// 获取指定路径的 crate 名称
-    fn get_crate_name(path: &Path) -> Result<String> {
+    pub fn get_crate_name(path: &Path) -> Result<String> {
         let cargo_toml_path = path.join("Cargo.toml");
 
         let content = fs::read_to_string(&cargo_toml_path)